@@ -22,12 +22,18 @@ fn result(l: LuaState) -> Result<i32, ResultError> {
 	}
 }
 
+// Panics are caught and raised as lua errors, like "panicked at src/lib.rs:28:2: Oh no!"
+#[lua_function]
+fn do_panic(msg: String) {
+	panic!("{msg}");
+}
+
 #[gmod_open]
 fn open(l: LuaState) -> i32 {
 	printgm!(l, "Loaded exception module!");
 
 	let lib = reg! [
-		// "panic" => panic, (Soon™️)
+		"panic" => do_panic,
 		"result" => result
 	];
 
@@ -1,133 +1,477 @@
 use proc_macro::TokenStream;
 use quote::{quote, ToTokens};
 
-use syn::{parse_macro_input, parse_quote, spanned::Spanned, FnArg, ItemFn, ReturnType, Type};
+mod userdata;
 
-fn handle_gmod(item: TokenStream, export: Option<&str>) -> TokenStream {
-	let mut returns_result: Option<&Box<Type>> = None;
+use syn::{
+	parse_macro_input, parse_quote, spanned::Spanned, AttributeArgs, DeriveInput, FnArg, GenericArgument, Ident, ItemFn, ItemImpl, Lit, LitStr, Meta,
+	MetaNameValue, NestedMeta, PatType, PathArguments, ReturnType, Type,
+};
 
-	let mut ast = parse_macro_input!(item as ItemFn);
+/// Whether the type is rglua's LuaState, going by the last path segment so any path to it works.
+fn is_lua_state(ty: &Type) -> bool {
+	match ty {
+		Type::Path(p) => p.qself.is_none() && p.path.segments.last().is_some_and(|s| s.ident == "LuaState"),
+		_ => false,
+	}
+}
 
-	if ast.sig.asyncness.is_some() {
-		return syn::Error::new(ast.sig.span(), "Cannot be async").into_compile_error().into();
+/// Whether the type is ``&mut ModuleContext``, going by the last path segment like [is_lua_state].
+fn is_module_context(ty: &Type) -> bool {
+	match ty {
+		Type::Reference(r) if r.mutability.is_some() => {
+			matches!(&*r.elem, Type::Path(p) if p.qself.is_none() && p.path.segments.last().is_some_and(|s| s.ident == "ModuleContext"))
+		}
+		_ => false,
 	}
+}
 
-	if ast.sig.constness.is_some() {
-		return syn::Error::new(ast.sig.span(), "Cannot be const").into_compile_error().into();
+fn is_i32(ty: &Type) -> bool {
+	matches!(ty, Type::Path(p) if p.qself.is_none() && p.path.is_ident("i32"))
+}
+
+/// If the type is a Result, returns the type of its Ok variant.
+fn result_ok_type(ty: &Type) -> Option<&Type> {
+	let Type::Path(p) = ty else { return None };
+	let last = p.path.segments.last()?;
+	if last.ident != "Result" {
+		return None;
 	}
 
-	if ast.sig.inputs.len() != 1 {
-		return syn::Error::new(ast.sig.span(), "Must have one parameter, being the Lua state (rglua::lua::LuaState)").into_compile_error().into();
+	match &last.arguments {
+		PathArguments::AngleBracketed(args) => args.args.iter().find_map(|a| match a {
+			GenericArgument::Type(t) => Some(t),
+			_ => None,
+		}),
+		_ => None,
 	}
+}
 
-	if let ReturnType::Type(_, ty) = &ast.sig.output {
-		let mut ret = ty.to_token_stream().to_string();
-		if ret.starts_with("Result < i32") | ret.starts_with("std :: result :: Result < i32") {
-			ret.retain(|c| !c.is_whitespace());
-			returns_result = Some(ty);
-		} else {
-			if ret.as_str() != "i32" {
-				return syn::Error::new(ast.sig.output.span(), "Exported function must return i32 or Result<i32, E>").into_compile_error().into();
+/// Arguments given to the attributes, like ``#[lua_function(name = "foo", catch_unwind = false)]``.
+struct Options {
+	name: Option<LitStr>,
+	lib: Option<LitStr>,
+	catch_unwind: bool,
+}
+
+fn parse_options(args: AttributeArgs, allow_register: bool) -> syn::Result<Options> {
+	let mut opts = Options {
+		name: None,
+		lib: None,
+		catch_unwind: true,
+	};
+
+	for arg in args {
+		match arg {
+			NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, lit: Lit::Str(s), .. }))
+				if allow_register && (path.is_ident("name") || path.is_ident("lib")) =>
+			{
+				if s.value().is_empty() || s.value().contains('\0') {
+					return Err(syn::Error::new(s.span(), "Must be a non-empty string without null bytes"));
+				}
+
+				if path.is_ident("name") {
+					opts.name = Some(s);
+				} else {
+					opts.lib = Some(s);
+				}
 			}
-		}
-	} else {
-		return syn::Error::new(ast.sig.output.span(), "Exported function must return i32 or Result<i32, E>").into_compile_error().into();
-	}
-
-	let lua_shared_param;
-	let lua_shared_ty;
-	// Make sure parameter is a LuaState
-	match ast.sig.inputs.first().unwrap() {
-		FnArg::Receiver(_) => return syn::Error::new(ast.sig.inputs.span(), "Parameter cannot be self").into_compile_error().into(),
-		FnArg::Typed(arg) => {
-			// In the future this could check if it is *c_void as well.
-			match arg.ty.to_token_stream().to_string().as_str() {
-				"LuaState" | "rglua :: lua :: LuaState" => (),
-				a => return syn::Error::new(arg.ty.span(), format!("Parameter must be rglua::lua::LuaState. Got {a}")).to_compile_error().into(),
+			NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, lit: Lit::Bool(b), .. })) if path.is_ident("catch_unwind") => {
+				opts.catch_unwind = b.value;
+			}
+			other => {
+				let expected = if allow_register {
+					"Expected name = \"..\", lib = \"..\" or catch_unwind = bool"
+				} else {
+					"Expected catch_unwind = bool"
+				};
+				return Err(syn::Error::new(other.span(), expected));
 			}
+		}
+	}
 
-			lua_shared_ty = &arg.ty;
+	Ok(opts)
+}
+
+/// Name and type of the lua state parameter.
+type StateParam<'a> = (&'a Ident, &'a Type);
+
+/// Splits parameters into the lua state, if the first one is a LuaState, and the arguments after it.
+fn split_params(params: Vec<&PatType>) -> syn::Result<(Option<StateParam<'_>>, Vec<&Type>)> {
+	let mut state = None;
+	let mut args = vec![];
 
+	for (i, arg) in params.into_iter().enumerate() {
+		if i == 0 && is_lua_state(&arg.ty) {
 			match *arg.pat {
-				syn::Pat::Ident(ref i) => {
-					lua_shared_param = &i.ident;
-				}
-				syn::Pat::Wild(_) => {
-					return syn::Error::new(arg.pat.span(), "Parameter must be named. Try _foo").to_compile_error().into();
-				}
-				_ => return syn::Error::new(arg.pat.span(), "Parameter must be in 'ident: ty' format").to_compile_error().into(),
+				syn::Pat::Ident(ref i) => state = Some((&i.ident, &*arg.ty)),
+				syn::Pat::Wild(_) => return Err(syn::Error::new(arg.pat.span(), "Parameter must be named. Try _foo")),
+				_ => return Err(syn::Error::new(arg.pat.span(), "Parameter must be in 'ident: ty' format")),
+			}
+		} else {
+			if is_lua_state(&arg.ty) {
+				return Err(syn::Error::new(arg.ty.span(), "The Lua state must be the first parameter"));
 			}
+
+			args.push(&*arg.ty);
 		}
 	}
 
+	Ok((state, args))
+}
+
+/// Whether a function pushes its returns itself and returns how many, and whether it returns a Result.
+/// Functions that take the state and return i32 / Result<i32, E> manage the stack themselves.
+/// Anything else returns values that are pushed for them with ToLuaMulti.
+fn return_kind(output: &ReturnType, has_state: bool) -> (bool, bool) {
+	let (ret_ty, ok_ty) = match output {
+		ReturnType::Type(_, ty) => (Some(&**ty), result_ok_type(ty)),
+		ReturnType::Default => (None, None),
+	};
+
+	(has_state && ok_ty.or(ret_ty).is_some_and(is_i32), ok_ty.is_some())
+}
+
+fn handle_gmod(item: TokenStream, export: Option<&str>, catch_unwind: bool) -> TokenStream {
+	let mut ast = parse_macro_input!(item as ItemFn);
+
+	// Async lua functions are run by rglua::runtime, entrypoints can't wait for that.
+	let is_async = ast.sig.asyncness.is_some();
+	if is_async && export.is_some() {
+		return syn::Error::new(ast.sig.span(), "Cannot be async").into_compile_error().into();
+	}
+
+	if ast.sig.constness.is_some() {
+		return syn::Error::new(ast.sig.span(), "Cannot be const").into_compile_error().into();
+	}
+
 	// Make sure abi is either omitted, "C", or "C-unwind"
+	// Defaults to "C-unwind", as lua errors unwind through the function on 64 bit LuaJIT.
 	if let Some(abi) = &ast.sig.abi {
 		match abi.name.as_ref().unwrap().value().as_str() {
 			"C" | "C-unwind" => (),
 			_ => return syn::Error::new(abi.span(), "Only C or C-unwind are supported").to_compile_error().into(),
 		}
 	} else {
-		ast.sig.abi = Some(parse_quote!(extern "C"))
+		ast.sig.abi = Some(parse_quote!(extern "C-unwind"))
+	}
+
+	for attr in &ast.attrs {
+		if let Some(id) = attr.path.get_ident() {
+			if id == "no_mangle" {
+				return syn::Error::new(id.span(), "Using no_mangle is unnecessary on exported functions").into_compile_error().into();
+			}
+		}
+	}
+
+	let mut params = vec![];
+	for input in &ast.sig.inputs {
+		match input {
+			FnArg::Receiver(_) => return syn::Error::new(input.span(), "Parameter cannot be self").into_compile_error().into(),
+			FnArg::Typed(arg) => params.push(arg),
+		}
+	}
+
+	let (state, args) = match split_params(params) {
+		Ok(split) => split,
+		Err(why) => return why.into_compile_error().into(),
+	};
+
+	// #[gmod_open] can take a ModuleContext after the state, which gets gmod13_close generated to tear it down.
+	let with_context = export == Some("gmod13_open") && state.is_some() && args.len() == 1 && is_module_context(args[0]);
+	let args = if with_context { vec![] } else { args };
+
+	// The future outlives the call, so it can't borrow userdata from the stack.
+	if let Some(arg) = args.iter().find(|ty| is_async && matches!(ty, Type::Reference(_))) {
+		return syn::Error::new(arg.span(), "Async functions can't take references").into_compile_error().into();
+	}
+
+	// What an async function returns is always given back as values, once it's done.
+	let (returns_count, returns_result) = return_kind(&ast.sig.output, state.is_some() && !is_async);
+
+	if export.is_some() && (state.is_none() || !args.is_empty() || !returns_count) {
+		return syn::Error::new(
+			ast.sig.span(),
+			"Must have one parameter, being the Lua state (rglua::lua::LuaState), and return i32 or Result<i32, E>",
+		)
+		.into_compile_error()
+		.into();
+	}
+
+	// The context is torn down after the function, so it can still use what's in it.
+	if export == Some("gmod13_close") {
+		let (l, _) = state.expect("gmod_close takes the state");
+		let ret_ty = match &ast.sig.output {
+			ReturnType::Type(_, ty) => quote!(#ty),
+			ReturnType::Default => quote!(()),
+		};
+		let block = &ast.block;
+
+		ast.block = parse_quote!({
+			let __rglua_ret = (move || -> #ret_ty #block)();
+			rglua::context::close(#l);
+			__rglua_ret
+		});
+	}
+
+	// Functions collected with #[lua_function(name = "..", lib = "..")] are registered before the entrypoint runs.
+	// The main thread is remembered first, for references made from coroutines later,
+	// and string.dump before lua code gets to replace it, for stripping bytecode.
+	let register = (export == Some("gmod13_open")).then(|| {
+		let (l, _) = state.expect("gmod_open takes the state");
+		quote! {{
+			rglua::state::main_thread(#l);
+			rglua::bytecode::remember_string_dump(#l);
+			rglua::registry::register_all(#l);
+		}}
+	});
+
+	// Plain lua function, nothing to generate.
+	if returns_count && args.is_empty() && !returns_result && !catch_unwind && !is_async && !with_context {
+		if let Some(export) = export {
+			ast.sig.ident = quote::format_ident!("{}", export);
+		}
+
+		if let Some(register) = register {
+			ast.block.stmts.insert(0, parse_quote!(#register));
+		}
+
+		ast.attrs.push(parse_quote!(#[no_mangle]));
+		return ast.into_token_stream().into();
 	}
 
-	if let Some(ret) = returns_result {
-		// We don't need to change the name of the innter function, because the outer function will compile to
-		// gmod13_(open|close)
-		let inner_fn = &ast.sig.ident;
-		let inner_stmts = &ast.block.stmts;
-		let attrs = &ast.attrs;
+	// Otherwise the original function is kept as an inner function (with its attributes),
+	// wrapped by a lua function that converts its arguments and return values.
+	let inner_fn = &ast.sig.ident;
+	let outer_fn = match export {
+		Some(export) => quote::format_ident!("{}", export),
+		None => inner_fn.clone(),
+	};
+
+	let l = state.map_or_else(|| quote::format_ident!("__rglua_l"), |(ident, _)| ident.clone());
+	let lua_state_ty: Type = state.map_or_else(|| parse_quote!(rglua::lua::LuaState), |(_, ty)| ty.clone());
+
+	let wrapper = Wrapper {
+		l: l.clone(),
+		state_ty: lua_state_ty.clone(),
+		pass_state: state.is_some(),
+		receiver: None,
+		args,
+		returns_count,
+		returns_result,
+		catch_unwind,
+		is_async,
+		register,
+	};
+
+	let callee = if with_context {
+		quote! { (|#l: #lua_state_ty| #inner_fn(#l, &mut rglua::context::ModuleContext::__open(#l))) }
+	} else {
+		quote!(#inner_fn)
+	};
+	let trampoline = wrapper.trampoline(callee);
+
+	let close = with_context.then(|| {
+		quote! {
+			#[no_mangle]
+			pub extern "C-unwind" fn gmod13_close(#l: #lua_state_ty) -> i32 {
+				rglua::context::close(#l);
+				0
+			}
+		}
+	});
 
-		let inner = quote! {
+	let attrs = &ast.attrs;
+	let vis = &ast.vis;
+	let abi = &ast.sig.abi;
+	let mut inner = ast.sig.clone();
+	inner.abi = None;
+	let block = &ast.block;
+
+	quote! {
+		#[no_mangle]
+		#vis #abi fn #outer_fn(#l: #lua_state_ty) -> i32 {
 			#(#attrs)*
-			fn #inner_fn(#lua_shared_param: #lua_shared_ty) -> #ret {
-				#(#inner_stmts)*
+			#inner #block
+
+			#trampoline
+		}
+
+		#close
+	}
+	.into()
+}
+
+/// Generates the body of a lua function that calls a Rust function, converting its arguments and return values.
+/// Shared by ``#[lua_function]`` and ``#[lua_methods]``.
+pub(crate) struct Wrapper<'a> {
+	/// Name of the lua state in the generated code.
+	l: Ident,
+	state_ty: Type,
+	/// Whether the state is passed to the function, as its first argument (after self).
+	pass_state: bool,
+	/// Type of self and whether it is taken mutably, for methods.
+	receiver: Option<(&'a Type, bool)>,
+	args: Vec<&'a Type>,
+	/// Whether the function pushes its returns itself and returns how many (i32 / Result<i32, E>).
+	returns_count: bool,
+	returns_result: bool,
+	catch_unwind: bool,
+	/// Whether the function is async, so its future is given to rglua::runtime instead of being called.
+	is_async: bool,
+	/// Statements run before the function is called.
+	register: Option<proc_macro2::TokenStream>,
+}
+
+impl Wrapper<'_> {
+	/// Reads an argument at ``idx``. References to types are userdata, read with LuaUserData. Anything else uses FromLua.
+	/// Userdata can be borrowed for the whole call, as arguments stay on the stack until it returns.
+	fn convert_arg(&self, ty: &Type, idx: i32) -> proc_macro2::TokenStream {
+		let l = &self.l;
+		let conversion = match ty {
+			Type::Reference(r) if r.mutability.is_some() => {
+				let elem = &r.elem;
+				quote! { unsafe { <#elem as rglua::userdata::LuaUserData>::from_arg_mut(#l, #idx) } }
+			}
+			Type::Reference(r) => {
+				let elem = &r.elem;
+				quote! { unsafe { <#elem as rglua::userdata::LuaUserData>::from_arg(#l, #idx) } }
+			}
+			ty => quote! { <#ty as rglua::convert::FromLua>::from_lua(#l, #idx) },
+		};
+
+		quote! {
+			match #conversion {
+				Ok(v) => v,
+				Err(why) => return Err(rglua::__private::Raise::arg_error(#l, why)),
+			}
+		}
+	}
+
+	/// Binds an argument to ``ident``. Userdata references are borrow guards, which need to be mutable for ``&mut T``.
+	fn bind(ident: &Ident, ty: &Type, conversion: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+		match ty {
+			Type::Reference(r) if r.mutability.is_some() => quote! { let mut #ident = #conversion; },
+			_ => quote! { let #ident = #conversion; },
+		}
+	}
+
+	/// Passes an argument bound by [Wrapper::bind], reborrowing the guards of userdata references.
+	fn pass(ident: &Ident, ty: &Type) -> proc_macro2::TokenStream {
+		match ty {
+			Type::Reference(r) if r.mutability.is_some() => quote! { &mut *#ident },
+			Type::Reference(_) => quote! { &*#ident },
+			_ => quote! { #ident },
+		}
+	}
+
+	/// The trampoline and the code calling it, ``callee`` being the path to the function to call.
+	fn trampoline(&self, callee: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+		let l = &self.l;
+		let state_ty = &self.state_ty;
+
+		let mut idx = 0;
+		let receiver = self.receiver.map(|(ty, mutable)| {
+			idx += 1;
+			let ty: Type = if mutable { parse_quote!(&mut #ty) } else { parse_quote!(&#ty) };
+			let conversion = self.convert_arg(&ty, idx);
+			let ident = quote::format_ident!("__rglua_self");
+			(Self::bind(&ident, &ty, conversion), Self::pass(&ident, &ty))
+		});
+		let (receiver, self_arg) = receiver.map(|(bind, pass)| (bind, quote!(#pass,))).unzip();
+
+		let (bindings, arg_exprs): (Vec<_>, Vec<_>) = self
+			.args
+			.iter()
+			.enumerate()
+			.map(|(i, ty)| {
+				idx += 1;
+				let ident = quote::format_ident!("__rglua_arg{}", i);
+				(Self::bind(&ident, ty, self.convert_arg(ty, idx)), Self::pass(&ident, ty))
+			})
+			.unzip();
+
+		// The future outlives the call, so it gets the main thread instead of what may be a coroutine (see below).
+		let state_arg = self.pass_state.then(|| if self.is_async { quote!(__rglua_main,) } else { quote!(#l,) });
+
+		let mut call = quote! { #callee(#self_arg #state_arg #(#arg_exprs),*) };
+
+		// Panics are turned into lua errors instead of unwinding into lua.
+		// Functions that take the state can raise lua errors, which on some platforms can't unwind through catch_unwind,
+		// so panics in those abort there instead (see catch_panic_with_state).
+		// Async functions don't run anything until polled, where the runtime catches panics instead.
+		if self.catch_unwind && !self.is_async {
+			let catcher = if self.pass_state {
+				quote!(catch_panic_with_state)
+			} else {
+				quote!(catch_panic)
+			};
+
+			call = quote! {
+				match rglua::__private::#catcher(move || #call) {
+					Ok(ret) => ret,
+					Err(msg) => return Err(rglua::__private::Raise::error(#l, msg)),
+				}
+			};
+		}
+
+		let push = |val: proc_macro2::TokenStream| {
+			if self.returns_count {
+				val
+			} else {
+				quote! { rglua::convert::ToLuaMulti::push_multi(#val, #l) }
 			}
 		};
 
-		let resultant = quote! {
-			match #inner_fn(#lua_shared_param) {
-				Err(why) => {
+		let body = if self.is_async {
+			let nargs = self.args.len() as i32;
+			let output = if self.returns_result {
+				quote! { #call.await.map_err(|why| why.to_string()) }
+			} else {
+				quote! { Ok::<_, String>(#call.await) }
+			};
+
+			let main = self.pass_state.then(|| quote! { let __rglua_main = rglua::state::main_thread(#l); });
+
+			quote! {{
+				#main
+				rglua::runtime::__resolve(#l, #nargs, async move { #output })
+			}}
+		} else if self.returns_result {
+			let ok = push(quote!(ret));
+			quote! {
+				match #call {
+					Ok(ret) => Ok(#ok),
 					// Your error must implement display / .to_string().
 					// I'd recommend ``thiserror``.
-					let err = why.to_string();
-					let err = cstr!(err);
-					rglua::lua::luaL_error(#lua_shared_param, cstr!("%s"), err.as_ptr());
-				},
-				Ok(n) => { return n }
+					Err(why) => Err(rglua::__private::Raise::error(#l, why.to_string())),
+				}
 			}
+		} else {
+			let ok = push(call);
+			quote! { Ok(#ok) }
 		};
 
-		ast.block
-			.stmts
-			.insert(0, syn::parse2(inner).expect("Error parsing inner fn"));
-		ast.block
-			.stmts
-			.insert(1, syn::parse2(resultant).expect("Error parsing resultant"));
-		ast.block.stmts.truncate(2);
-
-		ast.sig.output = ReturnType::Type(Default::default(), Box::new(parse_quote!(i32)));
+		let register = &self.register;
 
-		// Prevent attributes from going onto the generated extern "C" function
-		// They will be applied to the inner function.
-		ast.attrs.clear();
-	}
+		// Raising a lua error never returns, skipping destructors of anything still alive (or aborting, if it unwinds through catch_unwind).
+		// So all of the Rust work happens in the trampoline, which leaves the error on the lua stack and returns,
+		// dropping everything before the outer function raises it.
+		quote! {
+			fn __rglua_trampoline(#l: #state_ty) -> Result<i32, rglua::__private::Raise> {
+				#receiver
+				#(#bindings)*
 
-	if let Some(export) = export {
-		ast.sig.ident = quote::format_ident!("{}", export);
-	}
+				#register
+				#body
+			}
 
-	for attr in &ast.attrs {
-		if let Some(id) = attr.path.get_ident() {
-			if id == "no_mangle" {
-				return syn::Error::new(id.span(), "Using no_mangle is unnecessary on exported functions").into_compile_error().into();
+			match __rglua_trampoline(#l) {
+				Ok(n) => n,
+				Err(raise) => raise.raise(#l),
 			}
 		}
 	}
-
-	// Attributes will go onto the inner function
-	ast.attrs.push(parse_quote!(#[no_mangle]));
-
-	ast.into_token_stream().into()
 }
 
 #[proc_macro_attribute]
@@ -135,6 +479,12 @@ fn handle_gmod(item: TokenStream, export: Option<&str>) -> TokenStream {
 ///
 /// Normally you would not be able to return types other than i32 through to gmod13_open,
 /// this is still true, but this proc-macro allows it through unwrapping the result and containing attributes on a hidden generated function.
+///
+/// Functions registered with ``#[lua_function(name = "..", lib = "..")]`` are registered before this runs.
+/// Panics are caught like with ``#[lua_function]``, ``#[gmod_open(catch_unwind = false)]`` opts out.
+///
+/// It can take a ``&mut ModuleContext`` after the state, to record what it sets up.
+/// Then ``gmod13_close`` is generated to tear that down, so it can't be used with ``#[gmod_close]`` (see ``rglua::context``).
 /// # Examples
 /// ```rust
 /// use rglua::prelude::*;
@@ -147,8 +497,11 @@ fn handle_gmod(item: TokenStream, export: Option<&str>) -> TokenStream {
 ///     Ok(0)
 /// }
 /// ```
-pub fn gmod_open(_attr: TokenStream, item: TokenStream) -> TokenStream {
-	handle_gmod(item, Some("gmod13_open"))
+pub fn gmod_open(attr: TokenStream, item: TokenStream) -> TokenStream {
+	match parse_options(parse_macro_input!(attr as AttributeArgs), false) {
+		Ok(opts) => handle_gmod(item, Some("gmod13_open"), opts.catch_unwind),
+		Err(why) => why.into_compile_error().into(),
+	}
 }
 
 #[proc_macro_attribute]
@@ -156,6 +509,8 @@ pub fn gmod_open(_attr: TokenStream, item: TokenStream) -> TokenStream {
 ///
 /// Normally you would not be able to return types other than i32 through to gmod13_open,
 /// this is still true, but this proc-macro allows it through unwrapping the result and containing attributes on a hidden generated function.
+///
+/// Anything recorded in the ``rglua::context::ModuleContext`` of the state is torn down after this runs.
 /// # Examples
 /// ```rust
 /// use rglua::prelude::*;
@@ -167,14 +522,35 @@ pub fn gmod_open(_attr: TokenStream, item: TokenStream) -> TokenStream {
 ///     Ok(0)
 /// }
 /// ```
-pub fn gmod_close(_attr: TokenStream, item: TokenStream) -> TokenStream {
-	handle_gmod(item, Some("gmod13_close"))
+pub fn gmod_close(attr: TokenStream, item: TokenStream) -> TokenStream {
+	match parse_options(parse_macro_input!(attr as AttributeArgs), false) {
+		Ok(opts) => handle_gmod(item, Some("gmod13_close"), opts.catch_unwind),
+		Err(why) => why.into_compile_error().into(),
+	}
 }
 
 #[proc_macro_attribute]
 /// Creates a valid function to be passed down to lua.
-/// Note this function will not be registered automatically for you, you must use luaL_register or functions like lua_pushcfunction.
-/// This may change in the future or allow for something like #[lua_function(name = "foo", auto = true)]
+/// By default this function will not be registered automatically for you, you must use luaL_register or functions like lua_pushcfunction.
+/// Giving it a ``name`` and/or ``lib``, like ``#[lua_function(name = "foo", lib = "mylib")]``, registers it as ``mylib.foo`` when ``#[gmod_open]`` runs (see ``rglua::registry``).
+/// ``name`` defaults to the name of the function, and without ``lib`` it is registered as a global.
+/// Registering two functions with the same name in the same library is a compile error within a module,
+/// and otherwise reported with ``ErrorNoHalt`` when registering, where only the first is registered.
+///
+/// The function can take the Lua state (rglua::lua::LuaState) as its first parameter, and any number of arguments after it.
+/// Arguments are read with ``rglua::convert::FromLua``, raising ``bad argument #n to 'name' (number expected, got nil)`` if one doesn't convert.
+/// Use ``Option`` for optional arguments.
+///
+/// Returned values are pushed with ``rglua::convert::ToLuaMulti``, so a tuple returns multiple values and ``()`` none.
+/// Returning ``Err`` raises it as a lua error instead, so the error must implement Display.
+/// The exception is functions taking the state and returning ``i32`` or ``Result<i32, E>``, which push their returns themselves and return how many they pushed.
+///
+/// Panics are caught and raised as lua errors with the panic message and location, instead of unwinding into lua.
+/// Functions that take the state can raise lua errors themselves, which on 64 bit unix can't pass through the catch,
+/// so a panic in one of those aborts the process there instead. Use ``#[lua_function(catch_unwind = false)]`` to skip this in hot functions.
+///
+/// It can also be an ``async fn``, which is run by ``rglua::runtime``. Its returns are given to a callback passed as the last argument, after its own,
+/// or to the coroutine it was called from once it's resumed. Its arguments can't be references.
 /// # Examples
 /// ```rust
 /// use rglua::prelude::*;
@@ -184,6 +560,148 @@ pub fn gmod_close(_attr: TokenStream, item: TokenStream) -> TokenStream {
 ///     std::fs::write("foo.txt", "bar")?;
 ///     Ok(0)
 /// }
-pub fn lua_function(_attr: TokenStream, item: TokenStream) -> TokenStream {
-	handle_gmod(item, None)
+///
+/// #[lua_function]
+/// fn add(a: f64, b: Option<f64>) -> (f64, bool) {
+///     let sum = a + b.unwrap_or(0.0);
+///     (sum, sum > 0.0)
+/// }
+/// ```
+pub fn lua_function(attr: TokenStream, item: TokenStream) -> TokenStream {
+	let Options { name, lib, catch_unwind } = match parse_options(parse_macro_input!(attr as AttributeArgs), true) {
+		Ok(opts) => opts,
+		Err(why) => return why.into_compile_error().into(),
+	};
+
+	let func = match syn::parse::<ItemFn>(item.clone()) {
+		Ok(func) => func.sig.ident,
+		Err(why) => return why.into_compile_error().into(),
+	};
+
+	let mut out: proc_macro2::TokenStream = handle_gmod(item, None, catch_unwind).into();
+
+	if name.is_some() || lib.is_some() {
+		let name = name.map_or_else(|| func.to_string(), |n| n.value());
+		let lib_tokens = match &lib {
+			Some(lib) => quote!(Some(#lib)),
+			None => quote!(None),
+		};
+
+		// Two functions registered under the same name in the same library of a module fail to compile with "defined multiple times".
+		// Ones in different modules or crates are caught by register_all.
+		let lib = lib.map_or_else(|| String::from("_G"), |l| l.value());
+		let dup_check = quote::format_ident!("__rglua_lua_function_{}_s_{}", encode_ident(&lib), encode_ident(&name));
+
+		out.extend(quote! {
+			const _: () = {
+				#[rglua::registry::linkme::distributed_slice(rglua::registry::LUA_FUNCTIONS)]
+				#[linkme(crate = rglua::registry::linkme)]
+				static REGISTRATION: rglua::registry::LuaFunction = rglua::registry::LuaFunction {
+					lib: #lib_tokens,
+					name: #name,
+					func: #func,
+				};
+			};
+
+			#[doc(hidden)]
+			#[allow(non_upper_case_globals)]
+			const #dup_check: () = ();
+		});
+	}
+
+	out.into()
+}
+
+#[proc_macro_derive(LuaUserData, attributes(lua))]
+/// Implements ``rglua::userdata::LuaUserData``, so the type can be pushed to lua as a userdata with its own metatable.
+/// The metatable is named after the type, or ``#[lua(name = "..")]``. Also implements ``ToLua``, so it can be returned from lua functions.
+///
+/// Give it methods with a ``#[lua_methods]`` impl block.
+/// Generic types aren't supported, and the type can't need more than 8 byte alignment.
+/// # Examples
+/// ```rust
+/// use rglua::prelude::*;
+/// #[derive(LuaUserData)]
+/// #[lua(name = "Point")]
+/// struct Point {
+///     x: f64,
+///     y: f64,
+/// }
+///
+/// #[lua_function(name = "Point")]
+/// fn new_point(x: f64, y: f64) -> Point {
+///     Point { x, y }
+/// }
+///
+/// #[lua_function]
+/// fn distance(a: &Point, b: &Point) -> f64 {
+///     ((a.x - b.x).powi(2) + (a.y - b.y).powi(2)).sqrt()
+/// }
+/// ```
+pub fn derive_lua_userdata(item: TokenStream) -> TokenStream {
+	match userdata::derive(parse_macro_input!(item as DeriveInput)) {
+		Ok(out) => out.into(),
+		Err(why) => why.into_compile_error().into(),
+	}
+}
+
+#[proc_macro_attribute]
+/// Exposes the methods of an impl block to lua, for a type deriving ``LuaUserData``.
+///
+/// Methods taking ``&self`` or ``&mut self`` are called from lua like ``obj:method(...)``,
+/// with arguments and returns converted like ``#[lua_function]`` (including taking the Lua state after self).
+/// Associated functions without self are left alone, and methods can be hidden with ``#[lua(skip)]``.
+///
+/// ``#[lua(name = "..")]`` renames a method, which is how metamethods like ``__add`` or ``__tostring`` are defined.
+/// ``#[lua(catch_unwind = false)]`` skips catching panics, like with ``#[lua_function]``.
+/// # Examples
+/// ```rust
+/// use rglua::prelude::*;
+/// #[derive(LuaUserData)]
+/// struct Point {
+///     x: f64,
+///     y: f64,
+/// }
+///
+/// #[lua_methods]
+/// impl Point {
+///     fn x(&self) -> f64 {
+///         self.x
+///     }
+///
+///     fn translate(&mut self, x: f64, y: f64) {
+///         self.x += x;
+///         self.y += y;
+///     }
+///
+///     #[lua(name = "__add")]
+///     fn add(&self, other: &Self) -> Self {
+///         Point { x: self.x + other.x, y: self.y + other.y }
+///     }
+/// }
+/// ```
+pub fn lua_methods(attr: TokenStream, item: TokenStream) -> TokenStream {
+	if !attr.is_empty() {
+		return syn::Error::new(proc_macro2::Span::call_site(), "Expected no arguments")
+			.into_compile_error()
+			.into();
+	}
+
+	match userdata::lua_methods(parse_macro_input!(item as ItemImpl)) {
+		Ok(out) => out.into(),
+		Err(why) => why.into_compile_error().into(),
+	}
+}
+
+/// Encodes a string into something usable in an identifier, without two different strings encoding to the same thing.
+fn encode_ident(s: &str) -> String {
+	let mut out = String::new();
+	for c in s.chars() {
+		match c {
+			'_' => out.push_str("__"),
+			c if c.is_ascii_alphanumeric() => out.push(c),
+			c => out.push_str(&format!("_x{:x}_", c as u32)),
+		}
+	}
+	out
 }
@@ -0,0 +1,216 @@
+//! ``#[derive(LuaUserData)]`` and ``#[lua_methods]``.
+use proc_macro2::{Group, TokenStream, TokenTree};
+use quote::{quote, ToTokens};
+use syn::{
+	parse_quote, spanned::Spanned, Attribute, DeriveInput, FnArg, ImplItem, ItemImpl, Lit, LitByteStr, LitStr, Meta, MetaNameValue,
+	NestedMeta, Type,
+};
+
+use crate::{return_kind, split_params, Wrapper};
+
+/// Options in ``#[lua(..)]`` attributes, on the type or its methods.
+#[derive(Default)]
+struct LuaAttr {
+	name: Option<LitStr>,
+	skip: bool,
+	catch_unwind: Option<bool>,
+}
+
+/// Takes the ``#[lua(..)]`` attributes out of ``attrs``, parsing them.
+fn take_lua_attrs(attrs: &mut Vec<Attribute>, method: bool) -> syn::Result<LuaAttr> {
+	let mut out = LuaAttr::default();
+	let mut err = None;
+
+	attrs.retain(|attr| {
+		if !attr.path.is_ident("lua") {
+			return true;
+		}
+
+		if let Err(why) = parse_lua_attr(attr, method, &mut out) {
+			err.get_or_insert(why);
+		}
+		false
+	});
+
+	match err {
+		Some(why) => Err(why),
+		None => Ok(out),
+	}
+}
+
+fn parse_lua_attr(attr: &Attribute, method: bool, out: &mut LuaAttr) -> syn::Result<()> {
+	let expected = if method {
+		"Expected name = \"..\", skip or catch_unwind = bool"
+	} else {
+		"Expected name = \"..\""
+	};
+
+	let Meta::List(list) = attr.parse_meta()? else {
+		return Err(syn::Error::new(attr.span(), expected));
+	};
+
+	for nested in list.nested {
+		match nested {
+			NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, lit: Lit::Str(s), .. })) if path.is_ident("name") => {
+				if s.value().is_empty() || s.value().contains('\0') {
+					return Err(syn::Error::new(s.span(), "Must be a non-empty string without null bytes"));
+				}
+				out.name = Some(s);
+			}
+			NestedMeta::Meta(Meta::Path(path)) if method && path.is_ident("skip") => out.skip = true,
+			NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, lit: Lit::Bool(b), .. }))
+				if method && path.is_ident("catch_unwind") =>
+			{
+				out.catch_unwind = Some(b.value);
+			}
+			other => return Err(syn::Error::new(other.span(), expected)),
+		}
+	}
+
+	Ok(())
+}
+
+/// Replaces ``Self`` with the actual type, as the generated lua functions are separate items that can't use it.
+fn replace_self(tokens: TokenStream, ty: &Type) -> TokenStream {
+	tokens
+		.into_iter()
+		.flat_map(|tt| match tt {
+			TokenTree::Ident(id) if id == "Self" => ty.to_token_stream(),
+			TokenTree::Group(g) => {
+				let mut new = Group::new(g.delimiter(), replace_self(g.stream(), ty));
+				new.set_span(g.span());
+				TokenTree::Group(new).into()
+			}
+			tt => tt.into(),
+		})
+		.collect()
+}
+
+pub(crate) fn derive(mut input: DeriveInput) -> syn::Result<TokenStream> {
+	if !input.generics.params.is_empty() || input.generics.where_clause.is_some() {
+		return Err(syn::Error::new(input.generics.span(), "LuaUserData can't be derived for generic types"));
+	}
+
+	let opts = take_lua_attrs(&mut input.attrs, false)?;
+	let ident = &input.ident;
+
+	let name = opts.name.map_or_else(|| ident.to_string(), |n| n.value());
+	let name = LitByteStr::new(format!("{name}\0").as_bytes(), ident.span());
+
+	Ok(quote! {
+		impl rglua::userdata::LuaUserData for #ident {
+			// Checked to be non-empty without null bytes in between by the macro.
+			const NAME: &'static ::std::ffi::CStr = unsafe { ::std::ffi::CStr::from_bytes_with_nul_unchecked(#name) };
+
+			fn methods() -> &'static [rglua::userdata::LuaMethod] {
+				// Shadowed by the const generated by #[lua_methods], if there is one.
+				#[allow(unused_imports)]
+				use rglua::userdata::__NoMethods as _;
+				Self::__RGLUA_METHODS
+			}
+		}
+
+		impl rglua::convert::ToLua for #ident {
+			fn push_to_lua(self, l: rglua::lua::LuaState) {
+				rglua::userdata::LuaUserData::push_userdata(self, l)
+			}
+		}
+
+		const _: () = assert!(
+			::std::mem::align_of::<#ident>() <= rglua::userdata::MAX_ALIGN,
+			"Type needs more alignment than lua gives userdata"
+		);
+	})
+}
+
+pub(crate) fn lua_methods(mut item: ItemImpl) -> syn::Result<TokenStream> {
+	if !item.generics.params.is_empty() || item.trait_.is_some() {
+		return Err(syn::Error::new(item.impl_token.span(), "Must be an inherent impl block for a type without generics"));
+	}
+
+	let self_ty = (*item.self_ty).clone();
+	let mut methods = vec![];
+
+	for impl_item in &mut item.items {
+		let ImplItem::Method(method) = impl_item else { continue };
+		let opts = take_lua_attrs(&mut method.attrs, true)?;
+
+		// Associated functions without self, like constructors, are left alone.
+		let Some(FnArg::Receiver(receiver)) = method.sig.inputs.first() else {
+			continue;
+		};
+
+		if opts.skip {
+			continue;
+		}
+
+		let sig = &method.sig;
+		if sig.asyncness.is_some() || sig.constness.is_some() || sig.unsafety.is_some() || !sig.generics.params.is_empty() {
+			return Err(syn::Error::new(
+				sig.span(),
+				"Methods exposed to lua can't be async, const, unsafe or generic. Skip this with #[lua(skip)]",
+			));
+		}
+
+		if receiver.reference.is_none() {
+			return Err(syn::Error::new(
+				receiver.span(),
+				"Methods exposed to lua take &self or &mut self. Skip this with #[lua(skip)]",
+			));
+		}
+
+		let mut params = vec![];
+		for input in sig.inputs.iter().skip(1) {
+			match input {
+				FnArg::Typed(arg) => params.push(arg),
+				FnArg::Receiver(_) => unreachable!("self is always first"),
+			}
+		}
+
+		let (state, args) = split_params(params)?;
+		let (returns_count, returns_result) = return_kind(&sig.output, state.is_some());
+
+		let args: Vec<Type> = args
+			.into_iter()
+			.map(|ty| syn::parse2(replace_self(ty.to_token_stream(), &self_ty)))
+			.collect::<syn::Result<_>>()?;
+
+		let wrapper = Wrapper {
+			l: quote::format_ident!("__rglua_l"),
+			state_ty: state.map_or_else(|| parse_quote!(rglua::lua::LuaState), |(_, ty)| ty.clone()),
+			pass_state: state.is_some(),
+			receiver: Some((&self_ty, receiver.mutability.is_some())),
+			args: args.iter().collect(),
+			returns_count,
+			returns_result,
+			catch_unwind: opts.catch_unwind.unwrap_or(true),
+			is_async: false,
+			register: None,
+		};
+
+		let method_ident = &sig.ident;
+		let trampoline = wrapper.trampoline(quote!(<#self_ty>::#method_ident));
+		let name = opts.name.map_or_else(|| method_ident.to_string(), |n| n.value());
+
+		methods.push(quote! {
+			rglua::userdata::LuaMethod {
+				name: #name,
+				func: {
+					extern "C-unwind" fn __rglua_method(__rglua_l: rglua::lua::LuaState) -> i32 {
+						#trampoline
+					}
+					__rglua_method
+				},
+			}
+		});
+	}
+
+	Ok(quote! {
+		#item
+
+		impl #self_ty {
+			#[doc(hidden)]
+			pub const __RGLUA_METHODS: &'static [rglua::userdata::LuaMethod] = &[#(#methods),*];
+		}
+	})
+}
@@ -1,5 +1,5 @@
 use rglua::prelude::*;
-use rglua_macros::{gmod_close, gmod_open, lua_function};
+use rglua_macros::{gmod_close, gmod_open, lua_function, lua_methods, LuaUserData};
 
 #[derive(Debug)]
 enum LuaError {}
@@ -18,6 +18,65 @@ fn add(_state: LuaState) -> Result<i32, LuaError> {
 	Ok(0)
 }
 
+#[lua_function]
+fn typed(_state: LuaState, a: f64, b: Option<String>) -> Result<(f64, bool), LuaError> {
+	Ok((a, b.is_some()))
+}
+
+#[lua_function]
+fn stateless(a: i32, _b: Vec<String>) -> i32 {
+	a
+}
+
+#[lua_function]
+fn nothing() {}
+
+#[lua_function(catch_unwind = false)]
+fn hot(_state: LuaState) -> i32 {
+	0
+}
+
+#[lua_function]
+async fn later(_state: LuaState, a: f64) -> Result<i32, LuaError> {
+	Ok(a as i32)
+}
+
+#[lua_function]
+async fn later_stateless(name: String) -> (String, bool) {
+	(name, true)
+}
+
+#[derive(LuaUserData)]
+#[lua(name = "Thing")]
+struct Thing {
+	value: i32,
+}
+
+#[lua_methods]
+impl Thing {
+	fn value(&self) -> i32 {
+		self.value
+	}
+
+	fn set(&mut self, _state: LuaState, value: i32) -> Result<(), LuaError> {
+		self.value = value;
+		Ok(())
+	}
+
+	#[lua(name = "__eq", catch_unwind = false)]
+	fn eq(&self, other: &Self) -> bool {
+		self.value == other.value
+	}
+}
+
+#[derive(LuaUserData)]
+struct Empty;
+
+#[lua_function]
+fn make_thing(value: i32) -> Thing {
+	Thing { value }
+}
+
 #[gmod_open]
 fn entry(_state: LuaState) -> Result<i32, LuaError> {
 	println!("Hello world!");
@@ -0,0 +1,15 @@
+use rglua::prelude::*;
+use rglua_macros::{gmod_open, lua_function};
+
+#[lua_function]
+fn think() {}
+
+#[gmod_open]
+fn open(_state: LuaState, ctx: &mut ModuleContext) -> Result<i32, LuaError> {
+	ctx.add_hook("Think", "context", think)?;
+	Ok(0)
+}
+
+fn main() {
+	let _close: extern "C-unwind" fn(LuaState) -> i32 = gmod13_close;
+}
@@ -2,4 +2,6 @@
 fn tests() {
 	let t = trybuild::TestCases::new();
 	t.pass("tests/base.rs");
+	t.pass("tests/context.rs");
+	t.compile_fail("tests/ui/duplicate.rs");
 }
@@ -0,0 +1,9 @@
+use rglua::prelude::*;
+
+#[lua_function(lib = "mylib")]
+fn foo() {}
+
+#[lua_function(name = "foo", lib = "mylib")]
+fn other_foo() {}
+
+fn main() {}
@@ -0,0 +1,62 @@
+//! Compares calling through the cached [LuaSharedFns] table against looking the symbol up on every call,
+//! which is what the wrappers used to do.
+//!
+//! Needs a LuaJIT library to run against, set ``RGLUA_LUA_SHARED`` to its path:
+//! ```text
+//! RGLUA_LUA_SHARED=/usr/lib/x86_64-linux-gnu/libluajit-5.1.so.2 cargo bench -p rglua
+//! ```
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rglua::prelude::*;
+
+// What dyn_symbols! generated before the table, a dlsym for every call.
+fn lua_gettop_uncached(l: LuaState) -> i32 {
+	unsafe {
+		let f = LUA_SHARED_RAW
+			.get::<extern "C-unwind" fn(LuaState) -> i32>(b"lua_gettop")
+			.expect("Couldn't get extern function: lua_gettop");
+		f(l)
+	}
+}
+
+fn lua_pushnumber_uncached(l: LuaState, n: LuaNumber) {
+	unsafe {
+		let f = LUA_SHARED_RAW
+			.get::<extern "C-unwind" fn(LuaState, LuaNumber)>(b"lua_pushnumber")
+			.expect("Couldn't get extern function: lua_pushnumber");
+		f(l, n)
+	}
+}
+
+fn symbols(c: &mut Criterion) {
+	if let Err(why) = try_lua_shared_fns() {
+		eprintln!("Skipping benchmarks, couldn't load lua_shared: {why}");
+		return;
+	}
+
+	let l = luaL_newstate();
+
+	let mut group = c.benchmark_group("lua_gettop");
+	group.bench_function("uncached", |b| b.iter(|| lua_gettop_uncached(black_box(l))));
+	group.bench_function("cached", |b| b.iter(|| lua_gettop(black_box(l))));
+	group.finish();
+
+	let mut group = c.benchmark_group("lua_pushnumber");
+	group.bench_function("uncached", |b| {
+		b.iter(|| {
+			lua_pushnumber_uncached(black_box(l), 5.0);
+			lua_settop(l, 0);
+		})
+	});
+	group.bench_function("cached", |b| {
+		b.iter(|| {
+			lua_pushnumber(black_box(l), 5.0);
+			lua_settop(l, 0);
+		})
+	});
+	group.finish();
+
+	lua_close(l);
+}
+
+criterion_group!(benches, symbols);
+criterion_main!(benches);
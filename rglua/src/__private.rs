@@ -0,0 +1,118 @@
+//! Used by code generated from rglua-macros, not public api.
+use crate::convert::FromLuaError;
+use crate::lua::*;
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+
+/// A lua error left on top of the stack by the trampoline generated for lua functions, for the outer function to raise.
+/// This is Copy and owns nothing, so nothing is leaked when raising it skips the rest of the frame.
+#[derive(Debug, Clone, Copy)]
+pub enum Raise {
+	/// Raise the error on top of the stack with [lua_error].
+	Error,
+	/// Raise with [luaL_argerror] for this argument, with the message on top of the stack.
+	ArgError(c_int)
+}
+
+impl Raise {
+	/// Pushes ``msg``, prefixed with the position like [luaL_error].
+	pub fn error(l: LuaState, msg: String) -> Self {
+		luaL_where(l, 1);
+		lua_pushlstring(l, msg.as_ptr() as LuaString, msg.len());
+		lua_concat(l, 2);
+		Self::Error
+	}
+
+	/// Pushes the reason of a failed argument conversion.
+	pub fn arg_error(l: LuaState, why: FromLuaError) -> Self {
+		let reason = why.reason();
+		lua_pushlstring(l, reason.as_ptr() as LuaString, reason.len());
+		Self::ArgError(why.arg)
+	}
+
+	pub fn raise(self, l: LuaState) -> ! {
+		match self {
+			Self::Error => lua_error(l),
+			Self::ArgError(arg) => luaL_argerror(l, arg, lua_tostring(l, -1))
+		}
+	}
+}
+
+thread_local! {
+	static PANIC_LOCATION: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// The panic payload doesn't have where it panicked, so a hook saves it (and calls the previous hook to still print it).
+fn install_hook() {
+	static HOOK: std::sync::Once = std::sync::Once::new();
+
+	HOOK.call_once(|| {
+		let prev = panic::take_hook();
+		panic::set_hook(Box::new(move |info| {
+			let location = info.location().map(|l| l.to_string());
+			PANIC_LOCATION.with(|l| *l.borrow_mut() = location);
+			prev(info)
+		}));
+	});
+}
+
+/// Runs ``f``, returning the panic message and location if it panicked.
+pub fn catch_panic<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+	install_hook();
+	PANIC_LOCATION.with(|l| l.borrow_mut().take());
+
+	panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+		let msg = match payload.downcast_ref::<&str>() {
+			Some(s) => s.to_string(),
+			None => match payload.downcast_ref::<String>() {
+				Some(s) => s.clone(),
+				None => String::from("Box<dyn Any>")
+			}
+		};
+
+		match PANIC_LOCATION.with(|l| l.borrow_mut().take()) {
+			Some(location) => format!("panicked at {location}: {msg}"),
+			None => format!("panicked: {msg}")
+		}
+	})
+}
+
+/// Whether lua errors unwind as exceptions Rust can't catch.
+/// 64 bit LuaJIT on unix raises errors with DWARF unwinding, which aborts the process if it reaches catch_unwind,
+/// while on 32 bit it jumps straight past Rust frames, and on windows raises SEH exceptions catch_unwind lets through.
+pub const LUA_ERRORS_UNWIND: bool = cfg!(all(unix, not(target_arch = "x86")));
+
+/// [catch_panic] for functions that have the lua state and so could raise lua errors.
+/// Where those unwind as exceptions (see [LUA_ERRORS_UNWIND]) a panic in ``f`` can't be caught, so it aborts the process instead,
+/// after the panic hook printed where it panicked.
+///
+/// Catching it isn't possible there, as whichever frame is closer to ``f`` catches everything:
+/// catch_unwind aborts on the lua errors it sees, and LuaJIT's protected frames (like [lua_cpcall]) also catch Rust panics,
+/// which aborts with "Rust panics must be rethrown" once dropped. So lua errors are let through, and panics abort with a reason.
+pub fn catch_panic_with_state<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+	if !LUA_ERRORS_UNWIND {
+		return catch_panic(f);
+	}
+
+	/// Only dropped while panicking if ``f`` panicked, as lua errors don't count as panics.
+	struct AbortOnPanic {
+		/// Whether this already ran while panicking (like from a drop), so panicking says nothing about ``f``.
+		was_panicking: bool
+	}
+
+	impl Drop for AbortOnPanic {
+		fn drop(&mut self) {
+			if std::thread::panicking() && !self.was_panicking {
+				eprintln!("rglua: aborting, as a panic in a function that has the lua state can't be caught while lua errors unwind through it");
+				std::process::abort();
+			}
+		}
+	}
+
+	let guard = AbortOnPanic {
+		was_panicking: std::thread::panicking()
+	};
+	let out = f();
+	drop(guard);
+	Ok(out)
+}
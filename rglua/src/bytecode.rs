@@ -0,0 +1,318 @@
+//! Dumping lua functions to bytecode and loading it back, see [dump_function] and [load_chunk],
+//! or [load_reader] to load chunks from any [Read].
+//!
+//! Bytecode isn't verified by LuaJIT, so only load it from a source you trust (like your own cache).
+//! # Examples
+//! ```rust
+//! use rglua::prelude::*;
+//! use rglua::bytecode::{dump_function, load_chunk};
+//!
+//! #[lua_function]
+//! fn recompile(l: LuaState) -> Result<i32, Box<dyn std::error::Error>> {
+//!     let bytes = dump_function(l, 1, true)?;
+//!     load_chunk(l, &bytes, "=recompiled", Some("b"))?;
+//!     Ok(1)
+//! }
+//! ```
+use crate::__private::catch_panic;
+use crate::error::LuaError;
+use crate::lua::*;
+use crate::state::{abs_index, load_buffer, to_cstring};
+use std::io::{self, Read, Write};
+
+/// Error from [dump_to] and [dump_function].
+#[derive(Debug, thiserror::Error)]
+pub enum DumpError {
+	#[error("only lua functions can be dumped")]
+	NotLuaFunction,
+
+	/// ``string.dump``, used to strip debug info, wasn't remembered (see [remember_string_dump]) or failed.
+	#[error("failed to strip: {0}")]
+	Strip(String),
+
+	#[error(transparent)]
+	Io(#[from] io::Error)
+}
+
+struct Writer<'a> {
+	out: &'a mut dyn Write,
+	error: Option<io::Error>
+}
+
+extern "C" fn write_trampoline(_: LuaState, p: *const c_void, sz: SizeT, ud: *mut c_void) -> c_int {
+	let writer = unsafe { &mut *(ud as *mut Writer) };
+	let bytes = unsafe { std::slice::from_raw_parts(p as *const u8, sz) };
+
+	// Can't unwind out of here, and there's no lua error to raise instead.
+	match catch_panic(|| writer.out.write_all(bytes)) {
+		Ok(Ok(())) => 0,
+		Ok(Err(e)) => {
+			writer.error = Some(e);
+			1
+		}
+		Err(msg) => {
+			writer.error = Some(io::Error::other(msg));
+			1
+		}
+	}
+}
+
+/// Writes the bytecode of the lua function at ``idx`` to ``out``.
+///
+/// With ``strip``, debug info (like line numbers and local names) is left out, using ``string.dump`` as it was when the module was opened.
+/// LuaJIT 2.0 ignores that, so check [Header::stripped] if it matters.
+pub fn dump_to<W: Write>(l: LuaState, idx: c_int, strip: bool, out: &mut W) -> Result<(), DumpError> {
+	if lua_type(l, idx) != TFUNCTION || lua_iscfunction(l, idx) != 0 {
+		return Err(DumpError::NotLuaFunction);
+	}
+
+	if strip {
+		return dump_stripped(l, idx, out);
+	}
+
+	let mut writer = Writer { out, error: None };
+	lua_pushvalue(l, idx);
+	let status = lua_dump(l, write_trampoline, &mut writer as *mut Writer as *mut c_void);
+	lua_pop(l, 1);
+
+	match writer.error {
+		Some(e) => Err(e.into()),
+		None if status != 0 => Err(DumpError::NotLuaFunction),
+		None => Ok(())
+	}
+}
+
+/// Remembers ``string.dump`` in the registry for stripping, which would otherwise use whatever lua code replaced it with since.
+/// Does nothing if it's already remembered or doesn't exist.
+///
+/// ``#[gmod_open]`` calls this before the entrypoint runs, so only call it when opening the module some other way.
+pub fn remember_string_dump(l: LuaState) {
+	lua_getfield(l, REGISTRYINDEX, cstr!("rglua.string_dump"));
+	let remembered = lua_type(l, -1) == TFUNCTION;
+	lua_pop(l, 1);
+	if remembered {
+		return;
+	}
+
+	lua_getfield(l, GLOBALSINDEX, cstr!("string"));
+	if lua_istable(l, -1) {
+		lua_getfield(l, -1, cstr!("dump"));
+		if lua_type(l, -1) == TFUNCTION {
+			lua_setfield(l, REGISTRYINDEX, cstr!("rglua.string_dump"));
+		} else {
+			lua_pop(l, 1);
+		}
+	}
+	lua_pop(l, 1);
+}
+
+fn dump_stripped<W: Write>(l: LuaState, idx: c_int, out: &mut W) -> Result<(), DumpError> {
+	let idx = abs_index(l, idx);
+	let top = lua_gettop(l);
+
+	lua_getfield(l, REGISTRYINDEX, cstr!("rglua.string_dump"));
+	if lua_type(l, -1) != TFUNCTION {
+		lua_settop(l, top);
+		return Err(DumpError::Strip(String::from("string.dump wasn't remembered when opening")));
+	}
+
+	lua_pushvalue(l, idx);
+	lua_pushboolean(l, 1);
+	let status = lua_pcall(l, 2, 1, 0);
+
+	let mut len = 0;
+	let ptr = lua_tolstring(l, -1, &mut len);
+	let result = match (status, ptr.is_null()) {
+		(OK, false) => {
+			let bytes = unsafe { std::slice::from_raw_parts(ptr as *const u8, len) };
+			out.write_all(bytes).map_err(DumpError::from)
+		}
+		_ => Err(DumpError::Strip(LuaError::pop(l, status).message()))
+	};
+
+	lua_settop(l, top);
+	result
+}
+
+/// Returns the bytecode of the lua function at ``idx``, see [dump_to].
+pub fn dump_function(l: LuaState, idx: c_int, strip: bool) -> Result<Vec<u8>, DumpError> {
+	let mut bytes = vec![];
+	dump_to(l, idx, strip, &mut bytes)?;
+	Ok(bytes)
+}
+
+/// Loads a chunk from ``bytes``, pushing it as a function.
+/// ``mode`` is "b" to only allow bytecode, "t" for only text or "bt" (the default) for both.
+pub fn load_chunk(l: LuaState, bytes: &[u8], name: &str, mode: Option<&str>) -> Result<(), LuaError> {
+	load_buffer(l, bytes, name, mode)
+}
+
+/// Size of the buffer [load_reader] reads into.
+pub const READ_BUFFER_SIZE: usize = 16 * 1024;
+
+/// Error from [load_reader].
+#[derive(Debug, thiserror::Error)]
+pub enum LoadError {
+	/// Reading failed. Whatever lua made of the chunk up to that point is thrown away.
+	#[error("failed to read chunk: {0}")]
+	Io(#[from] io::Error),
+
+	#[error(transparent)]
+	Lua(#[from] LuaError)
+}
+
+struct Reader<'a> {
+	input: &'a mut dyn Read,
+	buffer: Vec<u8>,
+	error: Option<io::Error>
+}
+
+extern "C" fn read_trampoline(_: LuaState, ud: *mut c_void, sz: *mut SizeT) -> *const u8 {
+	let reader = unsafe { &mut *(ud as *mut Reader) };
+
+	let read = catch_panic(|| loop {
+		match reader.input.read(&mut reader.buffer) {
+			Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+			result => break result
+		}
+	});
+
+	let len = match read {
+		Ok(Ok(len)) => len,
+		Ok(Err(e)) => {
+			reader.error = Some(e);
+			0
+		}
+		Err(msg) => {
+			reader.error = Some(io::Error::other(msg));
+			0
+		}
+	};
+
+	// A size of 0 ends the chunk, errors are checked after lua is done.
+	unsafe { *sz = len };
+	if len == 0 {
+		std::ptr::null()
+	} else {
+		reader.buffer.as_ptr()
+	}
+}
+
+/// Loads a chunk from ``reader`` as lua asks for it, without reading it all into memory first, pushing it as a function.
+/// ``mode`` is like in [load_chunk].
+/// # Examples
+/// ```rust
+/// use rglua::prelude::*;
+/// use rglua::bytecode::load_reader;
+/// use std::io::BufReader;
+///
+/// #[lua_function]
+/// fn load_file(l: LuaState, path: String) -> Result<i32, Box<dyn std::error::Error>> {
+///     let file = std::fs::File::open(&path)?;
+///     load_reader(l, BufReader::new(file), &format!("@{path}"), Some("t"))?;
+///     Ok(1)
+/// }
+/// ```
+pub fn load_reader<R: Read>(l: LuaState, mut reader: R, name: &str, mode: Option<&str>) -> Result<(), LoadError> {
+	let name = to_cstring(name);
+	let mode = mode.map(to_cstring);
+
+	let mut reader = Reader {
+		input: &mut reader,
+		buffer: vec![0; READ_BUFFER_SIZE],
+		error: None
+	};
+
+	let status = lua_loadx(
+		l,
+		read_trampoline,
+		&mut reader as *mut Reader as *mut c_void,
+		name.as_ptr(),
+		mode.as_ref().map_or(std::ptr::null(), |m| m.as_ptr())
+	);
+
+	// Either the function or the error message
+	if let Some(e) = reader.error {
+		lua_pop(l, 1);
+		return Err(e.into());
+	}
+
+	match status {
+		OK => Ok(()),
+		status => Err(LuaError::pop(l, status).into())
+	}
+}
+
+/// Starts every LuaJIT bytecode dump, ``\x1bLJ``.
+pub const SIGNATURE: &[u8] = b"\x1bLJ";
+
+const FLAG_BE: u32 = 0x01;
+const FLAG_STRIP: u32 = 0x02;
+const FLAG_FFI: u32 = 0x04;
+const FLAG_FR2: u32 = 0x08;
+
+/// Error from [Header::parse].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum HeaderError {
+	#[error("not LuaJIT bytecode")]
+	NotBytecode,
+
+	#[error("bytecode header is cut off")]
+	Truncated
+}
+
+/// The header of a LuaJIT bytecode dump.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+	/// 1 for LuaJIT 2.0, 2 for 2.1. Each only loads its own.
+	pub version: u8,
+	pub big_endian: bool,
+	/// Debug info was left out, see [dump_to].
+	pub stripped: bool,
+	/// Uses FFI types, like ``1LL``, so it needs the FFI library to load.
+	pub ffi: bool,
+	/// Made by a LuaJIT 2.1 with two slot frames (GC64 builds), which other builds can't load.
+	pub fr2: bool,
+	/// The name of the chunk, like ``@lua/autorun/foo.lua``. None if stripped.
+	pub chunk_name: Option<String>
+}
+
+impl Header {
+	/// Parses the header at the start of ``bytes``.
+	pub fn parse(bytes: &[u8]) -> Result<Self, HeaderError> {
+		let rest = bytes.strip_prefix(SIGNATURE).ok_or(HeaderError::NotBytecode)?;
+		let (&version, mut rest) = rest.split_first().ok_or(HeaderError::Truncated)?;
+		let flags = read_uleb128(&mut rest)?;
+
+		let chunk_name = if flags & FLAG_STRIP == 0 {
+			let len = read_uleb128(&mut rest)? as usize;
+			let name = rest.get(..len).ok_or(HeaderError::Truncated)?;
+			Some(String::from_utf8_lossy(name).into_owned())
+		} else {
+			None
+		};
+
+		Ok(Self {
+			version,
+			big_endian: flags & FLAG_BE != 0,
+			stripped: flags & FLAG_STRIP != 0,
+			ffi: flags & FLAG_FFI != 0,
+			fr2: flags & FLAG_FR2 != 0,
+			chunk_name
+		})
+	}
+}
+
+fn read_uleb128(bytes: &mut &[u8]) -> Result<u32, HeaderError> {
+	let mut value = 0u32;
+	for shift in (0..35).step_by(7) {
+		let (&byte, rest) = bytes.split_first().ok_or(HeaderError::Truncated)?;
+		*bytes = rest;
+
+		value |= ((byte & 0x7f) as u32).wrapping_shl(shift);
+		if byte & 0x80 == 0 {
+			return Ok(value);
+		}
+	}
+	Err(HeaderError::NotBytecode)
+}
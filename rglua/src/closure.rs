@@ -0,0 +1,142 @@
+//! Rust closures as lua functions, see [push_closure].
+use crate::__private::{catch_panic, catch_panic_with_state, Raise};
+use crate::context::{untrack, ModuleContext};
+use crate::convert::{FromLuaMulti, ToLuaMulti};
+use crate::lua::*;
+use crate::state::to_cstring;
+use std::rc::Rc;
+
+/// The closure with its arguments and returns converted.
+/// Reference counted so a call that's running keeps it alive, even if the module is closed meanwhile.
+type Callback = Rc<dyn Fn(LuaState) -> Result<c_int, Raise>>;
+
+/// What the userdata upvalue holds, emptied when the module is closed.
+type Slot = Option<Callback>;
+
+/// Pushes ``f`` as a lua function. It's boxed into a userdata, which is an upvalue of the function and drops it once collected.
+///
+/// It's also recorded in the [ModuleContext] of the state, which drops it when the module is closed,
+/// as lua may still have the function then. Calling it after that is an error.
+///
+/// Arguments are converted to ``A`` like the arguments of a ``#[lua_function]``, and what it returns is pushed with [ToLuaMulti].
+/// Panics are caught like in a ``#[lua_function]`` taking the state, so they abort on 64 bit unix, where lua errors couldn't pass through.
+/// # Examples
+/// ```rust
+/// use rglua::prelude::*;
+/// use rglua::closure::push_closure;
+///
+/// #[lua_function]
+/// fn make_adder(l: LuaState, by: f64) -> i32 {
+///     push_closure(l, move |_, n: f64| n + by);
+///     1
+/// }
+/// ```
+pub fn push_closure<A, R, F>(l: LuaState, f: F)
+where
+	A: FromLuaMulti,
+	R: ToLuaMulti,
+	F: Fn(LuaState, A) -> R + 'static
+{
+	let callback: Callback = Rc::new(move |l| {
+		let args = A::from_lua_multi(l, 1).map_err(|why| Raise::arg_error(l, why))?;
+		Ok(f(l, args).push_multi(l))
+	});
+
+	let ptr = lua_newuserdata(l, std::mem::size_of::<Slot>()) as *mut Slot;
+	unsafe { ptr.write(Some(callback)) };
+
+	lua_createtable(l, 0, 1);
+	lua_pushcfunction(l, gc);
+	lua_setfield(l, -2, cstr!("__gc"));
+	lua_setmetatable(l, -2);
+
+	push_alive(l);
+	lua_pushlightuserdata(l, ptr as *mut c_void);
+	lua_pushvalue(l, -3);
+	lua_rawset(l, -3);
+	lua_pop(l, 1);
+
+	ModuleContext::get(l).track(
+		closure_key(ptr),
+		Box::new(move |l| {
+			release(l, ptr);
+			Ok(())
+		})
+	);
+
+	lua_pushcclosure(l, trampoline, 1);
+}
+
+fn closure_key(ptr: *mut Slot) -> String {
+	format!("closure {ptr:p}")
+}
+
+/// Pushes the registry table of the closures of this module that are alive, by address, which doesn't keep them alive.
+fn push_alive(l: LuaState) {
+	// Unique to this module, as its closures are dropped by its own code.
+	let key = to_cstring(&format!("rglua.closures.{:p}", trampoline as *const ()));
+	lua_getfield(l, REGISTRYINDEX, key.as_ptr());
+	if lua_istable(l, -1) {
+		return;
+	}
+	lua_pop(l, 1);
+
+	lua_createtable(l, 0, 0);
+	lua_createtable(l, 0, 1);
+	lua_pushstring(l, cstr!("v"));
+	lua_setfield(l, -2, cstr!("__mode"));
+	lua_setmetatable(l, -2);
+
+	lua_pushvalue(l, -1);
+	lua_setfield(l, REGISTRYINDEX, key.as_ptr());
+}
+
+/// Drops the closure at ``ptr`` if it's still alive, and removes its ``__gc`` so nothing runs once the module is unloaded.
+fn release(l: LuaState, ptr: *mut Slot) {
+	push_alive(l);
+	lua_pushlightuserdata(l, ptr as *mut c_void);
+	lua_rawget(l, -2);
+
+	if lua_touserdata(l, -1) == ptr as *mut c_void {
+		lua_pushnil(l);
+		lua_setmetatable(l, -2);
+		drop(unsafe { (*ptr).take() });
+	}
+	lua_pop(l, 2);
+}
+
+extern "C-unwind" fn trampoline(l: LuaState) -> c_int {
+	// The slot is alive while the function is running, as its upvalue.
+	let slot = unsafe { &*(lua_touserdata(l, lua_upvalueindex(1)) as *const Slot) };
+	let Some(callback) = slot.clone() else {
+		Raise::error(l, String::from("function was called after its module was closed")).raise(l)
+	};
+
+	// Like the trampoline of a #[lua_function], everything is dropped before raising.
+	let result = match catch_panic_with_state(|| callback(l)) {
+		Ok(result) => result,
+		Err(msg) => Err(Raise::error(l, msg))
+	};
+	drop(callback);
+
+	match result {
+		Ok(n) => n,
+		Err(raise) => raise.raise(l)
+	}
+}
+
+extern "C-unwind" fn gc(l: LuaState) -> c_int {
+	let ptr = lua_touserdata(l, 1) as *mut Slot;
+	if ptr.is_null() {
+		return 0;
+	}
+
+	lua_pushnil(l);
+	lua_setmetatable(l, 1);
+	untrack(l, &closure_key(ptr));
+
+	match catch_panic(|| drop(unsafe { (*ptr).take() })) {
+		Ok(()) => 0,
+		Err(msg) => Raise::error(l, msg).raise(l)
+	}
+}
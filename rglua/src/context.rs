@@ -0,0 +1,179 @@
+//! Tracking what a module sets up in ``#[gmod_open]`` to undo it when the module is closed, see [ModuleContext].
+//! # Examples
+//! ```rust
+//! use rglua::prelude::*;
+//!
+//! #[lua_function]
+//! fn on_think() {}
+//!
+//! #[lua_function]
+//! fn reload(l: LuaState) {
+//!     printgm!(l, "reloading");
+//! }
+//!
+//! // gmod13_close is generated, removing the hook and console command again.
+//! #[gmod_open]
+//! fn open(l: LuaState, ctx: &mut ModuleContext) -> Result<i32, LuaError> {
+//!     ctx.add_hook("Think", "mymodule", on_think)?;
+//!     ctx.add_concommand("mymodule_reload", reload)?;
+//!     ctx.on_close(|l| printgm!(l, "closing"));
+//!     Ok(0)
+//! }
+//! ```
+use crate::data::{set_state_data, take_state_data, with_state_data};
+use crate::error::LuaError;
+use crate::lua::*;
+use crate::reference::LuaRef;
+use crate::runtime::report;
+use crate::state::{call_library, protected};
+
+pub(crate) type Cleanup = Box<dyn FnOnce(LuaState) -> Result<(), LuaError>>;
+
+/// What's recorded in the context, kept as state data.
+/// Keyed entries are replaced when recorded again, like a hook added twice under the same name.
+struct Recorded(Vec<(Option<String>, Cleanup)>);
+
+/// What a module set up, to be torn down in reverse order when it's closed.
+///
+/// ``#[gmod_open]`` gets one by taking ``&mut ModuleContext`` after the state, and then generates ``gmod13_close``,
+/// so use [ModuleContext::on_close] instead of ``#[gmod_close]`` for anything else to do on close.
+/// Without that, ``#[gmod_close]`` still tears down the context (after running), so one can be used through [ModuleContext::get].
+///
+/// What's recorded is kept in the state (see [crate::data]), so this is only a handle to it.
+/// If the module is opened again without being closed (like when it's reloaded with ``require``), the old context is closed first.
+#[derive(Debug, Clone, Copy)]
+pub struct ModuleContext {
+	l: LuaState
+}
+
+impl ModuleContext {
+	/// Returns the context of the state of ``l``. It's created the first time something is recorded in it.
+	pub fn get(l: LuaState) -> Self {
+		Self { l }
+	}
+
+	/// Closes any context left from before and returns a new one. Called by ``#[gmod_open]``.
+	#[doc(hidden)]
+	pub fn __open(l: LuaState) -> Self {
+		close(l);
+		Self::get(l)
+	}
+
+	/// Records ``cleanup`` under ``key``, replacing what was recorded under it before, and creating the context if there isn't one.
+	fn push(&self, key: Option<String>, cleanup: Cleanup) {
+		if let Some(key) = &key {
+			untrack(self.l, key);
+		}
+
+		if with_state_data(self.l, |_: &mut Recorded| ()).is_none() {
+			set_state_data(self.l, Recorded(vec![]));
+		}
+		with_state_data(self.l, |recorded: &mut Recorded| recorded.0.push((key, cleanup)));
+	}
+
+	/// Runs ``f`` when the module is closed, after tearing down everything recorded after this.
+	/// Lua errors it raises are reported, see [close].
+	pub fn on_close<F: FnOnce(LuaState) + 'static>(&self, f: F) {
+		self.push(
+			None,
+			Box::new(move |l| {
+				f(l);
+				Ok(())
+			})
+		);
+	}
+
+	/// Keeps ``reference`` alive until the module is closed, then releases it.
+	pub fn keep(&self, reference: LuaRef) {
+		self.push(
+			None,
+			Box::new(move |_| {
+				drop(reference);
+				Ok(())
+			})
+		);
+	}
+
+	/// Adds ``func`` as the hook ``name`` on ``event`` with ``hook.Add``, removing it on close.
+	pub fn add_hook(&self, event: &str, name: &str, func: LuaCFunction) -> Result<(), LuaError> {
+		call_library::<_, ()>(self.l, "hook", "Add", (event, name, func))?;
+
+		self.track(hook_key(event, name), remove_hook(event, name));
+		Ok(())
+	}
+
+	/// Adds the console command ``name`` with ``concommand.Add``, removing it on close.
+	pub fn add_concommand(&self, name: &str, func: LuaCFunction) -> Result<(), LuaError> {
+		call_library::<_, ()>(self.l, "concommand", "Add", (name, func))?;
+
+		let key = format!("concommand {name}");
+		let name = name.to_owned();
+		self.track(key, Box::new(move |l| call_library(l, "concommand", "Remove", name)));
+		Ok(())
+	}
+
+	/// Creates the timer ``name`` with ``timer.Create``, removing it on close.
+	/// It runs ``func`` every ``delay`` seconds, ``repetitions`` times (or forever if 0).
+	pub fn create_timer(&self, name: &str, delay: f64, repetitions: u32, func: LuaCFunction) -> Result<(), LuaError> {
+		call_library::<_, ()>(self.l, "timer", "Create", (name, delay, repetitions, func))?;
+
+		self.track(timer_key(name), remove_timer(name));
+		Ok(())
+	}
+
+	/// The state the context was created with.
+	pub fn state(&self) -> LuaState {
+		self.l
+	}
+
+	/// Records ``cleanup`` under ``key``, replacing what was recorded under it before.
+	pub(crate) fn track(&self, key: String, cleanup: Cleanup) {
+		self.push(Some(key), cleanup);
+	}
+}
+
+/// Forgets what was recorded under ``key`` in the context of the state of ``l``, for when it was already undone.
+pub(crate) fn untrack(l: LuaState, key: &str) {
+	// Dropped after the borrow ends, in case that touches the context.
+	let removed: Option<Vec<_>> = with_state_data(l, |recorded: &mut Recorded| {
+		let (removed, kept) = std::mem::take(&mut recorded.0).into_iter().partition(|(k, _)| k.as_deref() == Some(key));
+		recorded.0 = kept;
+		removed
+	});
+	drop(removed);
+}
+
+pub(crate) fn hook_key(event: &str, name: &str) -> String {
+	format!("hook {event} {name}")
+}
+
+pub(crate) fn remove_hook(event: &str, name: &str) -> Cleanup {
+	let (event, name) = (event.to_owned(), name.to_owned());
+	Box::new(move |l| call_library(l, "hook", "Remove", (event, name)))
+}
+
+pub(crate) fn timer_key(name: &str) -> String {
+	format!("timer {name}")
+}
+
+pub(crate) fn remove_timer(name: &str) -> Cleanup {
+	let name = name.to_owned();
+	Box::new(move |l| call_library(l, "timer", "Remove", name))
+}
+
+/// Tears down everything recorded in the context of the state of ``l``, in reverse order, and removes it.
+/// Errors (like a library being gone already), lua errors and panics are reported with ``ErrorNoHalt``, and don't stop the rest.
+/// Each cleanup runs in a protected call, where panics are caught like in a ``#[lua_function]`` taking the state, so they abort on 64 bit unix.
+///
+/// Anything recorded while closing (like by an [ModuleContext::on_close] callback) is torn down too, so no context is left after.
+///
+/// Called at the end of ``#[gmod_close]``, and by the ``gmod13_close`` generated for a ``#[gmod_open]`` taking a context.
+pub fn close(l: LuaState) {
+	while let Some(Recorded(mut cleanup)) = take_state_data::<Recorded>(l) {
+		while let Some((_, cleanup)) = cleanup.pop() {
+			if let Err(e) = protected(l, cleanup).and_then(|result| result) {
+				report(l, &e.to_string());
+			}
+		}
+	}
+}
@@ -0,0 +1,416 @@
+//! Traits to push Rust values to lua and read them back, instead of picking the right ``lua_push*`` / ``luaL_check*`` by hand.
+//! # Examples
+//! ```rust
+//! use rglua::prelude::*;
+//!
+//! #[lua_function]
+//! fn sum(l: LuaState) -> i32 {
+//!     let nums = match Vec::<f64>::from_lua(l, 1) {
+//!         Ok(nums) => nums,
+//!         Err(why) => why.raise(l)
+//!     };
+//!
+//!     nums.iter().sum::<f64>().push_to_lua(l);
+//!     1
+//! }
+//! ```
+use crate::lua::*;
+use crate::state::{abs_index, to_bytes, type_name};
+use crate::userdata::{Angle, Vector};
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// A lua value couldn't be converted to the Rust type that was asked for.
+/// Displays like lua's own argument errors, ``bad argument #1 (number expected, got nil)``.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("bad argument #{arg} ({})", self.reason())]
+pub struct FromLuaError {
+	/// Stack index of the value, which is the argument number inside of a lua function.
+	pub arg: c_int,
+	/// Name of the type that was expected, like "number" or "table of string".
+	pub expected: Cow<'static, str>,
+	/// What was found instead, usually the lua type name.
+	pub got: Cow<'static, str>
+}
+
+impl FromLuaError {
+	pub fn new(arg: c_int, expected: impl Into<Cow<'static, str>>, got: impl Into<Cow<'static, str>>) -> Self {
+		Self {
+			arg,
+			expected: expected.into(),
+			got: got.into()
+		}
+	}
+
+	/// Creates an error for the value at ``idx``, with ``got`` being its lua type name.
+	pub fn type_mismatch(l: LuaState, idx: c_int, expected: impl Into<Cow<'static, str>>) -> Self {
+		Self::new(abs_index(l, idx), expected, type_name(l, idx))
+	}
+
+	/// The part in parenthesis of the message, ``number expected, got nil``.
+	pub fn reason(&self) -> String {
+		format!("{} expected, got {}", self.expected, self.got)
+	}
+
+	/// Raises this as a lua error with [luaL_argerror], naming the function being called like lua's own errors do.
+	/// The error is dropped first, as raising the error never returns.
+	pub fn raise(self, l: LuaState) -> ! {
+		let arg = self.arg;
+		let reason = self.reason();
+		lua_pushlstring(l, reason.as_ptr() as LuaString, reason.len());
+
+		drop(reason);
+		drop(self);
+
+		luaL_argerror(l, arg, lua_tostring(l, -1))
+	}
+
+	/// Wraps an error for a value inside of a table at ``idx``, keeping which key it was.
+	fn nested(self, l: LuaState, idx: c_int, key: impl std::fmt::Display) -> Self {
+		Self::new(
+			abs_index(l, idx),
+			format!("table of {}", self.expected),
+			format!("{} at [{key}]", self.got)
+		)
+	}
+}
+
+/// A Rust value that can be pushed to the lua stack as a single value.
+pub trait ToLua {
+	fn push_to_lua(self, l: LuaState);
+}
+
+/// A Rust value that can be read from a single value on the lua stack.
+pub trait FromLua: Sized {
+	/// Reads the value at ``idx``, leaving the stack as it was.
+	fn from_lua(l: LuaState, idx: c_int) -> Result<Self, FromLuaError>;
+}
+
+/// Values that push any number of lua values, like returns of a lua function.
+/// Implemented for every [ToLua] as one value, ``()`` as none, and tuples of [ToLua] as one value each.
+pub trait ToLuaMulti {
+	/// Pushes the values, returning how many were pushed.
+	fn push_multi(self, l: LuaState) -> c_int;
+}
+
+/// Values read from consecutive stack slots, like the arguments of a lua function.
+/// Implemented for every [FromLua] as one value, ``()`` as none, and tuples of [FromLua] as one value each.
+pub trait FromLuaMulti: Sized {
+	/// How many stack slots this reads.
+	const COUNT: c_int;
+
+	/// Reads [FromLuaMulti::COUNT] values starting at the absolute index ``start``.
+	fn from_lua_multi(l: LuaState, start: c_int) -> Result<Self, FromLuaError>;
+}
+
+impl<T: ToLua> ToLuaMulti for T {
+	fn push_multi(self, l: LuaState) -> c_int {
+		self.push_to_lua(l);
+		1
+	}
+}
+
+impl<T: FromLua> FromLuaMulti for T {
+	const COUNT: c_int = 1;
+
+	fn from_lua_multi(l: LuaState, start: c_int) -> Result<Self, FromLuaError> {
+		T::from_lua(l, start)
+	}
+}
+
+impl ToLuaMulti for () {
+	fn push_multi(self, _: LuaState) -> c_int {
+		0
+	}
+}
+
+impl FromLuaMulti for () {
+	const COUNT: c_int = 0;
+
+	fn from_lua_multi(_: LuaState, _: c_int) -> Result<Self, FromLuaError> {
+		Ok(())
+	}
+}
+
+macro_rules! impl_tuple {
+	($($name:ident),+) => {
+		impl<$($name: ToLua),+> ToLuaMulti for ($($name,)+) {
+			#[allow(non_snake_case)]
+			fn push_multi(self, l: LuaState) -> c_int {
+				let ($($name,)+) = self;
+				let mut n = 0;
+				$(
+					$name.push_to_lua(l);
+					n += 1;
+				)+
+				n
+			}
+		}
+
+		impl<$($name: FromLua),+> FromLuaMulti for ($($name,)+) {
+			const COUNT: c_int = [$(stringify!($name)),+].len() as c_int;
+
+			fn from_lua_multi(l: LuaState, start: c_int) -> Result<Self, FromLuaError> {
+				let mut idx = start - 1;
+				Ok(($(
+					{
+						idx += 1;
+						$name::from_lua(l, idx)?
+					},
+				)+))
+			}
+		}
+	};
+}
+
+impl_tuple!(A);
+impl_tuple!(A, B);
+impl_tuple!(A, B, C);
+impl_tuple!(A, B, C, D);
+impl_tuple!(A, B, C, D, E);
+impl_tuple!(A, B, C, D, E, F);
+impl_tuple!(A, B, C, D, E, F, G);
+impl_tuple!(A, B, C, D, E, F, G, H);
+
+/// Reads a number like [lua_tonumber], but errors instead of returning 0 for anything that isn't a number or numeric string.
+fn to_number(l: LuaState, idx: c_int, expected: &'static str) -> Result<LuaNumber, FromLuaError> {
+	let mut isnum = 0;
+	let n = lua_tonumberx(l, idx, &mut isnum);
+	if isnum == 0 {
+		return Err(FromLuaError::type_mismatch(l, idx, expected));
+	}
+	Ok(n)
+}
+
+macro_rules! impl_number {
+	($($ty:ty),+ as float) => {$(
+		impl ToLua for $ty {
+			fn push_to_lua(self, l: LuaState) {
+				lua_pushnumber(l, self as LuaNumber);
+			}
+		}
+
+		impl FromLua for $ty {
+			fn from_lua(l: LuaState, idx: c_int) -> Result<Self, FromLuaError> {
+				to_number(l, idx, "number").map(|n| n as $ty)
+			}
+		}
+	)+};
+
+	($($ty:ty),+ as int) => {$(
+		impl ToLua for $ty {
+			fn push_to_lua(self, l: LuaState) {
+				// Lua numbers are doubles, so push as one instead of going through LuaInteger which may be 32 bit.
+				lua_pushnumber(l, self as LuaNumber);
+			}
+		}
+
+		impl FromLua for $ty {
+			/// Truncates towards zero like [luaL_checkinteger], but errors if the number doesn't fit.
+			fn from_lua(l: LuaState, idx: c_int) -> Result<Self, FromLuaError> {
+				// MAX rounds up to 2^bits as a double, so compare against that exclusively.
+				let bits = <$ty>::BITS as i32 - (<$ty>::MIN != 0) as i32;
+				let n = to_number(l, idx, "number")?.trunc();
+				if n.is_nan() || n < <$ty>::MIN as LuaNumber || n >= (2.0 as LuaNumber).powi(bits) {
+					return Err(FromLuaError::new(
+						abs_index(l, idx),
+						concat!("number in range of ", stringify!($ty)),
+						n.to_string()
+					));
+				}
+				Ok(n as $ty)
+			}
+		}
+	)+};
+}
+
+impl_number!(f32, f64 as float);
+impl_number!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize as int);
+
+impl ToLua for bool {
+	fn push_to_lua(self, l: LuaState) {
+		lua_pushboolean(l, self as c_int);
+	}
+}
+
+impl FromLua for bool {
+	/// Only accepts booleans, use ``Option<bool>`` to also allow nil.
+	fn from_lua(l: LuaState, idx: c_int) -> Result<Self, FromLuaError> {
+		if lua_type(l, idx) != TBOOLEAN {
+			return Err(FromLuaError::type_mismatch(l, idx, "boolean"));
+		}
+		Ok(lua_toboolean(l, idx) != 0)
+	}
+}
+
+impl ToLua for &str {
+	fn push_to_lua(self, l: LuaState) {
+		lua_pushlstring(l, self.as_ptr() as LuaString, self.len());
+	}
+}
+
+impl ToLua for String {
+	fn push_to_lua(self, l: LuaState) {
+		self.as_str().push_to_lua(l);
+	}
+}
+
+impl ToLua for &String {
+	fn push_to_lua(self, l: LuaState) {
+		self.as_str().push_to_lua(l);
+	}
+}
+
+impl FromLua for String {
+	/// Accepts strings and numbers like [luaL_checkstring], without converting numbers in place.
+	/// Invalid utf-8 is replaced with U+FFFD.
+	fn from_lua(l: LuaState, idx: c_int) -> Result<Self, FromLuaError> {
+		match to_bytes(l, idx) {
+			Some(bytes) => Ok(String::from_utf8_lossy(&bytes).into_owned()),
+			None => Err(FromLuaError::type_mismatch(l, idx, "string"))
+		}
+	}
+}
+
+impl<T: ToLua> ToLua for Option<T> {
+	/// Pushes nil for None.
+	fn push_to_lua(self, l: LuaState) {
+		match self {
+			Some(v) => v.push_to_lua(l),
+			None => lua_pushnil(l)
+		}
+	}
+}
+
+impl<T: FromLua> FromLua for Option<T> {
+	/// None if the value is nil or missing.
+	fn from_lua(l: LuaState, idx: c_int) -> Result<Self, FromLuaError> {
+		if lua_isnoneornil(l, idx) {
+			return Ok(None);
+		}
+		T::from_lua(l, idx).map(Some)
+	}
+}
+
+impl<T: ToLua> ToLua for Vec<T> {
+	/// Pushes a table with the elements at 1..=len.
+	fn push_to_lua(self, l: LuaState) {
+		lua_createtable(l, self.len() as c_int, 0);
+		for (i, v) in self.into_iter().enumerate() {
+			v.push_to_lua(l);
+			lua_rawseti(l, -2, i as c_int + 1);
+		}
+	}
+}
+
+impl<T: FromLua> FromLua for Vec<T> {
+	/// Reads the array part of a table, from 1 up to its length (``#t``).
+	fn from_lua(l: LuaState, idx: c_int) -> Result<Self, FromLuaError> {
+		if !lua_istable(l, idx) {
+			return Err(FromLuaError::type_mismatch(l, idx, "table"));
+		}
+
+		let idx = abs_index(l, idx);
+		let len = lua_objlen(l, idx) as c_int;
+		let mut out = Vec::with_capacity(len as usize);
+
+		for i in 1..=len {
+			lua_rawgeti(l, idx, i);
+			let v = T::from_lua(l, -1);
+			lua_pop(l, 1);
+			out.push(v.map_err(|e| e.nested(l, idx, i))?);
+		}
+
+		Ok(out)
+	}
+}
+
+impl<K: ToLua, V: ToLua, S> ToLua for HashMap<K, V, S> {
+	fn push_to_lua(self, l: LuaState) {
+		lua_createtable(l, 0, self.len() as c_int);
+		for (k, v) in self {
+			k.push_to_lua(l);
+			v.push_to_lua(l);
+			lua_rawset(l, -3);
+		}
+	}
+}
+
+impl<K, V, S> FromLua for HashMap<K, V, S>
+where
+	K: FromLua + Eq + Hash,
+	V: FromLua,
+	S: std::hash::BuildHasher + Default
+{
+	/// Reads every pair of a table, like ``pairs``.
+	fn from_lua(l: LuaState, idx: c_int) -> Result<Self, FromLuaError> {
+		if !lua_istable(l, idx) {
+			return Err(FromLuaError::type_mismatch(l, idx, "table"));
+		}
+
+		let idx = abs_index(l, idx);
+		let mut out = HashMap::default();
+
+		lua_pushnil(l);
+		while lua_next(l, idx) != 0 {
+			let pair = K::from_lua(l, -2).and_then(|k| Ok((k, V::from_lua(l, -1)?)));
+			match pair {
+				Ok((k, v)) => {
+					out.insert(k, v);
+					lua_pop(l, 1);
+				}
+				Err(why) => {
+					let key = to_bytes(l, -2)
+						.map(|k| String::from_utf8_lossy(&k).into_owned())
+						.unwrap_or_else(|| String::from("?"));
+					lua_pop(l, 2);
+					return Err(why.nested(l, idx, key));
+				}
+			}
+		}
+
+		Ok(out)
+	}
+}
+
+impl ToLua for Vector {
+	fn push_to_lua(self, l: LuaState) {
+		lua_pushvector(l, self);
+	}
+}
+
+impl FromLua for Vector {
+	fn from_lua(l: LuaState, idx: c_int) -> Result<Self, FromLuaError> {
+		lua_tovector(l, idx).ok_or_else(|| FromLuaError::type_mismatch(l, idx, "Vector"))
+	}
+}
+
+impl ToLua for Angle {
+	fn push_to_lua(self, l: LuaState) {
+		lua_pushangle(l, self);
+	}
+}
+
+impl FromLua for Angle {
+	fn from_lua(l: LuaState, idx: c_int) -> Result<Self, FromLuaError> {
+		lua_toangle(l, idx).ok_or_else(|| FromLuaError::type_mismatch(l, idx, "Angle"))
+	}
+}
+
+impl ToLua for LuaCFunction {
+	fn push_to_lua(self, l: LuaState) {
+		lua_pushcfunction(l, self);
+	}
+}
+
+impl FromLua for LuaCFunction {
+	/// Only C functions can be read back, not functions defined in lua.
+	fn from_lua(l: LuaState, idx: c_int) -> Result<Self, FromLuaError> {
+		if lua_iscfunction(l, idx) == 0 {
+			return Err(FromLuaError::type_mismatch(l, idx, "C function"));
+		}
+		Ok(lua_tocfunction(l, idx))
+	}
+}
@@ -0,0 +1,150 @@
+//! Rust values tied to a lua state, instead of ``static mut`` globals shared by every state the module is loaded into
+//! (like the client, server and menu states).
+//!
+//! Each type gets one value per state, kept in the registry as a userdata, and dropped by its ``__gc`` when the state closes
+//! if it wasn't taken out before.
+//! # Examples
+//! ```rust
+//! use rglua::prelude::*;
+//! use rglua::data::{set_state_data, take_state_data, with_state_data};
+//!
+//! #[derive(Default)]
+//! struct Stats {
+//!     calls: u32
+//! }
+//!
+//! #[lua_function(name = "count_call")]
+//! fn count_call(l: LuaState) -> Option<u32> {
+//!     with_state_data(l, |stats: &mut Stats| {
+//!         stats.calls += 1;
+//!         stats.calls
+//!     })
+//! }
+//!
+//! #[gmod_open]
+//! fn open(l: LuaState) -> i32 {
+//!     set_state_data(l, Stats::default());
+//!     0
+//! }
+//!
+//! #[gmod_close]
+//! fn close(l: LuaState) -> i32 {
+//!     drop(take_state_data::<Stats>(l));
+//!     0
+//! }
+//! ```
+use crate::__private::{catch_panic, Raise};
+use crate::lua::*;
+use crate::state::to_cstring;
+use std::any::{type_name, TypeId};
+use std::cell::{RefCell, RefMut};
+use std::ffi::CString;
+
+/// What the userdata holds. Boxed so any alignment works, and taken out by [take_state_data].
+/// The RefCell stops it from being borrowed twice, or taken while borrowed.
+type Slot<T> = RefCell<Option<Box<T>>>;
+
+/// Its address is unique to each copy of rglua, telling apart the same types in different modules loaded into the same state.
+static ANCHOR: u8 = 0;
+
+/// Registry key of the value of type ``T``.
+fn key<T: 'static>() -> CString {
+	to_cstring(&format!("rglua.data.{:?}.{:p}", TypeId::of::<T>(), &ANCHOR))
+}
+
+extern "C-unwind" fn gc<T: 'static>(l: LuaState) -> c_int {
+	let ptr = lua_touserdata(l, 1) as *mut Slot<T>;
+	if ptr.is_null() {
+		return 0;
+	}
+
+	// Remove the metatable first, so the value can't be dropped again.
+	lua_pushnil(l);
+	lua_setmetatable(l, 1);
+
+	// Taken out and not dropped in place, as the registry may still lead other finalizers to the slot while the state closes.
+	match catch_panic(|| drop(unsafe { &*ptr }.take())) {
+		Ok(()) => 0,
+		Err(msg) => Raise::error(l, msg).raise(l)
+	}
+}
+
+/// Pushes the slot of ``T``, returning None (and pushing nothing) if there isn't one.
+/// It's kept alive by the registry until taken or the state closes.
+fn push_slot<'a, T: 'static>(l: LuaState, key: &CString) -> Option<&'a Slot<T>> {
+	lua_getfield(l, REGISTRYINDEX, key.as_ptr());
+
+	let ptr = lua_touserdata(l, -1) as *const Slot<T>;
+	if ptr.is_null() {
+		lua_pop(l, 1);
+		return None;
+	}
+	Some(unsafe { &*ptr })
+}
+
+fn borrow_slot<T: 'static>(slot: &Slot<T>) -> RefMut<'_, Option<Box<T>>> {
+	match slot.try_borrow_mut() {
+		Ok(value) => value,
+		Err(_) => panic!("the {} of this state is already borrowed", type_name::<T>())
+	}
+}
+
+/// Stores ``value`` as the ``T`` of the state of ``l``, dropping the one it replaces. Call this in ``#[gmod_open]``.
+/// # Panics
+/// If the value it replaces is borrowed by [with_state_data].
+pub fn set_state_data<T: 'static>(l: LuaState, value: T) {
+	let key = key::<T>();
+
+	// Taken out first, so the old value is dropped now and not whenever it's collected.
+	let old = take_slot::<T>(l, &key);
+
+	let ptr = lua_newuserdata(l, std::mem::size_of::<Slot<T>>()) as *mut Slot<T>;
+	unsafe { ptr.write(RefCell::new(Some(Box::new(value)))) };
+
+	lua_createtable(l, 0, 1);
+	lua_pushcfunction(l, gc::<T>);
+	lua_setfield(l, -2, cstr!("__gc"));
+	lua_setmetatable(l, -2);
+
+	lua_setfield(l, REGISTRYINDEX, key.as_ptr());
+	drop(old);
+}
+
+/// Runs ``f`` with the ``T`` of the state of ``l`` and returns what it returned, or None if [set_state_data] wasn't called for it.
+///
+/// The value is borrowed while ``f`` runs, so it can't be replaced or taken meanwhile.
+/// # Panics
+/// If the value is already borrowed, by calling this again from ``f``.
+pub fn with_state_data<T: 'static, R>(l: LuaState, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+	let slot = push_slot::<T>(l, &key::<T>())?;
+	lua_pop(l, 1);
+
+	let mut value = borrow_slot(slot);
+	value.as_deref_mut().map(f)
+}
+
+/// Removes the ``T`` of the state of ``l`` and returns it. Call this in ``#[gmod_close]`` to drop it in a known order,
+/// otherwise it's dropped whenever the state is closed.
+/// # Panics
+/// If the value is borrowed by [with_state_data].
+pub fn take_state_data<T: 'static>(l: LuaState) -> Option<T> {
+	take_slot::<T>(l, &key::<T>())
+}
+
+fn take_slot<T: 'static>(l: LuaState, key: &CString) -> Option<T> {
+	// Popped before borrowing, so a panic doesn't leave it on the stack. The registry keeps it alive until it's removed below.
+	let slot = push_slot::<T>(l, key)?;
+	lua_pop(l, 1);
+	let value = borrow_slot(slot).take();
+
+	// Nothing is left to drop, and the __gc may not outlive the module (when it's unloaded before the state is closed).
+	lua_getfield(l, REGISTRYINDEX, key.as_ptr());
+	lua_pushnil(l);
+	lua_setmetatable(l, -2);
+	lua_pop(l, 1);
+
+	lua_pushnil(l);
+	lua_setfield(l, REGISTRYINDEX, key.as_ptr());
+
+	value.map(|value| *value)
+}
@@ -0,0 +1,284 @@
+//! Debug hooks that run Rust closures, see [set_hook].
+use crate::__private::{catch_panic_with_state, Raise};
+use crate::lua::*;
+use crate::state::registry_key;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// What triggered a hook, decoded from [LuaDebug::event].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+	/// A function was called. Enabled with [MASKCALL].
+	Call,
+	/// A function is returning. Enabled with [MASKRET].
+	Return,
+	/// A function that was tail called returned, in which case there's no info about it. Enabled with [MASKRET].
+	TailReturn,
+	/// A new line is about to run. Enabled with [MASKLINE].
+	Line(u32),
+	/// The count of instructions given to [set_hook] ran. Enabled with [MASKCOUNT].
+	Count
+}
+
+impl HookEvent {
+	fn decode(ar: &LuaDebug) -> Option<Self> {
+		Some(match ar.event {
+			HOOKCALL => Self::Call,
+			HOOKRET => Self::Return,
+			HOOKTAILRET => Self::TailReturn,
+			HOOKLINE => Self::Line(ar.currentline.max(0) as u32),
+			HOOKCOUNT => Self::Count,
+			_ => return None
+		})
+	}
+
+	/// The mask that enables this event.
+	pub fn mask(&self) -> c_int {
+		match self {
+			Self::Call => MASKCALL,
+			Self::Return | Self::TailReturn => MASKRET,
+			Self::Line(_) => MASKLINE,
+			Self::Count => MASKCOUNT
+		}
+	}
+}
+
+type Callback = dyn FnMut(LuaState, HookEvent, &LuaDebug) -> Result<(), String>;
+
+struct Entry {
+	id: u64,
+	mask: c_int,
+	count: c_int,
+	/// Instructions run since the last count event.
+	elapsed: c_int,
+	callback: Rc<RefCell<Callback>>
+}
+
+/// A hook that was set before ours, which keeps being called.
+struct Previous {
+	func: LuaHook,
+	mask: c_int,
+	count: c_int,
+	elapsed: c_int
+}
+
+struct Hooks {
+	previous: Option<Previous>,
+	entries: Vec<Entry>,
+	/// The count given to lua, which divides the counts of every hook.
+	count: c_int
+}
+
+impl Hooks {
+	/// Sets the lua hook to call all of the hooks.
+	fn apply(&mut self, l: LuaState) {
+		let previous = self.previous.as_ref().map(|p| (p.mask, p.count));
+		let all = self.entries.iter().map(|e| (e.mask, e.count)).chain(previous);
+
+		let (mut mask, mut count) = (0, 0);
+		for (m, c) in all {
+			mask |= m;
+			if m & MASKCOUNT != 0 {
+				count = gcd(count, c);
+			}
+		}
+
+		self.count = count;
+		lua_sethook(l, dispatch, mask, count);
+	}
+}
+
+thread_local! {
+	/// Hooks are shared by every thread (coroutine) of a state, so they're kept by its registry, which is too.
+	static HOOKS: RefCell<HashMap<usize, Hooks>> = RefCell::new(HashMap::new());
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+fn is_ours(hook: Option<LuaHook>) -> bool {
+	hook.is_some_and(|f| f as usize == dispatch as LuaHook as usize)
+}
+
+fn gcd(a: c_int, b: c_int) -> c_int {
+	if b == 0 {
+		a
+	} else {
+		gcd(b, a % b)
+	}
+}
+
+/// A hook set with [set_hook], removed when dropped.
+///
+/// It must be dropped before the state it was set on is closed.
+#[must_use = "The hook is removed when this is dropped"]
+pub struct Hook {
+	l: LuaState,
+	id: u64
+}
+
+impl Drop for Hook {
+	fn drop(&mut self) {
+		let l = self.l;
+		let _ = HOOKS.try_with(|hooks| {
+			let mut hooks = hooks.borrow_mut();
+			let Some(state) = hooks.get_mut(&registry_key(l)) else { return };
+			state.entries.retain(|e| e.id != self.id);
+
+			// If something replaced our hook since, leave theirs alone.
+			let ours = is_ours(lua_gethook(l));
+
+			if state.entries.is_empty() {
+				let state = hooks.remove(&registry_key(l)).expect("just got it");
+				if ours {
+					match state.previous {
+						Some(prev) => lua_sethook(l, prev.func, prev.mask, prev.count),
+						None => lua_sethook(l, dispatch, 0, 0)
+					};
+				}
+			} else if ours {
+				state.apply(l);
+			}
+		});
+	}
+}
+
+/// Calls ``f`` on the events in ``mask`` (made of [MASKCALL], [MASKRET], [MASKLINE] and [MASKCOUNT]),
+/// with [LuaDebug] filled in by [lua_getinfo] with "nSl". With [MASKCOUNT], it is called every ``count`` instructions.
+///
+/// Any number of hooks can be set on a state. A hook that was already set (like with ``debug.sethook``) keeps being called,
+/// and is put back once every hook set with this is removed.
+///
+/// Returning an error raises it as a lua error where the hook was triggered, which is how runaway code can be stopped.
+/// Panics are caught like in a ``#[lua_function]`` taking the state, so they abort on 64 bit unix.
+///
+/// Note that LuaJIT doesn't run line and count hooks in code it compiled, so loops may need ``jit.off()`` to be hooked.
+/// # Panics
+/// If ``mask`` has [MASKCOUNT] and ``count`` isn't positive.
+/// # Examples
+/// ```rust
+/// use rglua::prelude::*;
+/// use rglua::debug::{set_hook, HookEvent};
+///
+/// #[lua_function]
+/// fn run_watched(l: LuaState, code: String) -> Result<(), LuaError> {
+///     let mut budget = 1000;
+///     let _hook = set_hook(l, MASKCOUNT, 1000, move |_, _, _| {
+///         budget -= 1;
+///         if budget == 0 {
+///             return Err(String::from("took too long"));
+///         }
+///         Ok(())
+///     });
+///
+///     let lua = unsafe { Lua::from_raw(l) };
+///     lua.load_buffer(code.as_bytes(), "=watched", Some("t"))?;
+///     lua.pcall(0, 0)
+/// }
+/// ```
+pub fn set_hook<F>(l: LuaState, mask: c_int, count: c_int, f: F) -> Hook
+where
+	F: FnMut(LuaState, HookEvent, &LuaDebug) -> Result<(), String> + 'static
+{
+	assert!(mask & MASKCOUNT == 0 || count > 0, "Count hooks need a positive count");
+
+	let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+
+	HOOKS.with(|hooks| {
+		let mut hooks = hooks.borrow_mut();
+		let state = hooks.entry(registry_key(l)).or_insert_with(|| Hooks {
+			previous: lua_gethook(l).filter(|&f| !is_ours(Some(f))).map(|func| Previous {
+				func,
+				mask: lua_gethookmask(l),
+				count: lua_gethookcount(l),
+				elapsed: 0
+			}),
+			entries: vec![],
+			count: 0
+		});
+
+		state.entries.push(Entry {
+			id,
+			mask,
+			count,
+			elapsed: 0,
+			callback: Rc::new(RefCell::new(f))
+		});
+		state.apply(l);
+	});
+
+	Hook { l, id }
+}
+
+extern "C-unwind" fn dispatch(l: LuaState, ar: *mut LuaDebug) -> c_int {
+	let ar = unsafe { &mut *ar };
+	let Some(event) = HookEvent::decode(ar) else {
+		return 0;
+	};
+
+	// Picks what to call without holding the borrow, so the callbacks can set and remove hooks.
+	let (previous, callbacks) = HOOKS.with(|hooks| {
+		let mut hooks = hooks.borrow_mut();
+		let Some(state) = hooks.get_mut(&registry_key(l)) else {
+			return (None, vec![]);
+		};
+
+		let ran = state.count;
+		let fires = |mask: c_int, count: c_int, elapsed: &mut c_int| {
+			if mask & event.mask() == 0 {
+				return false;
+			}
+
+			if event != HookEvent::Count {
+				return true;
+			}
+
+			*elapsed += ran;
+			if *elapsed >= count {
+				*elapsed -= count;
+				true
+			} else {
+				false
+			}
+		};
+
+		let previous = state
+			.previous
+			.as_mut()
+			.and_then(|p| fires(p.mask, p.count, &mut p.elapsed).then_some(p.func));
+
+		let callbacks: Vec<_> = state
+			.entries
+			.iter_mut()
+			.filter_map(|e| fires(e.mask, e.count, &mut e.elapsed).then(|| e.callback.clone()))
+			.collect();
+
+		(previous, callbacks)
+	});
+
+	if let Some(previous) = previous {
+		previous(l, ar);
+	}
+
+	if callbacks.is_empty() {
+		return 0;
+	}
+
+	lua_getinfo(l, cstr!("nSl"), ar);
+
+	let result = catch_panic_with_state(move || {
+		for callback in callbacks {
+			// Only busy if a callback somehow triggered itself
+			if let Ok(mut callback) = callback.try_borrow_mut() {
+				callback(l, event, ar)?;
+			}
+		}
+		Ok(())
+	});
+
+	match result {
+		Ok(Ok(())) => 0,
+		Ok(Err(msg)) | Err(msg) => Raise::error(l, msg).raise(l)
+	}
+}
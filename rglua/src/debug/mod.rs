@@ -0,0 +1,196 @@
+//! Owned stack traces of a lua state, from [lua_getstack] and [lua_getinfo], and debug hooks running closures with [set_hook].
+//! # Examples
+//! ```rust
+//! use rglua::prelude::*;
+//! use rglua::debug::Backtrace;
+//!
+//! #[lua_function]
+//! fn where_am_i(l: LuaState) -> String {
+//!     // Level 0 is this function
+//!     Backtrace::capture_from(l, 1, false).to_string()
+//! }
+//! ```
+mod hook;
+pub use hook::*;
+
+use crate::lua::*;
+use crate::state::{to_bytes, type_name};
+use std::ffi::CStr;
+use std::fmt;
+
+/// A local variable or upvalue of a [Frame].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variable {
+	pub name: String,
+	/// Name of the type of the value, like "number".
+	pub type_name: &'static str,
+	/// The value for strings, numbers, booleans and nil, otherwise its type and address like ``table: 0x1234``.
+	/// Metamethods aren't called.
+	pub value: String
+}
+
+/// A function running at some level of the stack.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Frame {
+	/// The chunk name the function was defined in, like ``@lua/autorun/foo.lua`` or ``=[C]``.
+	pub source: String,
+	/// A printable version of [Frame::source], for error messages.
+	pub short_src: String,
+	/// The line being run, None if unknown (like for C functions).
+	pub line: Option<u32>,
+	/// The line the function was defined at, None if unknown.
+	pub line_defined: Option<u32>,
+	/// A name for the function, going by how it was called. None if there isn't one.
+	pub name: Option<String>,
+	/// How the name was found, ``global``, ``local``, ``method``, ``field``, ``upvalue`` or empty.
+	pub namewhat: String,
+	/// ``Lua`` for lua functions, ``C`` for C functions, ``main`` for the main part of a chunk and ``tail`` for tail calls.
+	pub what: String,
+	/// Number of upvalues of the function.
+	pub nups: u32,
+	/// Local variables active at [Frame::line], if they were asked for.
+	pub locals: Vec<Variable>,
+	/// Upvalues of the function, if they were asked for.
+	pub upvalues: Vec<Variable>
+}
+
+impl Frame {
+	/// Reads the frame at ``level`` (0 being the running function), or None if the stack isn't that deep.
+	pub fn at(l: LuaState, level: c_int, variables: bool) -> Option<Self> {
+		let mut ar = LuaDebug::default();
+		if lua_getstack(l, level, &mut ar) == 0 {
+			return None;
+		}
+
+		lua_getinfo(l, cstr!("nSluf"), &mut ar);
+		let mut frame = Self::from_debug(&ar);
+
+		if variables {
+			let mut n = 1;
+			loop {
+				let name = lua_getlocal(l, &mut ar, n);
+				if name.is_null() {
+					break;
+				}
+
+				let name = to_string(name).unwrap_or_default();
+				// Internal values like (*temporary)
+				if !name.starts_with('(') {
+					frame.locals.push(variable(l, name));
+				}
+				lua_pop(l, 1);
+				n += 1;
+			}
+
+			for i in 1..=ar.nups {
+				let name = lua_getupvalue(l, -1, i);
+				if name.is_null() {
+					break;
+				}
+
+				frame.upvalues.push(variable(l, to_string(name).unwrap_or_default()));
+				lua_pop(l, 1);
+			}
+		}
+
+		// The function pushed by "f"
+		lua_pop(l, 1);
+		Some(frame)
+	}
+
+	/// Copies what [lua_getinfo] filled in ``ar``. Fields it wasn't asked for are left empty.
+	pub fn from_debug(ar: &LuaDebug) -> Self {
+		let short_src = unsafe { CStr::from_ptr(ar.short_src.as_ptr()) };
+
+		Self {
+			source: to_string(ar.source).unwrap_or_default(),
+			short_src: short_src.to_string_lossy().into_owned(),
+			line: u32::try_from(ar.currentline).ok(),
+			line_defined: u32::try_from(ar.linedefined).ok(),
+			name: to_string(ar.name),
+			namewhat: to_string(ar.namewhat).unwrap_or_default(),
+			what: to_string(ar.what).unwrap_or_default(),
+			nups: ar.nups.max(0) as u32,
+			locals: vec![],
+			upvalues: vec![]
+		}
+	}
+}
+
+impl fmt::Display for Frame {
+	/// Formats like a line of [luaL_traceback], ``main.lua:4: in function 'foo'``.
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.line {
+			Some(line) => write!(f, "{}:{line}: ", self.short_src)?,
+			None => write!(f, "{}: ", self.short_src)?
+		}
+
+		match (&self.name, self.what.as_str()) {
+			(Some(name), _) if !self.namewhat.is_empty() => write!(f, "in function '{name}'"),
+			(_, "main") => write!(f, "in main chunk"),
+			(_, "C") | (_, "tail") => write!(f, "?"),
+			_ => write!(f, "in function <{}:{}>", self.short_src, self.line_defined.unwrap_or(0))
+		}
+	}
+}
+
+/// The frames of a stack, innermost first.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Backtrace {
+	pub frames: Vec<Frame>
+}
+
+impl Backtrace {
+	/// Captures the whole stack, without variables.
+	pub fn capture(l: LuaState) -> Self {
+		Self::capture_from(l, 0, false)
+	}
+
+	/// Captures the stack starting at ``level``, with the locals and upvalues of each frame if ``variables`` is true.
+	pub fn capture_from(l: LuaState, level: c_int, variables: bool) -> Self {
+		let frames = (level..).map_while(|level| Frame::at(l, level, variables)).collect();
+		Self { frames }
+	}
+}
+
+impl fmt::Display for Backtrace {
+	/// Formats like [luaL_traceback], with variables indented under their frame.
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "stack traceback:")?;
+
+		for frame in &self.frames {
+			write!(f, "\n\t{frame}")?;
+
+			for var in &frame.locals {
+				write!(f, "\n\t\tlocal {} = {}", var.name, var.value)?;
+			}
+
+			for var in &frame.upvalues {
+				write!(f, "\n\t\tupvalue {} = {}", var.name, var.value)?;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+fn to_string(ptr: LuaString) -> Option<String> {
+	(!ptr.is_null()).then(|| unsafe { CStr::from_ptr(ptr) }.to_string_lossy().into_owned())
+}
+
+/// Describes the value on top of the stack.
+fn variable(l: LuaState, name: String) -> Variable {
+	let value = match lua_type(l, -1) {
+		TNIL => String::from("nil"),
+		TBOOLEAN => (lua_toboolean(l, -1) != 0).to_string(),
+		TNUMBER => String::from_utf8_lossy(&to_bytes(l, -1).unwrap_or_default()).into_owned(),
+		TSTRING => format!("{:?}", String::from_utf8_lossy(&to_bytes(l, -1).unwrap_or_default())),
+		_ => format!("{}: {:p}", type_name(l, -1), lua_topointer(l, -1))
+	};
+
+	Variable {
+		name,
+		type_name: type_name(l, -1),
+		value
+	}
+}
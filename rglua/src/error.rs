@@ -0,0 +1,152 @@
+//! [LuaError], returned by protected calls and loading functions like [Lua::pcall](crate::state::Lua::pcall) and [Lua::load_buffer](crate::state::Lua::load_buffer).
+use crate::convert::FromLuaError;
+use crate::lua::*;
+use crate::state::to_bytes;
+use std::fmt;
+
+/// Separates the error message from the traceback added by [traceback].
+const TRACEBACK_HEADER: &str = "\nstack traceback:\n";
+
+/// An error from running or loading lua code, one for each status code lua returns.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum LuaError {
+	/// [ERRRUN], an error raised while running. Has the traceback when it went through the [traceback] handler.
+	#[error("{message}")]
+	Runtime {
+		message: String,
+		traceback: Vec<TracebackFrame>
+	},
+
+	/// [ERRSYNTAX], the code couldn't be compiled.
+	#[error("{message}")]
+	Syntax { message: String },
+
+	/// [ERRMEM], memory couldn't be allocated.
+	#[error("{message}")]
+	Memory { message: String },
+
+	/// [ERRERR], the error handler errored.
+	#[error("error in error handling: {message}")]
+	Handler { message: String },
+
+	/// [ERRFILE], a file couldn't be opened or read.
+	#[error("{message}")]
+	File { message: String },
+
+	/// The call succeeded, but the values it returned couldn't be converted.
+	#[error("bad return value: {0}")]
+	Returns(#[from] FromLuaError)
+}
+
+impl LuaError {
+	/// Creates the error for ``status``, parsing the traceback out of runtime error messages.
+	/// Unknown status codes are treated as runtime errors.
+	pub fn new(status: c_int, message: String) -> Self {
+		match status {
+			ERRSYNTAX => Self::Syntax { message },
+			ERRMEM => Self::Memory { message },
+			ERRERR => Self::Handler { message },
+			ERRFILE => Self::File { message },
+			_ => match message.split_once(TRACEBACK_HEADER) {
+				Some((message, traceback)) => Self::Runtime {
+					message: message.to_owned(),
+					traceback: traceback.lines().filter_map(TracebackFrame::parse).collect()
+				},
+				None => Self::Runtime {
+					message,
+					traceback: vec![]
+				}
+			}
+		}
+	}
+
+	/// Pops the error value left on the stack by a call or load that failed with ``status``.
+	pub fn pop(l: LuaState, status: c_int) -> Self {
+		let message = match to_bytes(l, -1) {
+			Some(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+			None => format!("(error object is a {} value)", crate::state::type_name(l, -1))
+		};
+
+		lua_pop(l, 1);
+		Self::new(status, message)
+	}
+
+	/// The status code, like [ERRRUN]. Conversion errors of returned values count as runtime errors.
+	pub fn status(&self) -> c_int {
+		match self {
+			Self::Runtime { .. } | Self::Returns(_) => ERRRUN,
+			Self::Syntax { .. } => ERRSYNTAX,
+			Self::Memory { .. } => ERRMEM,
+			Self::Handler { .. } => ERRERR,
+			Self::File { .. } => ERRFILE
+		}
+	}
+
+	/// The error message, without the traceback.
+	pub fn message(&self) -> String {
+		match self {
+			Self::Runtime { message, .. }
+			| Self::Syntax { message }
+			| Self::Memory { message }
+			| Self::Handler { message }
+			| Self::File { message } => message.clone(),
+			Self::Returns(why) => why.to_string()
+		}
+	}
+
+	/// The frames of the traceback, outermost last. Empty for anything but runtime errors.
+	pub fn traceback(&self) -> &[TracebackFrame] {
+		match self {
+			Self::Runtime { traceback, .. } => traceback,
+			_ => &[]
+		}
+	}
+}
+
+/// A line of a traceback made by [luaL_traceback], like ``main.lua:4: in function 'foo'``.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TracebackFrame {
+	/// The chunk name, or ``[C]`` for C functions.
+	pub source: String,
+	/// The line, if it's a lua function.
+	pub line: Option<u32>,
+	/// What was running, like ``in function 'foo'`` or ``in main chunk``.
+	pub context: String
+}
+
+impl TracebackFrame {
+	/// Parses a line of a traceback, returning None for lines that aren't frames (like the ``...`` for skipped levels).
+	pub fn parse(line: &str) -> Option<Self> {
+		let (location, context) = line.trim_start().split_once(": ")?;
+
+		let (source, line) = match location.rsplit_once(':') {
+			Some((source, line)) if line.parse::<u32>().is_ok() => (source, line.parse().ok()),
+			_ => (location, None)
+		};
+
+		Some(Self {
+			source: source.to_owned(),
+			line,
+			context: context.to_owned()
+		})
+	}
+}
+
+impl fmt::Display for TracebackFrame {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.line {
+			Some(line) => write!(f, "{}:{line}: {}", self.source, self.context),
+			None => write!(f, "{}: {}", self.source, self.context)
+		}
+	}
+}
+
+/// Message handler for [lua_pcall] that appends a traceback to string errors, which [LuaError] parses back out.
+/// Other error values are left alone.
+pub extern "C-unwind" fn traceback(l: LuaState) -> c_int {
+	if lua_type(l, 1) == TSTRING {
+		let msg = lua_tostring(l, 1);
+		luaL_traceback(l, l, msg, 1);
+	}
+	1
+}
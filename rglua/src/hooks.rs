@@ -0,0 +1,73 @@
+//! Adding Rust closures to gmod's ``hook`` library, and calling hooks.
+//!
+//! Hooks added here are recorded in the [ModuleContext] of the state, so they're removed when the module is closed.
+//! # Examples
+//! ```rust
+//! use rglua::prelude::*;
+//! use rglua::hooks;
+//!
+//! #[gmod_open]
+//! fn open(l: LuaState) -> Result<i32, LuaError> {
+//!     hooks::add(l, "PlayerSay", "mymodule", |l, (_ply, text): (LuaRef, String)| {
+//!         printgm!(l, "someone said {text}");
+//!     })?;
+//!
+//!     let blocked: Option<bool> = hooks::call(l, "MyModuleLoaded", ())?;
+//!     Ok(0)
+//! }
+//!
+//! #[gmod_close]
+//! fn close(_l: LuaState) -> i32 {
+//!     // The hook is removed after this
+//!     0
+//! }
+//! ```
+use crate::closure::push_closure;
+use crate::context::{hook_key, remove_hook, untrack, ModuleContext};
+use crate::convert::{FromLuaMulti, ToLuaMulti};
+use crate::error::LuaError;
+use crate::lua::*;
+use crate::reference::LuaRef;
+use crate::state::call_library;
+
+/// Adds ``f`` as the hook ``id`` on ``event`` with ``hook.Add``, replacing any hook with the same ``id``.
+///
+/// It's called with the arguments of the event converted to ``A``, like ``(LuaRef, String)`` for ``PlayerSay``.
+/// Returning anything but ``()`` (or None) overrides the event, like returning from a hook in lua.
+pub fn add<A, R, F>(l: LuaState, event: &str, id: &str, f: F) -> Result<(), LuaError>
+where
+	A: FromLuaMulti,
+	R: ToLuaMulti,
+	F: Fn(LuaState, A) -> R + 'static
+{
+	push_closure(l, f);
+	let func = LuaRef::pop(l);
+	call_library::<_, ()>(l, "hook", "Add", (event, id, &func))?;
+
+	ModuleContext::get(l).track(hook_key(event, id), remove_hook(event, id));
+	Ok(())
+}
+
+/// Removes the hook ``id`` on ``event`` with ``hook.Remove``.
+pub fn remove(l: LuaState, event: &str, id: &str) -> Result<(), LuaError> {
+	untrack(l, &hook_key(event, id));
+
+	call_library(l, "hook", "Remove", (event, id))
+}
+
+/// Arguments of ``hook.Call``, with ``GAMEMODE`` as the gamemode like ``hook.Run``.
+struct CallArgs<'a, A>(&'a str, A);
+
+impl<A: ToLuaMulti> ToLuaMulti for CallArgs<'_, A> {
+	fn push_multi(self, l: LuaState) -> c_int {
+		lua_pushlstring(l, self.0.as_ptr() as LuaString, self.0.len());
+		lua_getglobal(l, cstr!("GAMEMODE"));
+		2 + self.1.push_multi(l)
+	}
+}
+
+/// Runs the hooks of ``event`` (and the function for it of ``GAMEMODE``) with ``args`` using ``hook.Call``,
+/// returning what the hook that overrode it returned, converted to ``R``.
+pub fn call<A: ToLuaMulti, R: FromLuaMulti>(l: LuaState, event: &str, args: A) -> Result<R, LuaError> {
+	call_library(l, "hook", "Call", CallArgs(event, args))
+}
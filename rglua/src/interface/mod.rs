@@ -22,14 +22,13 @@ mod client;
 
 pub use cvar::{CVar, ConVar};
 pub use engine::{EngineClient, EngineServer};
-pub use lua::{LuaInterface, LuaObject, LuaShared};
+pub use lua::{LuaBase, LuaInterface, LuaObject, LuaShared};
 pub use materials::MaterialSystem;
 pub use mdl::{MdlCache, MdlCacheNotify};
 pub use net::{NetChannelInfo, NetChannel, NetChannelHandler, NetMessage, CNetChan};
 pub use panel::Panel;
 pub use client::Client;
 
-use crate::try_cstr;
 use libloading::{Library, Symbol};
 use std::ffi::c_void;
 
@@ -90,7 +89,8 @@ pub enum Error {
 /// Getting the raw PaintTraverse function from vgui:
 /// ```no_run
 /// // Wrappers to these interfaces are already provided but they do not give raw function pointers which is needed to detour / modify the functions
-/// // in any way, which you may want to do here, especially for painttraverse since you can safely run lua here if you queue it from a thread to avoid crashes.
+/// // in any way, which you may want to do here, especially for painttraverse since you can safely run lua here if you queue it from a thread (see rglua::queue) to avoid crashes.
+/// # #[cfg(target_arch = "x86")] {
 /// use rglua::{prelude::*, interface::Panel};
 /// type PaintTraverseFn = extern "fastcall" fn(&'static Panel, usize, bool, bool);
 /// let vgui = iface!(Panel).expect("Couldn't get VGUI interface");
@@ -103,6 +103,7 @@ pub enum Error {
 ///             .read()
 ///     )
 /// };
+/// # }
 /// ```
 pub fn get_from_interface(iface: &str, factory: CreateInterfaceFn) -> Result<*mut (), Error> {
 	let mut status = 0;
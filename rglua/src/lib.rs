@@ -8,5 +8,29 @@ pub mod lua;
 pub use lua::types;
 
 pub use rglua_macros::*;
+pub mod bytecode;
+pub mod closure;
+pub mod context;
+pub mod convert;
+pub mod data;
+pub mod debug;
+pub mod error;
+pub mod hooks;
+pub mod limit;
 pub mod prelude;
+pub mod queue;
+pub mod reference;
+pub mod registry;
+pub mod runtime;
+pub mod sandbox;
+pub mod state;
+pub mod table;
+pub mod thread;
+pub mod timer;
 pub mod userdata;
+
+#[doc(hidden)]
+pub mod __private;
+
+#[cfg(feature = "testing")]
+pub mod testing;
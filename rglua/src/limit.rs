@@ -0,0 +1,185 @@
+//! Running untrusted code with limits on instructions, time and memory, see [run_limited].
+use crate::convert::FromLuaMulti;
+use crate::debug::set_hook;
+use crate::error::{traceback, LuaError};
+use crate::lua::*;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// How often the instruction and time limits are checked, in instructions.
+pub const CHECK_INTERVAL: u64 = 1000;
+
+/// Limits for [run_limited]. Anything left as None isn't limited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+	/// Most VM instructions to run. Checked every [CHECK_INTERVAL] instructions (or less if this is smaller).
+	pub max_instructions: Option<u64>,
+	/// Longest the chunk may run. Checked every [CHECK_INTERVAL] instructions.
+	pub max_duration: Option<Duration>,
+	/// Most bytes the state may allocate, on top of what it was using when the run started.
+	pub max_memory: Option<usize>
+}
+
+/// Error from [run_limited], telling apart a limit being hit from the chunk failing on its own.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum LimitError {
+	#[error("instruction limit of {0} reached")]
+	Instructions(u64),
+
+	#[error("time limit of {0:?} reached")]
+	Duration(Duration),
+
+	#[error("memory limit of {0} bytes reached")]
+	Memory(usize),
+
+	/// The chunk failed to load or errored, without hitting a limit.
+	#[error(transparent)]
+	Lua(#[from] LuaError)
+}
+
+/// Memory budget, the userdata of [limited_alloc].
+struct Budget {
+	inner: LuaAlloc,
+	inner_ud: *mut c_void,
+	used: isize,
+	max: isize,
+	hit: bool
+}
+
+extern "C" fn limited_alloc(ud: *mut c_void, ptr: *mut c_void, osize: usize, nsize: usize) -> *mut c_void {
+	let budget = unsafe { &mut *(ud as *mut Budget) };
+
+	// Shrinking and freeing always go through, as lua can't handle those failing.
+	let grow = nsize as isize - osize as isize;
+	if grow > 0 && budget.used + grow > budget.max {
+		budget.hit = true;
+		return std::ptr::null_mut();
+	}
+
+	// Freeing what was allocated before the run doesn't make room for more.
+	let new = (budget.inner)(budget.inner_ud, ptr, osize, nsize);
+	if !new.is_null() || nsize == 0 {
+		budget.used = (budget.used + grow).max(0);
+	}
+	new
+}
+
+/// Puts back the allocator replaced by [limited_alloc].
+struct AllocGuard {
+	l: LuaState,
+	budget: Box<Budget>
+}
+
+impl AllocGuard {
+	fn new(l: LuaState, max: usize) -> Self {
+		let mut inner_ud = std::ptr::null_mut();
+		let inner = lua_getallocf(l, &mut inner_ud);
+
+		let mut budget = Box::new(Budget {
+			inner,
+			inner_ud,
+			used: 0,
+			max: max.min(isize::MAX as usize) as isize,
+			hit: false
+		});
+
+		lua_setallocf(l, limited_alloc, &mut *budget as *mut Budget as *mut c_void);
+		Self { l, budget }
+	}
+}
+
+impl Drop for AllocGuard {
+	fn drop(&mut self) {
+		lua_setallocf(self.l, self.budget.inner, self.budget.inner_ud);
+	}
+}
+
+/// Loads ``chunk`` as text and calls it, returning what it returns.
+/// Aborts it with a [LimitError] if it goes over one of the ``limits``.
+///
+/// Instruction and time limits raise a lua error from a count hook (see [set_hook]), then keep raising it on every
+/// instruction, so the chunk can't get around them with ``pcall``. JIT compilation is turned off for the chunk,
+/// as LuaJIT doesn't run hooks in compiled code. Functions the chunk calls that were compiled before aren't limited.
+///
+/// The memory limit wraps the allocator of the state for the duration of the run, failing allocations over the budget.
+/// This affects every thread of the state, so other code running meanwhile (like ``__gc`` metamethods) counts too.
+/// # Examples
+/// ```rust
+/// use rglua::prelude::*;
+/// use rglua::limit::{run_limited, Limits, LimitError};
+/// use std::time::Duration;
+///
+/// #[lua_function]
+/// fn run_snippet(l: LuaState, code: String) -> Result<Option<String>, LimitError> {
+///     let limits = Limits {
+///         max_instructions: Some(1_000_000),
+///         max_duration: Some(Duration::from_millis(50)),
+///         max_memory: Some(1024 * 1024)
+///     };
+///
+///     run_limited(l, &code, limits)
+/// }
+/// ```
+pub fn run_limited<R: FromLuaMulti>(l: LuaState, chunk: &str, limits: Limits) -> Result<R, LimitError> {
+	let top = lua_gettop(l);
+	lua_pushcfunction(l, traceback);
+
+	let status = luaL_loadbufferx(l, chunk.as_ptr() as LuaString, chunk.len(), cstr!("=limited"), cstr!("t"));
+	if status != OK {
+		let e = LuaError::pop(l, status);
+		lua_settop(l, top);
+		return Err(e.into());
+	}
+	luaJIT_setmode(l, -1, jit::MODE_ALLFUNC | jit::MODE_OFF);
+
+	let hit = Rc::new(Cell::new(None));
+	let trap = Rc::new(RefCell::new(None));
+	let hook = (limits.max_instructions.is_some() || limits.max_duration.is_some()).then(|| {
+		let interval = limits.max_instructions.map_or(CHECK_INTERVAL, |max| max.clamp(1, CHECK_INTERVAL));
+		let start = Instant::now();
+		let mut ran = 0;
+
+		let (hit, trap) = (hit.clone(), trap.clone());
+		set_hook(l, MASKCOUNT, interval as c_int, move |l, _, _| {
+			ran += interval;
+
+			let limit = match (limits.max_instructions, limits.max_duration) {
+				(Some(max), _) if ran >= max => LimitError::Instructions(max),
+				(_, Some(max)) if start.elapsed() >= max => LimitError::Duration(max),
+				_ => return Ok(())
+			};
+
+			let msg = limit.to_string();
+			hit.set(Some(limit));
+
+			// Raise on every instruction from now on, so a pcall in the chunk only gets it one level further out.
+			let again = msg.clone();
+			*trap.borrow_mut() = Some(set_hook(l, MASKCOUNT, 1, move |_, _, _| Err(again.clone())));
+			Err(msg)
+		})
+	});
+
+	// Only the call itself is limited, so running out of memory can't raise an error outside of it.
+	let alloc = limits.max_memory.map(|max| AllocGuard::new(l, max));
+	let status = lua_pcall(l, 0, R::COUNT, top + 1);
+	let memory_hit = alloc.is_some_and(|alloc| alloc.budget.hit);
+
+	drop(hook);
+	trap.take();
+
+	let result = match status {
+		OK => R::from_lua_multi(l, top + 2).map_err(|e| LimitError::Lua(e.into())),
+		status => {
+			let e = LuaError::pop(l, status);
+			match hit.take() {
+				Some(limit) => Err(limit),
+				None if memory_hit => Err(LimitError::Memory(limits.max_memory.unwrap_or_default())),
+				None => Err(e.into())
+			}
+		}
+	};
+
+	lua_settop(l, top);
+	result
+}
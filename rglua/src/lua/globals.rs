@@ -72,6 +72,9 @@ pub const ERRMEM: c_int = 4;
 /// Error when running the  error handler, code used by functions like [lua_pcall](super::lua_pcall)
 pub const ERRERR: c_int = 5;
 
+/// Error opening or reading a file, code used by [luaL_loadfile](super::luaL_loadfile)
+pub const ERRFILE: c_int = 6;
+
 /// Enum used with [lua_gc](super::lua_gc) - Stops the garbage collector.
 pub const GCSTOP: c_int = 0;
 
@@ -134,21 +137,21 @@ pub mod jit {
 	pub const MODE_MASK: c_int = 0x00ff;
 
 	/// Enum used by [luaJIT_setmode](crate::lua::luaJIT_setmode) -- Set mode for the whole JIT engine
-	pub const MODE_ENGINE: c_int = 1;
+	pub const MODE_ENGINE: c_int = 0;
 
 	/// Enum used by [luaJIT_setmode](crate::lua::luaJIT_setmode) -- Set debug mode (idx = level).
-	pub const MODE_DEBUG: c_int = 2;
+	pub const MODE_DEBUG: c_int = 1;
 
 	/// Enum used by [luaJIT_setmode](crate::lua::luaJIT_setmode) -- Change mode for a function.
-	pub const MODE_FUNC: c_int = 3;
+	pub const MODE_FUNC: c_int = 2;
 
 	/// Enum used by [luaJIT_setmode](crate::lua::luaJIT_setmode) -- Recurse into subroutine protos.
-	pub const MODE_ALLFUNC: c_int = 4;
+	pub const MODE_ALLFUNC: c_int = 3;
 	/// Enum used by [luaJIT_setmode](crate::lua::luaJIT_setmode) -- Change only the subroutines.
-	pub const MODE_ALLSUBFUNC: c_int = 5;
+	pub const MODE_ALLSUBFUNC: c_int = 4;
 
 	/// Enum used by [luaJIT_setmode](crate::lua::luaJIT_setmode) -- Flush a compiled trace.
-	pub const MODE_TRACE: c_int = 6;
+	pub const MODE_TRACE: c_int = 5;
 
 	/// Enum used by [luaJIT_setmode](crate::lua::luaJIT_setmode) -- Set wrapper mode for C function calls.
 	pub const MODE_WRAPCFUNC: c_int = 0x10;
@@ -0,0 +1,278 @@
+use libloading::Library;
+use once_cell::sync::OnceCell;
+use std::path::{Path, PathBuf};
+
+/// Environment variable that, when set, points rglua to the lua_shared library to load.
+/// It is tried after an explicit [LuaSharedLoader::path] and before the default gmod paths.
+pub const LUA_SHARED_ENV: &str = "RGLUA_LUA_SHARED";
+
+static LUA_SHARED: OnceCell<Library> = OnceCell::new();
+
+/// Paths to lua_shared relative to the gmod directory, in the order they are tried.
+#[cfg(all(target_os = "windows", target_arch = "x86_64"))]
+pub const DEFAULT_PATHS: &[&str] = &["bin/win64/lua_shared.dll"];
+
+/// Paths to lua_shared relative to the gmod directory, in the order they are tried.
+#[cfg(all(target_os = "windows", target_arch = "x86"))]
+pub const DEFAULT_PATHS: &[&str] = &["garrysmod/bin/lua_shared.dll", "bin/lua_shared.dll"];
+
+/// Paths to lua_shared relative to the gmod directory, in the order they are tried.
+#[cfg(target_os = "macos")]
+pub const DEFAULT_PATHS: &[&str] = &["garrysmod/bin/lua_shared.dylib"];
+
+/// Paths to lua_shared relative to the gmod directory, in the order they are tried.
+#[cfg(all(target_os = "linux", target_arch = "x86"))]
+pub const DEFAULT_PATHS: &[&str] = &[
+	"garrysmod/bin/lua_shared_srv.so",
+	"garrysmod/bin/lua_shared.so",
+	"bin/linux32/lua_shared.so",
+	"bin/linux32/lua_shared_client.so"
+];
+
+/// Paths to lua_shared relative to the gmod directory, in the order they are tried.
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub const DEFAULT_PATHS: &[&str] = &["bin/linux64/lua_shared.so", "bin/linux64/lua_shared_client.so"];
+
+/// Paths to lua_shared relative to the gmod directory, in the order they are tried.
+/// Gmod does not ship for this platform, so you'll need to provide a path yourself.
+#[cfg(not(any(
+	all(target_os = "windows", any(target_arch = "x86", target_arch = "x86_64")),
+	all(target_os = "linux", any(target_arch = "x86", target_arch = "x86_64")),
+	target_os = "macos"
+)))]
+pub const DEFAULT_PATHS: &[&str] = &[];
+
+/// A path that was tried while looking for lua_shared, and why it was skipped.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+	pub path: PathBuf,
+	pub reason: String
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum LuaSharedError {
+	#[error("Couldn't find lua_shared dylib! Searched:{}", fmt_candidates(.0))]
+	NotFound(Vec<Candidate>),
+
+	#[error("lua_shared has already been loaded")]
+	AlreadyLoaded,
+
+	#[error("lua_shared is missing functions: {}", .0.join(", "))]
+	MissingSymbols(Vec<&'static str>)
+}
+
+fn fmt_candidates(candidates: &[Candidate]) -> String {
+	if candidates.is_empty() {
+		return String::from(" nothing (no paths given and no defaults for this platform)");
+	}
+
+	candidates
+		.iter()
+		.map(|c| format!("\n\t{} ({})", c.path.display(), c.reason))
+		.collect()
+}
+
+/// Builder to find and open lua_shared.
+///
+/// Candidates are tried in this order:
+/// 1. Libraries already loaded into the process (if [LuaSharedLoader::reuse_loaded])
+/// 2. The explicit [LuaSharedLoader::path]
+/// 3. The path in the [LUA_SHARED_ENV] environment variable
+/// 4. [DEFAULT_PATHS], relative to [std::env::current_dir] (if [LuaSharedLoader::search_defaults])
+///
+/// Paths that are just a file name, like ``libluajit-5.1.so.2``, are searched for by the system's dynamic loader.
+/// # Examples
+/// ```rust, no_run
+/// use rglua::lua::LuaSharedLoader;
+/// LuaSharedLoader::new()
+///     .path("/usr/lib/x86_64-linux-gnu/libluajit-5.1.so.2")
+///     .search_defaults(false)
+///     .init()
+///     .expect("Couldn't load luajit");
+/// ```
+#[derive(Debug, Clone)]
+pub struct LuaSharedLoader {
+	paths: Vec<PathBuf>,
+	env: Option<String>,
+	reuse_loaded: bool,
+	search_defaults: bool,
+	base: Option<PathBuf>
+}
+
+impl Default for LuaSharedLoader {
+	fn default() -> Self {
+		Self {
+			paths: vec![],
+			env: Some(String::from(LUA_SHARED_ENV)),
+			reuse_loaded: true,
+			search_defaults: true,
+			base: None
+		}
+	}
+}
+
+impl LuaSharedLoader {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Adds an explicit path to try, before the environment variable and defaults.
+	/// Can be called multiple times, paths are tried in the order given.
+	pub fn path<P: AsRef<Path>>(mut self, path: P) -> Self {
+		self.paths.push(path.as_ref().to_owned());
+		self
+	}
+
+	/// Sets the environment variable to read a path from, or None to ignore the environment.
+	/// Defaults to [LUA_SHARED_ENV].
+	pub fn env(mut self, var: Option<&str>) -> Self {
+		self.env = var.map(String::from);
+		self
+	}
+
+	/// Whether to first look for a lua_shared that the host process already loaded, without loading a new copy.
+	/// This is what you want inside of gmod. Defaults to true.
+	pub fn reuse_loaded(mut self, reuse: bool) -> Self {
+		self.reuse_loaded = reuse;
+		self
+	}
+
+	/// Whether to try [DEFAULT_PATHS]. Defaults to true.
+	pub fn search_defaults(mut self, search: bool) -> Self {
+		self.search_defaults = search;
+		self
+	}
+
+	/// Directory [DEFAULT_PATHS] are relative to. Defaults to [std::env::current_dir].
+	pub fn base_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+		self.base = Some(dir.as_ref().to_owned());
+		self
+	}
+
+	/// Returns every path this loader would try, in order.
+	pub fn candidates(&self) -> Vec<PathBuf> {
+		let mut out = self.paths.clone();
+
+		if let Some(path) = self.env.as_ref().and_then(std::env::var_os) {
+			out.push(PathBuf::from(path));
+		}
+
+		if self.search_defaults {
+			let base = self
+				.base
+				.clone()
+				.or_else(|| std::env::current_dir().ok())
+				.unwrap_or_default();
+
+			out.extend(DEFAULT_PATHS.iter().map(|p| base.join(p)));
+		}
+
+		out
+	}
+
+	/// Finds and opens lua_shared without installing it for use by the functions in [crate::lua].
+	/// You probably want [LuaSharedLoader::init].
+	pub fn open(&self) -> Result<Library, LuaSharedError> {
+		let candidates = self.candidates();
+		let mut tried = vec![];
+
+		if self.reuse_loaded {
+			let mut names: Vec<&std::ffi::OsStr> = vec![];
+			for name in candidates.iter().filter_map(|p| p.file_name()) {
+				if !names.contains(&name) {
+					names.push(name);
+				}
+			}
+
+			for name in names {
+				match open_loaded(name) {
+					Ok(lib) => return Ok(lib),
+					Err(why) => tried.push(Candidate {
+						path: PathBuf::from(name),
+						reason: format!("not already loaded: {why}")
+					})
+				}
+			}
+		}
+
+		for path in candidates {
+			// Bare file names like libluajit-5.1.so.2 are left to the system's library search path.
+			let bare = path.parent().map_or(true, |dir| dir.as_os_str().is_empty());
+
+			if !bare && !path.exists() {
+				tried.push(Candidate {
+					path,
+					reason: String::from("does not exist")
+				});
+				continue;
+			}
+
+			match unsafe { Library::new(&path) } {
+				Ok(lib) => return Ok(lib),
+				Err(why) => tried.push(Candidate {
+					path,
+					reason: why.to_string()
+				})
+			}
+		}
+
+		Err(LuaSharedError::NotFound(tried))
+	}
+
+	/// Finds and opens lua_shared, installing it as the library every function in [crate::lua] calls into.
+	/// # Errors
+	/// Returns [LuaSharedError::AlreadyLoaded] if lua_shared was already initialized, or [LuaSharedError::NotFound] listing every path that was tried.
+	pub fn init(&self) -> Result<&'static Library, LuaSharedError> {
+		if LUA_SHARED.get().is_some() {
+			return Err(LuaSharedError::AlreadyLoaded);
+		}
+
+		let lib = self.open()?;
+		LUA_SHARED.set(lib).map_err(|_| LuaSharedError::AlreadyLoaded)?;
+
+		Ok(lua_shared_loaded().expect("lua_shared was just set"))
+	}
+}
+
+#[cfg(unix)]
+fn open_loaded(name: &std::ffi::OsStr) -> Result<Library, libloading::Error> {
+	use libloading::os::unix::{Library as UnixLibrary, RTLD_LAZY};
+
+	#[cfg(any(target_os = "macos", target_os = "ios"))]
+	const RTLD_NOLOAD: std::os::raw::c_int = 0x10;
+	#[cfg(not(any(target_os = "macos", target_os = "ios")))]
+	const RTLD_NOLOAD: std::os::raw::c_int = 0x4;
+
+	unsafe { UnixLibrary::open(Some(name), RTLD_LAZY | RTLD_NOLOAD) }.map(Library::from)
+}
+
+#[cfg(windows)]
+fn open_loaded(name: &std::ffi::OsStr) -> Result<Library, libloading::Error> {
+	libloading::os::windows::Library::open_already_loaded(name).map(Library::from)
+}
+
+/// Loads lua_shared from ``path`` (or [LUA_SHARED_ENV] if that fails) and installs it for use by the functions in [crate::lua].
+/// This has to be called before any lua function is, else the default gmod paths will be loaded instead.
+/// # Examples
+/// ```rust, no_run
+/// use rglua::prelude::*;
+/// init_lua_shared("/usr/lib/x86_64-linux-gnu/libluajit-5.1.so.2").expect("Couldn't load luajit");
+/// let l = luaL_newstate();
+/// ```
+pub fn init_lua_shared<P: AsRef<Path>>(path: P) -> Result<&'static Library, LuaSharedError> {
+	LuaSharedLoader::new()
+		.path(path)
+		.reuse_loaded(false)
+		.search_defaults(false)
+		.init()
+}
+
+/// Returns the lua_shared library if it has been initialized, without trying to load it.
+pub fn lua_shared_loaded() -> Option<&'static Library> {
+	LUA_SHARED.get()
+}
+
+/// Returns the lua_shared library, loading it with the default [LuaSharedLoader] if it hasn't been initialized yet.
+pub fn lua_shared() -> Result<&'static Library, LuaSharedError> {
+	LUA_SHARED.get_or_try_init(|| LuaSharedLoader::new().open())
+}
@@ -3,39 +3,72 @@ use crate::{
 	userdata::{Angle, Vector}
 };
 
-use super::LUA_SHARED_RAW;
-use once_cell::sync::Lazy;
+use super::{lua_shared, LUA_SHARED_RAW};
+use libloading::Library;
+use once_cell::sync::{Lazy, OnceCell};
 
 macro_rules! dyn_symbols {
 	(
-		$(#[$outer:meta])*
-		$vis:vis extern $abi:literal fn $name:ident ( $($arg:ident : $argty:ty),* $(,)? ) -> $ret:ty; $($rest:tt)*
+		variadic {
+			$(
+				$(#[$vouter:meta])*
+				$vvis:vis extern $vabi:literal fn $vname:ident ( $($varg:ident : $vargty:ty),+ , ... ) -> $vret:ty;
+			)*
+		}
+
+		$(
+			$(#[$outer:meta])*
+			$vis:vis extern $abi:literal fn $name:ident ( $($arg:ident : $argty:ty),* $(,)? ) -> $ret:ty;
+		)*
 	) => {
-		$(#[$outer])*
+		/// Every function bound from lua_shared, looked up once when the table is created.
+		/// Functions the library doesn't export are None, but the table the wrappers call through is checked to have all of them when it's created.
+		///
+		/// You normally don't need this, since every function has a wrapper with the same name that calls through [lua_shared_fns].
 		#[allow(non_snake_case)]
-		$vis fn $name( $($arg: $argty),* ) -> $ret {
-			unsafe {
-				let f = LUA_SHARED_RAW.get::<extern $abi fn($($argty),*) -> $ret>(stringify!($name).as_bytes())
-					.expect(concat!("Couldn't get extern function: ", stringify!($name)));
-				f( $($arg),* )
+		pub struct LuaSharedFns {
+			$( pub $vname: Option<extern $vabi fn( $($varg: $vargty),+ , ... ) -> $vret>, )*
+			$( pub $name: Option<extern $abi fn( $($argty),* ) -> $ret>, )*
+		}
+
+		impl LuaSharedFns {
+			/// Looks up every function that ``lib`` exports.
+			fn lookup(lib: &Library) -> Self {
+				unsafe {
+					Self {
+						$( $vname: lib.get::<extern $vabi fn( $($vargty),+ , ... ) -> $vret>( concat!(stringify!($vname), "\0").as_bytes() ).ok().map(|f| *f), )*
+						$( $name: lib.get::<extern $abi fn( $($argty),* ) -> $ret>( concat!(stringify!($name), "\0").as_bytes() ).ok().map(|f| *f), )*
+					}
+				}
+			}
+
+			/// Names of the functions that are None.
+			pub fn missing(&self) -> Vec<&'static str> {
+				let mut missing = vec![];
+				$( if self.$vname.is_none() { missing.push(stringify!($vname)); } )*
+				$( if self.$name.is_none() { missing.push(stringify!($name)); } )*
+				missing
 			}
 		}
-		dyn_symbols!( $($rest)* );
-	};
 
-	(
-		$(#[$outer:meta])*
-		$vis:vis extern $abi:literal fn $name:ident ( $($arg:ident : $argty:ty),+ , ... ) -> $ret:ty; $($rest:tt)*
-	) => {
-		$(#[$outer])*
-		#[allow(non_upper_case_globals)]
-		pub static $name: Lazy<extern $abi fn( $($arg: $argty),+ , ... ) -> $ret> = Lazy::new(|| unsafe {
-			std::mem::transmute( LUA_SHARED_RAW.get::<extern $abi fn($($argty),*) -> $ret>( stringify!($name).as_bytes() ).unwrap() )
-		});
-		dyn_symbols!( $($rest)* );
+		$(
+			$(#[$vouter])*
+			#[allow(non_upper_case_globals)]
+			$vvis static $vname: Lazy<extern $vabi fn( $($varg: $vargty),+ , ... ) -> $vret> = Lazy::new(|| {
+				lua_shared_fns().$vname.expect("checked when the table was created")
+			});
+		)*
+
+		$(
+			$(#[$outer])*
+			#[inline]
+			#[allow(non_snake_case)]
+			$vis fn $name( $($arg: $argty),* ) -> $ret {
+				let f = lua_shared_fns().$name.expect("checked when the table was created");
+				f( $($arg),* )
+			}
+		)*
 	};
-
-	() => ();
 }
 
 macro_rules! lua_macros {
@@ -53,14 +86,100 @@ macro_rules! lua_macros {
 	() => ();
 }
 
-// Create Lazy cells that'll find the functions at runtime when called.
+impl LuaSharedFns {
+	/// Looks up every function from ``lib``.
+	///
+	/// Functions that only gmod's lua_shared has are filled in with stand-ins when ``lib`` doesn't export them (like a stock LuaJIT),
+	/// so ``lua_resume_real`` is LuaJIT's ``lua_resume``, and ``luaL_newmetatable_type`` is done with [luaL_newmetatable].
+	pub fn resolve(lib: &Library) -> Self {
+		let mut fns = Self::lookup(lib);
+
+		if fns.lua_resume_real.is_none() {
+			fns.lua_resume_real = unsafe { lib.get::<extern "C-unwind" fn(LuaState, c_int) -> c_int>(b"lua_resume\0").ok().map(|f| *f) };
+		}
+		fns.luaL_newmetatable_type.get_or_insert(newmetatable_type);
+		fns
+	}
+
+	/// Returns the table if it has every function, or [LuaSharedError::MissingSymbols](super::LuaSharedError::MissingSymbols) listing the ones it doesn't.
+	fn check(self) -> Result<Self, super::LuaSharedError> {
+		let missing = self.missing();
+		if missing.is_empty() {
+			Ok(self)
+		} else {
+			Err(super::LuaSharedError::MissingSymbols(missing))
+		}
+	}
+}
+
+/// Same as gmod's luaL_newmetatable_type, creates the metatable and sets its MetaName and MetaID fields.
+extern "C-unwind" fn newmetatable_type(l: LuaState, tname: LuaString, tid: c_int) -> c_int {
+	if luaL_newmetatable(l, tname) == 0 {
+		return 0;
+	}
+
+	lua_pushstring(l, tname);
+	lua_setfield(l, -2, cstr!("MetaName"));
+	lua_pushinteger(l, tid as LuaInteger);
+	lua_setfield(l, -2, cstr!("MetaID"));
+	1
+}
+
+/// The table every function is called through, which has all of them.
+static LUA_SHARED_FNS: OnceCell<LuaSharedFns> = OnceCell::new();
+
+/// Returns the table of lua_shared functions, looking all of them up from [LUA_SHARED_RAW] the first time it is called.
+/// Call this in your entrypoint if you'd rather pay for the lookups upfront than on the first lua call.
+/// # Panics
+/// If lua_shared could not be found, like [LUA_SHARED_RAW], or is missing functions. Use [try_lua_shared_fns] to get an error instead.
+#[inline]
+pub fn lua_shared_fns() -> &'static LuaSharedFns {
+	match LUA_SHARED_FNS.get_or_try_init(|| LuaSharedFns::resolve(*LUA_SHARED_RAW).check()) {
+		Ok(fns) => fns,
+		Err(why) => panic!("{why}")
+	}
+}
+
+/// Like [lua_shared_fns], but returns an error instead of panicking if lua_shared could not be found or is missing functions.
+pub fn try_lua_shared_fns() -> Result<&'static LuaSharedFns, super::LuaSharedError> {
+	LUA_SHARED_FNS.get_or_try_init(|| lua_shared().and_then(|lib| LuaSharedFns::resolve(lib).check()))
+}
+
+/// Installs ``fns`` as the table every function in [crate::lua] calls through, instead of resolving it from [LUA_SHARED_RAW].
+/// Useful to replace or fill in functions, see [LuaSharedFns::resolve].
+/// # Errors
+/// Returns [LuaSharedError::MissingSymbols](super::LuaSharedError::MissingSymbols) if a function is None,
+/// or [LuaSharedError::AlreadyLoaded](super::LuaSharedError::AlreadyLoaded) if the table was already created.
+pub fn init_lua_shared_fns(fns: LuaSharedFns) -> Result<&'static LuaSharedFns, super::LuaSharedError> {
+	LUA_SHARED_FNS
+		.set(fns.check()?)
+		.map_err(|_| super::LuaSharedError::AlreadyLoaded)?;
+
+	Ok(lua_shared_fns())
+}
+
+// Every function here is looked up once into [LuaSharedFns] and called through it.
 // Credit to https://pgl.yoyo.org/luai/i/about for most of the documentation below here.
 // (Of course they were tweaked to be more concise and fit for this library)
-
-// Loading functions
 dyn_symbols! {
+	// Variadic functions can't be wrapped, so these are Lazy pointers to the functions instead.
+	variadic {
+		/// Pushes a formatted [LuaString] to the stack
+		/// Note this is not a direct function but instead a Lazy pointer to a function.
+		/// This is because variadic functions are not yet supported in Rust, besides through external functions and pointers to them.
+		pub extern "C-unwind" fn lua_pushfstring(l: LuaState, fmt: LuaString, ...) -> LuaString;
+
+		/// Raises an error.
+		/// The error message format is given by fmt plus any extra arguments, following the same rules of [lua_pushfstring].
+		/// It also adds at the beginning of the message the file name and the line number where the error occurred, if this information is available.
+		/// Note this is not a direct function but instead a Lazy pointer to a function.
+		/// This is because variadic functions are not yet supported in Rust, besides through external functions and pointers to them.
+		pub extern "C-unwind" fn luaL_error(l: LuaState, fmt: LuaString, ...) -> !;
+	}
+
+	// Loading functions
 	/// Function used by [luaL_loadbuffer].
-	pub extern "C" fn luaL_loadbufferx(
+	pub extern "C-unwind" fn luaL_loadbufferx(
 		l: LuaState,
 		code: LuaString,
 		size: SizeT,
@@ -70,7 +189,7 @@ dyn_symbols! {
 
 	/// Loads a buffer as a Lua chunk.
 	/// This function uses [lua_load] to load the chunk in the buffer pointed to by buff with size ``sz``.
-	pub extern "C" fn luaL_loadbuffer(
+	pub extern "C-unwind" fn luaL_loadbuffer(
 		l: LuaState,
 		code: LuaString,
 		size: SizeT,
@@ -92,7 +211,7 @@ dyn_symbols! {
 	/// # Notes
 	/// * This function only loads a chunk; it does not run it.
 	/// * [lua_load] automatically detects whether the chunk is text or binary, and loads it accordingly.
-	pub extern "C" fn lua_load(
+	pub extern "C-unwind" fn lua_load(
 		l: LuaState,
 		reader: LuaReader,
 		data: *mut c_void,
@@ -102,7 +221,7 @@ dyn_symbols! {
 	/// Function used by [lua_load] internally.
 	/// ``mode`` is whether to take the chunk as bytecode or as text.
 	/// You should just use [lua_load] instead though.
-	pub extern "C" fn lua_loadx(
+	pub extern "C-unwind" fn lua_loadx(
 		l: LuaState,
 		reader: LuaReader,
 		dt: *mut c_void,
@@ -113,21 +232,19 @@ dyn_symbols! {
 	/// Loads a string as a Lua chunk. This function uses [lua_load] to load the chunk in the zero-terminated string ``s``.
 	/// This function returns the same results as [lua_load].
 	/// Also as [lua_load], this function only loads the chunk; it does not run it.
-	pub extern "C" fn luaL_loadstring(l: LuaState, code: LuaString) -> c_int;
+	pub extern "C-unwind" fn luaL_loadstring(l: LuaState, code: LuaString) -> c_int;
 
 	/// Loads a file as a Lua chunk.
 	/// This function uses [lua_load] to load the chunk in the file named ``filename``.
 	/// If filename is [std::ptr::null_mut()], then it loads from the standard input.
 	/// The first line in the file is ignored if it starts with a # (shebang)
-	pub extern "C" fn luaL_loadfile(l: LuaState, filename: LuaString) -> c_int;
+	pub extern "C-unwind" fn luaL_loadfile(l: LuaState, filename: LuaString) -> c_int;
 
 	/// Same as how [lua_loadx] is to [lua_load].
 	/// You should probably use [luaL_loadfile] instead.
-	pub extern "C" fn luaL_loadfilex(l: LuaState, filename: LuaString, mode: LuaString) -> c_int;
-}
+	pub extern "C-unwind" fn luaL_loadfilex(l: LuaState, filename: LuaString, mode: LuaString) -> c_int;
 
-// Calling lua code
-dyn_symbols! {
+	// Calling lua code
 	/// Calls a function in protected mode.
 	/// Both nargs and nresults have the same meaning as in [lua_call].
 	/// If there are no errors during the call, [lua_pcall] behaves exactly like [lua_call].
@@ -146,8 +263,7 @@ dyn_symbols! {
 	/// * [ERRRUN] - There was an error at runtime
 	/// * [ERRMEM] - There was a memory allocation error
 	/// * [ERRERR] - Error when running the error handler
-	pub extern "C" fn lua_pcall(l: LuaState, nargs: c_int, nresults: c_int, errfunc: c_int) -> c_int;
-
+	pub extern "C-unwind" fn lua_pcall(l: LuaState, nargs: c_int, nresults: c_int, errfunc: c_int) -> c_int;
 
 	/// Calls a function.
 	/// To call a function you must use the following protocol: first, the function to be called is pushed onto the stack;
@@ -161,89 +277,83 @@ dyn_symbols! {
 	/// # Stack Behavior
 	/// All arguments and the function value are popped from the stack when the function is called.
 	/// The function results are pushed onto the stack when the function returns in direct order, so the last result is on the top of the stack.
-	pub extern "C" fn lua_call(l: LuaState, nargs: c_int, nresults: c_int) -> c_int;
+	pub extern "C-unwind" fn lua_call(l: LuaState, nargs: c_int, nresults: c_int) -> c_int;
 
 	/// Calls the C function func in protected mode.
 	/// ``func`` starts with only one element in its stack, a light userdata containing ud.
 	/// In case of errors, this returns the same error codes as [lua_pcall], plus the error object on the top of the stack.
 	/// Otherwise, it returns zero, and does not change the stack.
 	/// All values returned by func are discarded.
-	pub extern "C" fn lua_cpcall(l: LuaState, func: LuaCFunction, userdata: *mut c_void) -> c_int;
+	pub extern "C-unwind" fn lua_cpcall(l: LuaState, func: LuaCFunction, userdata: *mut c_void) -> c_int;
 
 	/// Calls a metamethod.
 	/// If the object at index obj has a metatable and this metatable has a field e, this function calls this field and passes the object as its only argument.
 	/// # Returns
 	/// In this case this function returns 1 and pushes onto the stack the value returned by the call.
 	/// If there is no metatable or no metamethod, this function returns 0 (without pushing any value on the stack).
-	pub extern "C" fn luaL_callmeta(l: LuaState, obj: c_int, name: LuaString) -> c_int;
-}
+	pub extern "C-unwind" fn luaL_callmeta(l: LuaState, obj: c_int, name: LuaString) -> c_int;
 
-dyn_symbols! {
 	/// Does the equivalent to t\[k\] = v, where t is the value at the given valid index and v is the value at the top of the stack.
 	/// This function pops the value from the stack.
 	/// As in Lua, this function may trigger the __newindex metamethod.
-	pub extern "C" fn lua_setfield(l: LuaState, idx: c_int, name: LuaString) -> ();
+	pub extern "C-unwind" fn lua_setfield(l: LuaState, idx: c_int, name: LuaString) -> ();
 
 	/// Pops a table from the stack and sets it as the new metatable for the value at the given acceptable index.
-	pub extern "C" fn lua_setmetatable(l: LuaState, idx: c_int) -> ();
+	pub extern "C-unwind" fn lua_setmetatable(l: LuaState, idx: c_int) -> ();
 
 	/// Accepts any acceptable index, or 0, and sets the stack top to this index.
 	/// If the new top is larger than the old one, then the new elements are filled with nil.
 	/// If index is 0, then all stack elements are removed.
-	pub extern "C" fn lua_settop(l: LuaState, ind: c_int) -> ();
+	pub extern "C-unwind" fn lua_settop(l: LuaState, ind: c_int) -> ();
 
 	/// Pops a table from the stack and sets it as the new environment for the value at the given index.
 	/// # Returns
 	/// If the value at the given index is neither a function nor a thread nor a userdata, returns 0.
 	/// Otherwise returns 1.
-	pub extern "C" fn lua_setfenv(l: LuaState, idx: c_int) -> c_int;
+	pub extern "C-unwind" fn lua_setfenv(l: LuaState, idx: c_int) -> c_int;
 
 	/// Does the equivalent to t\[k\] = v, where t is the value at the given valid index, v is the value at the top of the stack, and k is the value just below the top.
-	pub extern "C" fn lua_settable(l: LuaState, idx: c_int) -> ();
+	pub extern "C-unwind" fn lua_settable(l: LuaState, idx: c_int) -> ();
 
 	/// Same as lua_settable, but without calling any metamethods.
-	pub extern "C" fn lua_rawset(l: LuaState, idx: c_int) -> ();
+	pub extern "C-unwind" fn lua_rawset(l: LuaState, idx: c_int) -> ();
 
 	/// Does the equivalent of t\[n\] = v, where t is the value at the given valid index and v is the value at the top of the stack.
 	/// This function pops the value from the stack. The assignment is raw; that is, it does not invoke metamethods.
-	pub extern "C" fn lua_rawseti(l: LuaState, idx: c_int, n: c_int) -> ();
-}
+	pub extern "C-unwind" fn lua_rawseti(l: LuaState, idx: c_int, n: c_int) -> ();
 
-// Getters
-dyn_symbols! {
+	// Getters
 	/// Pushes onto the stack the value t\[k\], where t is the value at the given valid index and k is the value at the top of the stack.
 	/// This function pops the key from the stack (putting the resulting value in its place). As in Lua, this function may trigger a metamethod for the "index" event (see §2.8).
-	pub extern "C" fn lua_gettable(l: LuaState, idx: c_int) -> ();
+	pub extern "C-unwind" fn lua_gettable(l: LuaState, idx: c_int) -> ();
 
 	/// This is the same as lua_gettable, but without calling any metamethods
-	pub extern "C" fn lua_rawget(l: LuaState, idx: c_int) -> ();
+	pub extern "C-unwind" fn lua_rawget(l: LuaState, idx: c_int) -> ();
 
 	/// Pushes onto the stack the value t\[n\], where t is the value at the given valid index.
 	/// The access is raw; that is, it does not invoke metamethods.
-	pub extern "C" fn lua_rawgeti(l: LuaState, idx: c_int, n: c_int) -> ();
+	pub extern "C-unwind" fn lua_rawgeti(l: LuaState, idx: c_int, n: c_int) -> ();
 
 	/// Pushes onto the stack the environment table of the value at the given index.
-	pub extern "C" fn lua_getfenv(l: LuaState, idx: c_int) -> ();
+	pub extern "C-unwind" fn lua_getfenv(l: LuaState, idx: c_int) -> ();
 
 	/// Pushes onto the stack the metatable of the value at the given acceptable index.
 	/// If the index is not valid, or if the value does not have a metatable, the function returns 0 and pushes nothing on the stack.
-	pub extern "C" fn lua_getmetatable(l: LuaState, idx: c_int) -> c_int;
+	pub extern "C-unwind" fn lua_getmetatable(l: LuaState, idx: c_int) -> c_int;
 
 	/// Pushes onto the stack the value t\[k\], where t is the value at ``idx``.
 	/// As in Lua, this function may trigger a metamethod for the "index" event.
-	pub extern "C" fn lua_getfield(l: LuaState, idx: c_int, key: LuaString) -> ();
-}
+	pub extern "C-unwind" fn lua_getfield(l: LuaState, idx: c_int, key: LuaString) -> ();
 
-// Non-stack getters
-dyn_symbols! {
+	// Non-stack getters
 	/// Returns the type of the value in the given acceptable index, or [TNONE] for a non-valid index (that is, an index to an "empty" stack position).
 	/// The types returned by lua_type are coded by the following constants:
 	/// [TNIL], [TNUMBER], [TBOOLEAN], [TSTRING], [TTABLE], [TFUNCTION], [TUSERDATA], [TTHREAD], and [TLIGHTUSERDATA].
-	pub extern "C" fn lua_type(l: LuaState, idx: c_int) -> c_int;
+	pub extern "C-unwind" fn lua_type(l: LuaState, idx: c_int) -> c_int;
 
 	/// Returns the name of the type ``typeid`` which must be one the values returned by [lua_type].
 	/// Use [luaL_typename] if you want to get it directly from a value in the stack.
-	pub extern "C" fn lua_typename(l: LuaState, typeid: c_int) -> LuaString; // To be used with the return value of lua_type
+	pub extern "C-unwind" fn lua_typename(l: LuaState, typeid: c_int) -> LuaString; // To be used with the return value of lua_type
 
 	// Type conversion getters
 
@@ -252,12 +362,12 @@ dyn_symbols! {
 	/// The Lua value must be a string or a number; otherwise, the function returns a [std::ptr::null()].
 	/// If the value is a number, then lua_tolstring also changes the actual value in the stack to a string.
 	/// (This change confuses lua_next when lua_tolstring is applied to keys during a table traversal.)
-	pub extern "C" fn lua_tolstring(l: LuaState, ind: c_int, len: *mut SizeT) -> LuaString;
+	pub extern "C-unwind" fn lua_tolstring(l: LuaState, ind: c_int, len: *mut SizeT) -> LuaString;
 
 	/// Converts the Lua value at the given acceptable index to a C boolean value (0 or 1).
 	/// Like all tests in Lua, lua_toboolean returns 1 for any Lua value different from false and nil; otherwise returning 0.
 	/// This also returns 0 when called with a non-valid index. (If you want to accept only actual boolean values, use [lua_isboolean] to test the value's type.)
-	pub extern "C" fn lua_toboolean(l: LuaState, idx: c_int) -> c_int;
+	pub extern "C-unwind" fn lua_toboolean(l: LuaState, idx: c_int) -> c_int;
 
 	/// Converts a value at the given acceptable index to a C function.
 	/// That value must be a C function; otherwise, returns None.
@@ -271,113 +381,102 @@ dyn_symbols! {
 	/// 	0
 	/// }
 	/// ```
-	pub extern "C" fn lua_tocfunction(l: LuaState, idx: c_int) -> LuaCFunction;
+	pub extern "C-unwind" fn lua_tocfunction(l: LuaState, idx: c_int) -> LuaCFunction;
 
 	/// Converts the Lua value at the given acceptable index to the signed integral type [LuaInteger].
 	/// The Lua value must be a number or a string convertible to a number; otherwise, this returns 0.
 	/// If the number is not an integer, it is truncated in some non-specified way.
-	pub extern "C" fn lua_tointeger(l: LuaState, idx: c_int) -> LuaInteger;
+	pub extern "C-unwind" fn lua_tointeger(l: LuaState, idx: c_int) -> LuaInteger;
 
 	/// Converts the Lua value at the given acceptable index to a [LuaNumber].
 	/// The Lua value must be a number or a string convertible to a number; otherwise, this returns 0.
-	pub extern "C" fn lua_tonumber(l: LuaState, idx: c_int) -> LuaNumber;
+	pub extern "C-unwind" fn lua_tonumber(l: LuaState, idx: c_int) -> LuaNumber;
 
 	/// Converts the value at the given acceptable index to a generic C pointer *mut [c_void].
 	/// The value can be a userdata, a table, a thread, or a function; otherwise this returns None.
 	/// Different objects will give different pointers.
 	/// There is no way to convert the pointer back to its original value.
-	pub extern "C" fn lua_topointer(l: LuaState, idx: c_int) -> *mut c_void;
+	pub extern "C-unwind" fn lua_topointer(l: LuaState, idx: c_int) -> *mut c_void;
 
 	/// Converts the value at the given acceptable index to a Lua thread (represented as [LuaState]).
 	/// This value must be a thread; otherwise, the function returns None.
-	pub extern "C" fn lua_tothread(l: LuaState, idx: c_int) -> LuaState;
+	pub extern "C-unwind" fn lua_tothread(l: LuaState, idx: c_int) -> LuaState;
 
 	/// Returns the value at the given index assuming it is a userdata.
 	/// # Returns
 	/// If the value at the given acceptable index is a full userdata, returns its block address.
 	/// If the value is a light userdata, returns its pointer.
 	/// Otherwise, returns [std::ptr::null_mut()].
-	pub extern "C" fn lua_touserdata(l: LuaState, idx: c_int) -> *mut c_void;
-}
+	pub extern "C-unwind" fn lua_touserdata(l: LuaState, idx: c_int) -> *mut c_void;
 
-dyn_symbols! {
 	/// Pushes the zero-terminated string pointed to by s onto the stack. Lua makes (or reuses) an internal copy of the given string, so the memory at s can be freed or reused immediately after the function returns. The string cannot contain embedded zeros; it is assumed to end at the first zero.
-	pub extern "C" fn lua_pushstring(l: LuaState, s: LuaString) -> ();
+	pub extern "C-unwind" fn lua_pushstring(l: LuaState, s: LuaString) -> ();
 
 	/// Pushes a boolean onto the stack. Note this is still a [c_int] so use 0 for false and 1 for true.
-	pub extern "C" fn lua_pushboolean(l: LuaState, s: c_int) -> ();
+	pub extern "C-unwind" fn lua_pushboolean(l: LuaState, s: c_int) -> ();
 
 	/// Pushes a string of length ``sz`` onto the stack.
-	pub extern "C" fn lua_pushlstring(l: LuaState, s: LuaString, sz: SizeT) -> ();
+	pub extern "C-unwind" fn lua_pushlstring(l: LuaState, s: LuaString, sz: SizeT) -> ();
 
 	/// Pushes a `nil` value onto the stack.
-	pub extern "C" fn lua_pushnil(l: LuaState) -> ();
+	pub extern "C-unwind" fn lua_pushnil(l: LuaState) -> ();
 
 	/// Pushes the number ``num`` onto the stack.
-	pub extern "C" fn lua_pushnumber(l: LuaState, num: LuaNumber) -> ();
+	pub extern "C-unwind" fn lua_pushnumber(l: LuaState, num: LuaNumber) -> ();
 
 	/// Pushes a copy of the element at the given valid index onto the stack.
-	pub extern "C" fn lua_pushvalue(l: LuaState, idx: c_int) -> ();
+	pub extern "C-unwind" fn lua_pushvalue(l: LuaState, idx: c_int) -> ();
 	/// Pushes a c function on the stack with associated values.
 	/// # Parameters
 	/// * `l` - LuaState
 	/// * `f` - Lua function
 	/// * `n` - Number of upvalues to associate and pull from stack with the function
-	pub extern "C" fn lua_pushcclosure(l: LuaState, fnc: LuaCFunction, nargs: c_int) -> ();
+	pub extern "C-unwind" fn lua_pushcclosure(l: LuaState, fnc: LuaCFunction, nargs: c_int) -> ();
 
 	/// Pushes a light userdata onto the stack.
 	/// Userdata represent C values in Lua.
 	/// A light userdata represents a pointer.
 	/// It is a value (like a number): you do not create it, it has no individual metatable, and it is not collected (as it was never created).
 	/// A light userdata is equal to "any" light userdata with the same C address.
-	pub extern "C" fn lua_pushlightuserdata(l: LuaState, p: *mut c_void) -> ();
+	pub extern "C-unwind" fn lua_pushlightuserdata(l: LuaState, p: *mut c_void) -> ();
 
 	/// Pushes a given thread (representing ``l``) to the stack.
 	/// # Parameters
 	/// * `l` - The thread to push.
 	/// # Returns
 	/// 1 if the thread is the main thread of the state.
-	pub extern "C" fn lua_pushthread(l: LuaState) -> c_int;
-
-	/// Pushes a formatted [LuaString] to the stack
-	/// Note this is not a direct function but instead a Lazy pointer to a function.
-	/// This is because variadic functions are not yet supported in Rust, besides through external functions and pointers to them.
-	pub extern "C" fn lua_pushfstring(l: LuaState, fmt: LuaString, ...) -> LuaString;
+	pub extern "C-unwind" fn lua_pushthread(l: LuaState) -> c_int;
 
 	/// Pushes a number with value ``n`` onto the stack.
-	pub extern "C" fn lua_pushinteger(l: LuaState, n: LuaInteger) -> ();
-}
+	pub extern "C-unwind" fn lua_pushinteger(l: LuaState, n: LuaInteger) -> ();
 
-// Type checking getters
-dyn_symbols! {
+	// Type checking getters
 	/// Same as luaL_checknumber, but casts it to an integer.
-	pub extern "C" fn luaL_checkinteger(l: LuaState, narg: c_int) -> LuaInteger;
+	pub extern "C-unwind" fn luaL_checkinteger(l: LuaState, narg: c_int) -> LuaInteger;
 	/// Checks whether the value at stack index 'narg' is a number and returns this number.
 	/// If it is not a lua number, will throw an error to Lua.
-	pub extern "C" fn luaL_checknumber(l: LuaState, narg: c_int) -> LuaNumber;
+	pub extern "C-unwind" fn luaL_checknumber(l: LuaState, narg: c_int) -> LuaNumber;
 
 	/// Checks whether the function argument ``narg`` is a string and returns this string.
 	/// If len is not [std::ptr::null_mut()] fills *len with the string's length.
-	pub extern "C" fn luaL_checklstring(l: LuaState, narg: c_int, len: *mut SizeT) -> LuaString;
+	pub extern "C-unwind" fn luaL_checklstring(l: LuaState, narg: c_int, len: *mut SizeT) -> LuaString;
 
 	/// Checks whether the function has an argument of any type (including nil) at position narg.
-	pub extern "C" fn luaL_checkany(l: LuaState, narg: c_int) -> ();
+	pub extern "C-unwind" fn luaL_checkany(l: LuaState, narg: c_int) -> ();
 
 	/// Checks whether the function argument narg has type ``t``.
 	/// See [lua_type] for the encoding of types for ``t``.
-	pub extern "C" fn luaL_checktype(l: LuaState, narg: c_int, typeid: c_int) -> ();
+	pub extern "C-unwind" fn luaL_checktype(l: LuaState, narg: c_int, typeid: c_int) -> ();
 
 	/// Checks whether the function argument narg is a userdata of the type tname (see luaL_newmetatable).
-	pub extern "C" fn luaL_checkudata(l: LuaState, ud: c_int, tname: LuaString) -> *mut Userdata;
-}
+	pub extern "C-unwind" fn luaL_checkudata(l: LuaState, ud: c_int, tname: LuaString) -> *mut Userdata;
 
-// Creation
-dyn_symbols! {
+	// Creation
 	/// Creates a new Lua state.
 	/// This calls [lua_newstate] with an allocator based on the standard C realloc function and then sets a panic function (see lua_atpanic) that prints an error message to the standard error output in case of fatal errors.
 	/// # Returns
 	/// The newly created [LuaState], or [std::ptr::null_mut()] if the allocation failed (due to memory).
-	pub extern "C" fn luaL_newstate() -> LuaState;
+	pub extern "C-unwind" fn luaL_newstate() -> LuaState;
 
 	/// Creates a new, independent state.
 	/// Note you might be looking for [luaL_newstate], which has no parameters
@@ -385,24 +484,20 @@ dyn_symbols! {
 	/// The argument f is the allocator function;
 	/// Lua does all memory allocation for this state through this function.
 	/// The second argument, ud, is an opaque pointer that Lua simply passes to the allocator in every call.
-	pub extern "C" fn lua_newstate(f: LuaAlloc, ud: *mut c_void) -> LuaState;
+	pub extern "C-unwind" fn lua_newstate(f: LuaAlloc, ud: *mut c_void) -> LuaState;
 
 	/// Creates a new empty table and pushes it onto the stack.
 	/// The new table has space pre-allocated for ``narr`` array elements and ``nrec`` non-array elements.
 	/// This pre-allocation is useful when you know exactly how many elements the table will have.
 	/// Otherwise you can use the function [lua_newtable].
-	pub extern "C" fn lua_createtable(l: LuaState, narr: c_int, nrec: c_int) -> ();
-}
+	pub extern "C-unwind" fn lua_createtable(l: LuaState, narr: c_int, nrec: c_int) -> ();
 
-// Destruction
-dyn_symbols! {
+	// Destruction
 	/// Destroys the given lua state.
 	/// You *probably* don't want to do this, unless you just want to self destruct the server / your client.
-	pub extern "C" fn lua_close(l: LuaState) -> ();
-}
+	pub extern "C-unwind" fn lua_close(l: LuaState) -> ();
 
-// LuaJIT
-dyn_symbols! {
+	// LuaJIT
 	/// This is a C API extension to allow control of the VM from "C"
 	/// # Parameters
 	/// * `l` - Lua state
@@ -410,11 +505,9 @@ dyn_symbols! {
 	/// * `mode` - The mode to set, 'or'ed with a flag from [lua::jit]
 	/// # Returns
 	/// 1 for success, 0 for failure.
-	pub extern "C" fn luaJIT_setmode(l: LuaState, idx: c_int, jit_mode: c_int) -> c_int;
-}
+	pub extern "C-unwind" fn luaJIT_setmode(l: LuaState, idx: c_int, jit_mode: c_int) -> c_int;
 
-// Coroutines
-dyn_symbols! {
+	// Coroutines
 	/// Yields a coroutine.
 	/// This function should only be called as the return expression of a C function, as follows:
 	/// ```ignore
@@ -422,82 +515,69 @@ dyn_symbols! {
 	/// ```
 	/// When a function calls [lua_yield] in that way, the running coroutine suspends its execution, and the call to [lua_resume] that started this coroutine returns.
 	/// The parameter nresults is the number of values from the stack that are passed as results to [lua_resume].
-	pub extern "C" fn lua_yield(l: LuaState, nresults: c_int) -> c_int;
+	pub extern "C-unwind" fn lua_yield(l: LuaState, nresults: c_int) -> c_int;
 
 	/// Returns the status of the thread/coroutine l.
 	/// # Returns
 	/// 0 for a normal thread, error code if it's finished with an error, or [lua::YIELD] if it is suspended.
-	pub extern "C" fn lua_status(l: LuaState) -> c_int;
+	pub extern "C-unwind" fn lua_status(l: LuaState) -> c_int;
 
 	/// Starts and resumes a coroutine in a given thread.
 	/// Blame garry for the _real
-	pub extern "C" fn lua_resume_real(l: LuaState, narg: c_int) -> c_int;
-}
+	pub extern "C-unwind" fn lua_resume_real(l: LuaState, narg: c_int) -> c_int;
 
-// Comparison
-dyn_symbols! {
+	// Comparison
 	/// Returns 1 or 0 for if the two values at given indices are equal, calling ``__eq`` metamethods along the way unlike [lua_rawequal].
 	/// Also returns 0 if any of the indices are non valid.
-	pub extern "C" fn lua_equal(l: LuaState, ind1: c_int, ind2: c_int) -> c_int; // Returns 1 or 0 bool
+	pub extern "C-unwind" fn lua_equal(l: LuaState, ind1: c_int, ind2: c_int) -> c_int; // Returns 1 or 0 bool
 
 	/// Returns 1 or 0 for if the two values at given indices are equal, without calling metamethods, as [lua_equal] does.
 	/// Also returns 0 if any of the indices are non valid.
-	pub extern "C" fn lua_rawequal(l: LuaState, ind1: c_int, ind2: c_int) -> c_int;
-}
+	pub extern "C-unwind" fn lua_rawequal(l: LuaState, ind1: c_int, ind2: c_int) -> c_int;
 
-dyn_symbols! {
 	// Raising Errors
 	/// Generates an error with a message like the following:
 	/// ```text
 	/// location: bad argument narg to 'func' (tname expected, got rt)
 	/// ```
 	/// where location is produced by luaL_where, func is the name of the current function, and rt is the type name of the actual argument.
-	pub extern "C" fn luaL_typerror(l: LuaState, narg: c_int, typename: LuaString) -> !;
-
-	/// Raises an error.
-	/// The error message format is given by fmt plus any extra arguments, following the same rules of [lua_pushfstring].
-	/// It also adds at the beginning of the message the file name and the line number where the error occurred, if this information is available.
-	/// Note this is not a direct function but instead a Lazy pointer to a function.
-	/// This is because variadic functions are not yet supported in Rust, besides through external functions and pointers to them.
-	pub extern "C" fn luaL_error(l: LuaState, fmt: LuaString, ...) -> !;
+	pub extern "C-unwind" fn luaL_typerror(l: LuaState, narg: c_int, typename: LuaString) -> !;
 
 	/// Raises an error with the following message, where func is retrieved from the call stack:
 	/// ```text
 	/// bad argument #<narg> to <func> (<extramsg>)
 	/// ```
 	/// This function never returns
-	pub extern "C" fn luaL_argerror(l: LuaState, narg: c_int, extramsg: LuaString) -> !;
+	pub extern "C-unwind" fn luaL_argerror(l: LuaState, narg: c_int, extramsg: LuaString) -> !;
 
 	/// Generates a Lua error.
 	/// The error message (which can actually be a Lua value of any type) must be on the stack top.T
 	/// This function does a long jump, and therefore never returns. (see [luaL_error]).
-	pub extern "C" fn lua_error(l: LuaState) -> !;
-}
+	pub extern "C-unwind" fn lua_error(l: LuaState) -> !;
 
-dyn_symbols! {
 	// Libraries
 	/// Opens the standard 'table' library for a lua state
-	pub extern "C" fn luaopen_table(l: LuaState) -> c_int;
+	pub extern "C-unwind" fn luaopen_table(l: LuaState) -> c_int;
 	/// Opens the standard 'string' library for a lua state
-	pub extern "C" fn luaopen_string(l: LuaState) -> c_int;
+	pub extern "C-unwind" fn luaopen_string(l: LuaState) -> c_int;
 	/// Opens the standard 'package' library for a lua state
-	pub extern "C" fn luaopen_package(l: LuaState) -> c_int;
+	pub extern "C-unwind" fn luaopen_package(l: LuaState) -> c_int;
 	/// Opens the standard 'os' library for a lua state
-	pub extern "C" fn luaopen_os(l: LuaState) -> c_int;
+	pub extern "C-unwind" fn luaopen_os(l: LuaState) -> c_int;
 	/// Opens the standard 'math' library for a lua state
-	pub extern "C" fn luaopen_math(l: LuaState) -> c_int;
+	pub extern "C-unwind" fn luaopen_math(l: LuaState) -> c_int;
 	/// Opens the standard 'jit' library for a lua state
-	pub extern "C" fn luaopen_jit(l: LuaState) -> c_int;
+	pub extern "C-unwind" fn luaopen_jit(l: LuaState) -> c_int;
 	/// Opens the standard 'debug' library for a lua state
-	pub extern "C" fn luaopen_debug(l: LuaState) -> c_int;
+	pub extern "C-unwind" fn luaopen_debug(l: LuaState) -> c_int;
 	/// Opens the standard 'bit' library for a lua state
-	pub extern "C" fn luaopen_bit(l: LuaState) -> c_int;
+	pub extern "C-unwind" fn luaopen_bit(l: LuaState) -> c_int;
 	/// Opens the standard library functions (like assert) for a lua state
-	pub extern "C" fn luaopen_base(l: LuaState) -> c_int;
+	pub extern "C-unwind" fn luaopen_base(l: LuaState) -> c_int;
 	/// Opens all of the standard libraries for a lua state
-	pub extern "C" fn luaL_openlibs(l: LuaState) -> ();
+	pub extern "C-unwind" fn luaL_openlibs(l: LuaState) -> ();
 	/// Internally called by luaL_register, opens given list of LuaRegs with number of functions provided explicitly
-	pub extern "C" fn luaL_openlib(l: LuaState, libname: LuaString, reg: *const LuaReg, nup: c_int) -> ();
+	pub extern "C-unwind" fn luaL_openlib(l: LuaState, libname: LuaString, reg: *const LuaReg, nup: c_int) -> ();
 
 	/// Registers a ``reg`` of functions onto the LuaState's _G\[libname\].
 	/// For example you could set libname to [cstr]!("math") to add functions onto the ``math`` table or create it if it does not exist.
@@ -520,10 +600,8 @@ dyn_symbols! {
 	///		0
 	/// }
 	/// ```
-	pub extern "C" fn luaL_register(l: LuaState, libname: LuaString, lib: *const LuaReg) -> ();
-}
+	pub extern "C-unwind" fn luaL_register(l: LuaState, libname: LuaString, lib: *const LuaReg) -> ();
 
-dyn_symbols! {
 	/// Creates and returns a reference, in the table at index t, for the object at the top of the stack (and pops the object).
 	/// A reference is a unique integer key.
 	/// As long as you do not manually add integer keys into table t, [luaL_ref] ensures the uniqueness of the key it returns.
@@ -532,20 +610,18 @@ dyn_symbols! {
 	///
 	/// If the object at the top of the stack is nil, luaL_ref returns the constant [REFNIL].
 	/// The constant [NOREF] is guaranteed to be different from any reference returned by this.
-	pub extern "C" fn luaL_ref(l: LuaState, t: c_int) -> c_int;
+	pub extern "C-unwind" fn luaL_ref(l: LuaState, t: c_int) -> c_int;
 
 	/// Releases reference ref from the table at index t (see [luaL_ref]).
 	/// The entry is removed from the table, so that the referred object can be collected.
 	/// The reference ref is also freed to be used again.
 	/// If ref is [NOREF] or [REFNIL], this does nothing.
-	pub extern "C" fn luaL_unref(l: LuaState, t: c_int, r: c_int) -> ();
-}
+	pub extern "C-unwind" fn luaL_unref(l: LuaState, t: c_int, r: c_int) -> ();
 
-// Metatables
-dyn_symbols! {
+	// Metatables
 	/// If the registry already has the key tname, returns 0. Otherwise, creates a new table to be used as a metatable for userdata, adds it to the registry with key tname, and returns 1.
 	/// In both cases pushes onto the stack the final value associated with ``tname`` in the registry.
-	pub extern "C" fn luaL_newmetatable(l: LuaState, tname: LuaString) -> c_int;
+	pub extern "C-unwind" fn luaL_newmetatable(l: LuaState, tname: LuaString) -> c_int;
 
 	/// Creates a metatable with type and typeid
 	/// Same as [luaL_newmetatable], but also sets the MetaName and MetaID fields of the metatable
@@ -553,50 +629,43 @@ dyn_symbols! {
 	/// * `l` - LuaState
 	/// * `tname` - TypeName to be added to the metatable
 	/// * `tid` - TypeID to be applied to the metatable
-	pub extern "C" fn luaL_newmetatable_type(l: LuaState, tname: LuaString, tid: c_int) -> c_int;
+	pub extern "C-unwind" fn luaL_newmetatable_type(l: LuaState, tname: LuaString, tid: c_int) -> c_int;
 
 	/// Pushes onto the stack the field ``e`` from the metatable of the object at index ``obj``.
 	/// If the object does not have a metatable, or if the metatable does not have this field, returns 0 and pushes nothing.
-	pub extern "C" fn luaL_getmetafield(l: LuaState, obj: c_int, e: LuaString) -> c_int;
-}
+	pub extern "C-unwind" fn luaL_getmetafield(l: LuaState, obj: c_int, e: LuaString) -> c_int;
 
-// Optional
-dyn_symbols! {
+	// Optional
 	/// If the function argument ``narg`` is a number, returns this number cast to a [LuaInteger].
 	/// If this argument is absent or is nil, returns d. Otherwise, raises an error.
-	pub extern "C" fn luaL_optinteger(l: LuaState, narg: c_int, d: LuaInteger) -> c_int;
+	pub extern "C-unwind" fn luaL_optinteger(l: LuaState, narg: c_int, d: LuaInteger) -> c_int;
 
 	/// If the function argument narg is a string, returns this string.
 	/// If this argument is absent or is nil, returns ``default``. Otherwise, raises an error.
 	///
 	/// If ``len`` is not nullptr, fills the position *``len`` with the results's length.
-	pub extern "C" fn luaL_optlstring(l: LuaState, arg: c_int, default: LuaString, len: *mut SizeT)
+	pub extern "C-unwind" fn luaL_optlstring(l: LuaState, arg: c_int, default: LuaString, len: *mut SizeT)
 		-> LuaString;
 
 	/// If the function argument ``arg`` is a number, returns this number.
 	/// If this argument is absent or is nil, returns ``default``. Otherwise, raises an error.
-	pub extern "C" fn luaL_optnumber(l: LuaState, arg: c_int, default: LuaNumber) -> LuaNumber;
-}
+	pub extern "C-unwind" fn luaL_optnumber(l: LuaState, arg: c_int, default: LuaNumber) -> LuaNumber;
 
-dyn_symbols! {
 	// x / ref functions
 	/// Converts the Lua value at the given index to the signed integral type [LuaInteger].
 	/// The Lua value must be an integer, or a number or string convertible to an integer; otherwise, this returns 0.
 	/// If ``isnum`` is not [std::ptr::null_mut()], its referent is assigned a boolean value that indicates whether the operation succeeded.
-	pub extern "C" fn lua_tointegerx(l: LuaState, index: c_int, isnum: *mut c_int) -> LuaInteger;
-
+	pub extern "C-unwind" fn lua_tointegerx(l: LuaState, index: c_int, isnum: *mut c_int) -> LuaInteger;
 
 	/// Converts the Lua value at the given index to a [LuaNumber].
 	/// The Lua value must be a number or a string convertible to a number; otherwise, this returns 0.
 	/// If ``isnum`` is not [std::ptr::null_mut()], its referent is assigned a boolean value that indicates whether the operation succeeded.
-	pub extern "C" fn lua_tonumberx(l: LuaState, index: c_int, isnum: *mut c_int) -> LuaNumber;
-}
+	pub extern "C-unwind" fn lua_tonumberx(l: LuaState, index: c_int, isnum: *mut c_int) -> LuaNumber;
 
-dyn_symbols! {
 	/// Creates and pushes a traceback of the stack L1.
 	/// If msg is not [std::ptr::null_mut()] it is appended at the beginning of the traceback.
 	/// The level parameter tells at which level to start the traceback.
-	pub extern "C" fn luaL_traceback(
+	pub extern "C-unwind" fn luaL_traceback(
 		l: LuaState,
 		state1: LuaState,
 		msg: LuaString,
@@ -610,17 +679,17 @@ dyn_symbols! {
 	/// ```
 	/// Level 0 is the running function, level 1 is the function that called the running function, etc.
 	/// This function is used to build a prefix for error messages.
-	pub extern "C" fn luaL_where(l: LuaState, lvl: c_int) -> ();
+	pub extern "C-unwind" fn luaL_where(l: LuaState, lvl: c_int) -> ();
 
 	/// This function produces the return values for process-related functions in the standard library (os.execute and io.close).
 	/// Although, those don't exist in gmod..
-	pub extern "C" fn luaL_execresult(l: LuaState, stat: c_int) -> c_int;
+	pub extern "C-unwind" fn luaL_execresult(l: LuaState, stat: c_int) -> c_int;
 
 	/// This function produces the return values for file-related functions in the standard library (like File:seek)
-	pub extern "C" fn luaL_fileresult(l: LuaState, stat: c_int, fname: LuaString) -> c_int;
+	pub extern "C-unwind" fn luaL_fileresult(l: LuaState, stat: c_int, fname: LuaString) -> c_int;
 
 	/// Function used internally by lua
-	pub extern "C" fn luaL_findtable(
+	pub extern "C-unwind" fn luaL_findtable(
 		l: LuaState,
 		idx: c_int,
 		fname: LuaString,
@@ -650,21 +719,26 @@ dyn_symbols! {
 	///     0
 	/// }
 	/// ```
-	pub extern "C" fn lua_next(l: LuaState, idx: c_int) -> c_int;
+	pub extern "C-unwind" fn lua_next(l: LuaState, idx: c_int) -> c_int;
 
 	/// Replaces object at index (idx) with the object at the top of the stack (-1) and pops the stack.
-	pub extern "C" fn lua_replace(l: LuaState, idx: c_int) -> ();
+	pub extern "C-unwind" fn lua_replace(l: LuaState, idx: c_int) -> ();
 
 	/// Returns 1 if the value at acceptable index index1 is smaller than the value at acceptable index index2,
 	/// following the semantics of the Lua < operator (that is, may call metamethods).
 	///
 	/// Otherwise returns 0. Also returns 0 if any of the indices is non valid.
-	pub extern "C" fn lua_lessthan(l: LuaState, idx1: c_int, idx2: c_int) -> c_int;
+	pub extern "C-unwind" fn lua_lessthan(l: LuaState, idx1: c_int, idx2: c_int) -> c_int;
 
 	/// Ensures that there are at least extra free stack slots in the stack.
 	/// It returns C 'false' if it cannot grow the stack to that size.
 	/// This function never shrinks the stack; if the stack is already larger than the new size, it is left unchanged.
-	pub extern "C" fn lua_checkstack(l: LuaState, extra: c_int) -> c_int;
+	pub extern "C-unwind" fn lua_checkstack(l: LuaState, extra: c_int) -> c_int;
+
+	/// Concatenates the n values at the top of the stack, pops them, and leaves the result at the top.
+	/// If n is 1, the result is the single value on the stack; if n is 0, the result is the empty string.
+	/// Concatenation is done following the usual semantics of Lua (may call the ``__concat`` metamethod).
+	pub extern "C-unwind" fn lua_concat(l: LuaState, n: c_int) -> ();
 
 	/// Sets a new panic function and returns the old one.
 	/// If an error happens outside any protected environment, Lua calls a panic function and then calls exit(EXIT_FAILURE), thus exiting the host application.
@@ -672,16 +746,16 @@ dyn_symbols! {
 	/// The panic function can access the error message at the top of the stack.
 	/// # Returns
 	/// The old panic function.
-	pub extern "C" fn lua_atpanic(l: LuaState, panicf: LuaCFunction) -> LuaCFunction;
+	pub extern "C-unwind" fn lua_atpanic(l: LuaState, panicf: LuaCFunction) -> LuaCFunction;
 
 	/// Returns the index of the top element in the stack.
 	/// Because indices start at 1, this result is equal to the number of elements in the stack (and so 0 means an empty stack).
-	pub extern "C" fn lua_gettop(l: LuaState) -> c_int;
+	pub extern "C-unwind" fn lua_gettop(l: LuaState) -> c_int;
 
 	/// Removes the element at the given valid index, shifting down the elements above this index to fill the gap.
 	/// Cannot be called with a pseudo-index, because a pseudo-index is not an actual stack position.
 	/// (Example of pseudoindices are LUA_GLOBALSINDEX and globals::REGISTRYINDEX)
-	pub extern "C" fn lua_remove(l: LuaState, index: c_int) -> ();
+	pub extern "C-unwind" fn lua_remove(l: LuaState, index: c_int) -> ();
 
 	/// Controls lua's garbage collector
 	/// Performs different tasks depending on what you provide to the `what` parameter.
@@ -697,17 +771,16 @@ dyn_symbols! {
 	///     * `[GCSETPAUSE]` - Sets `lua_gc`'s pause threshold.
 	///     * `[GCSETSTEPMUL]` - Sets `lua_gc`'s step multiplier.
 	/// * `data` - c_int
-	pub extern "C" fn lua_gc(l: LuaState, what: c_int, data: c_int) -> c_int;
+	pub extern "C-unwind" fn lua_gc(l: LuaState, what: c_int, data: c_int) -> c_int;
 
 	/// Moves the top element into the given valid index, shifting up the elements above this index to open space.
 	/// Cannot be called with a pseudo-index, because a pseudo-index is not an actual stack position.
-	pub extern "C" fn lua_insert(l: LuaState, idx: c_int) -> ();
-
+	pub extern "C-unwind" fn lua_insert(l: LuaState, idx: c_int) -> ();
 
 	/// Creates a new thread, pushes it on the stack, and returns a pointer to a lua_State that represents this new thread.
 	/// The new state returned by this function shares with the original state all global objects (such as tables), but has an independent execution stack.
 	/// There is no explicit function to close or to destroy a thread. Threads are subject to garbage collection, like any Lua object.
-	pub extern "C" fn lua_newthread(l: LuaState) -> LuaState;
+	pub extern "C-unwind" fn lua_newthread(l: LuaState) -> LuaState;
 
 	/// This function allocates a new block of memory with the given size, pushes onto the stack a new full userdata with the block address, and returns this address.
 	///
@@ -718,7 +791,7 @@ dyn_symbols! {
 	///
 	/// When Lua collects a full userdata with a gc metamethod, Lua calls the metamethod and marks the userdata as finalized.
 	/// When this userdata is collected again then Lua frees its corresponding memory.
-	pub extern "C" fn lua_newuserdata(l: LuaState, size: SizeT) -> *mut Userdata;
+	pub extern "C-unwind" fn lua_newuserdata(l: LuaState, size: SizeT) -> *mut Userdata;
 
 	/// Returns information about a specific function or function invocation.
 	///
@@ -739,7 +812,7 @@ dyn_symbols! {
 	///     0
 	/// }
 	/// ```
-	pub extern "C" fn lua_getinfo(
+	pub extern "C-unwind" fn lua_getinfo(
 		l: LuaState,
 		what: LuaString,
 		ar: *mut LuaDebug,
@@ -750,17 +823,15 @@ dyn_symbols! {
 	/// For tables, this is the result of the length operator ('#');
 	/// For userdata, this is the size of the block of memory allocated for the userdata;
 	/// For other values, it is 0.
-	pub extern "C" fn lua_objlen(l: LuaState, idx: c_int) -> SizeT;
-}
+	pub extern "C-unwind" fn lua_objlen(l: LuaState, idx: c_int) -> SizeT;
 
-// Lua Debug Library
-dyn_symbols! {
-	/// Returns the current hook function.
-	pub extern "C" fn lua_gethook(l: LuaState) -> LuaHook;
+	// Lua Debug Library
+	/// Returns the current hook function, None if there is no hook.
+	pub extern "C-unwind" fn lua_gethook(l: LuaState) -> Option<LuaHook>;
 	/// Returns the current hook count.
-	pub extern "C" fn lua_gethookcount(l: LuaState) -> c_int;
+	pub extern "C-unwind" fn lua_gethookcount(l: LuaState) -> c_int;
 	/// Returns the current hook mask.
-	pub extern "C" fn lua_gethookmask(l: LuaState) -> c_int;
+	pub extern "C-unwind" fn lua_gethookmask(l: LuaState) -> c_int;
 
 	/// Sets the debugging hook function.
 	/// # Parameters
@@ -776,7 +847,7 @@ dyn_symbols! {
 	/// **The count hook**: is called after the interpreter executes every count instructions. (This event only happens while Lua is executing a Lua function.)
 	///
 	/// A hook is disabled by setting ``mask`` to zero.
-	pub extern "C" fn lua_sethook(l: LuaState, func: LuaHook, mask: c_int, count: c_int) -> c_int;
+	pub extern "C-unwind" fn lua_sethook(l: LuaState, func: LuaHook, mask: c_int, count: c_int) -> c_int;
 
 	/// Gets information about a local variable of a given activation record.
 	/// The parameter ar must be a valid activation record that was filled by a previous call to lua_getstack or given as argument to a hook (see lua_Hook).
@@ -784,11 +855,11 @@ dyn_symbols! {
 	/// lua_getlocal pushes the variable's value onto the stack and returns its name.
 	/// # Returns
 	/// Returns NULL (and pushes nothing) when the index is greater than the number of active local variables.
-	pub extern "C" fn lua_getlocal(l: LuaState, ar: *mut LuaDebug, n: c_int) -> LuaString;
+	pub extern "C-unwind" fn lua_getlocal(l: LuaState, ar: *mut LuaDebug, n: c_int) -> LuaString;
 
 	/// Get information about the interpreter runtime stack.
 	/// This function fills in the priv part of the LuaDebug structure with information about the function that is running at the given level.
-	pub extern "C" fn lua_getstack(l: LuaState, level: c_int, ar: *mut LuaDebug) -> c_int;
+	pub extern "C-unwind" fn lua_getstack(l: LuaState, level: c_int, ar: *mut LuaDebug) -> c_int;
 
 	/// Gets information about a closure's upvalue. This is basically debug.getlocal.
 	/// (For Lua functions, upvalues are the external local variables that the function uses, and that are consequently included in its closure.)
@@ -801,98 +872,86 @@ dyn_symbols! {
 	/// # Returns
 	/// The name of the upvalue at given index `idx`, or NULL (and pushes nothing) if the index is greater than the number of upvalues.
 	/// For C functions (functions not created in lua), this returns an empty string for the name of all upvalues
-	pub extern "C" fn lua_getupvalue(l: LuaState, fidx: c_int, idx: c_int) -> LuaString;
+	pub extern "C-unwind" fn lua_getupvalue(l: LuaState, fidx: c_int, idx: c_int) -> LuaString;
 
 	/// Sets the value of a closure's upvalue. Parameters funcindex and n are as in lua_getupvalue (see lua_getupvalue). It assigns the value at the top of the stack to the upvalue and returns its name. It also pops the value from the stack.
-	pub extern "C" fn lua_setupvalue(l: LuaState, fidx: c_int, idx: c_int) -> LuaString;
+	pub extern "C-unwind" fn lua_setupvalue(l: LuaState, fidx: c_int, idx: c_int) -> LuaString;
 
 	/// Sets the value of a local variable of a given activation record.
 	/// Parameters ar and n are as in [lua_getlocal].
 	/// lua_setlocal assigns the value at the top of the stack to the variable and returns its name.
 	/// It also pops the value from the stack.
-	pub extern "C" fn lua_setlocal(l: LuaState, ar: *mut LuaDebug, n: c_int) -> LuaString;
-}
+	pub extern "C-unwind" fn lua_setlocal(l: LuaState, ar: *mut LuaDebug, n: c_int) -> LuaString;
 
-dyn_symbols! {
 	/// Creates a copy of string 's' by replacing any occurrence of the string 'p' with the string 'r'
 	/// Pushes the resulting string on the stack and returns it
-	pub extern "C" fn luaL_gsub(s: LuaString, pattern: LuaString, replace: LuaString) -> LuaString;
+	pub extern "C-unwind" fn luaL_gsub(s: LuaString, pattern: LuaString, replace: LuaString) -> LuaString;
 
 	/// Exchange values between different threads of the same global state.
 	/// This function pops `n` values from the stack `from`, and pushes them onto the stack `to`.
-	pub extern "C" fn lua_xmove(from: LuaState, to: LuaState, n: c_int) -> ();
-}
+	pub extern "C-unwind" fn lua_xmove(from: LuaState, to: LuaState, n: c_int) -> ();
 
-dyn_symbols! {
 	/// Returns an unique identifier for the upvalue numbered n from the closure at index funcindex.
 	/// Parameters funcindex and n are as in the [lua_getupvalue] (but n cannot be greater than the number of upvalues).
 	/// These unique identifiers allow a program to check whether different closures share upvalues.
 	/// Lua closures that share an upvalue (that is, that access a same external local variable) will return identical ids for those upvalue indices.
-	pub extern "C" fn lua_upvalueid(l: LuaState, fidx: c_int, n: c_int) -> *mut c_void;
+	pub extern "C-unwind" fn lua_upvalueid(l: LuaState, fidx: c_int, n: c_int) -> *mut c_void;
 
 	/// Make the ``n1`` upvalue of the Lua closure at index ``fidx1`` refer to the ``n2`` upvalue of the Lua closure at index ``fidx2``.
-	pub extern "C" fn lua_upvaluejoin(l: LuaState, fidx1: c_int, n1: c_int, fidx2: c_int, n2: c_int) -> ();
-}
+	pub extern "C-unwind" fn lua_upvaluejoin(l: LuaState, fidx1: c_int, n1: c_int, fidx2: c_int, n2: c_int) -> ();
 
-// Buffer functions
-dyn_symbols! {
+	// Buffer functions
 	/// Initializes a buffer `b`.
 	/// This function does not allocate any space; the buffer must be declared as a variable.
-	pub extern "C" fn luaL_buffinit(l: LuaState, b: *mut LuaBuffer) -> ();
+	pub extern "C-unwind" fn luaL_buffinit(l: LuaState, b: *mut LuaBuffer) -> ();
 
 	/// Returns an address to a space of size [$crate::lua::BUFFERSIZE] where you can copy a string to be added to buffer [LuaBuffer] `b`.
 	/// After copying the string into this space you must call luaL_addsize with the size of the string to actually add it to the buffer.
-	pub extern "C" fn luaL_prepbuffer(b: *mut LuaBuffer) -> *mut c_char;
+	pub extern "C-unwind" fn luaL_prepbuffer(b: *mut LuaBuffer) -> *mut c_char;
 
 	/// Adds the zero-terminated string pointed to by `s` to the [LuaBuffer] `b` (see luaL_Buffer).
 	/// The string may not contain embedded zeros.
-	pub extern "C" fn luaL_addstring(b: *mut LuaBuffer, s: LuaString) -> ();
+	pub extern "C-unwind" fn luaL_addstring(b: *mut LuaBuffer, s: LuaString) -> ();
 
 	/// Adds the string pointed to by `s` with length `l` to the [LuaBuffer] `b`.
 	/// The string may contain embedded zeros.
-	pub extern "C" fn luaL_addlstring(b: *mut LuaBuffer, s: LuaString, l: SizeT) -> ();
+	pub extern "C-unwind" fn luaL_addlstring(b: *mut LuaBuffer, s: LuaString, l: SizeT) -> ();
 
 	/// Adds the value at the top of the stack to the buffer [LuaBuffer] `b`. Pops the value.
 	/// This is the only function on string buffers that can (and must) be called with an extra element on the stack, which is the value to be added to the buffer.
-	pub extern "C" fn luaL_addvalue(b: *mut LuaBuffer) -> ();
+	pub extern "C-unwind" fn luaL_addvalue(b: *mut LuaBuffer) -> ();
 
 	/// Finishes the use of buffer `b` leaving the final string on the top of the stack.
-	pub extern "C" fn luaL_pushresult(b: *mut LuaBuffer) -> ();
-}
+	pub extern "C-unwind" fn luaL_pushresult(b: *mut LuaBuffer) -> ();
 
-dyn_symbols! {
 	/// Returns the memory-allocation function of a given state.
 	/// If ud is not NULL, Lua stores in *ud the opaque pointer passed to lua_newstate.
-	pub extern "C" fn lua_getallocf(l: LuaState, ud: *mut *mut c_void) -> LuaAlloc;
+	pub extern "C-unwind" fn lua_getallocf(l: LuaState, ud: *mut *mut c_void) -> LuaAlloc;
 
 	/// Changes the allocator function of a given state to f with user data ud.
-	pub extern "C" fn lua_setallocf(l: LuaState, f: LuaAlloc, ud: *mut c_void) -> ();
-}
+	pub extern "C-unwind" fn lua_setallocf(l: LuaState, f: LuaAlloc, ud: *mut c_void) -> ();
 
-// Misc
-dyn_symbols! {
+	// Misc
 	/// Dumps a function as a binary chunk.
 	/// Receives a Lua function on the top of the stack and produces a binary chunk that, if loaded again, results in a function equivalent to the one dumped. As it produces parts of the chunk, lua_dump calls function writer (see lua_Writer) with the given data to write them.
-	pub extern "C" fn lua_dump(l: LuaState, writer: LuaWriter, data: *mut c_void) -> c_int;
+	pub extern "C-unwind" fn lua_dump(l: LuaState, writer: LuaWriter, data: *mut c_void) -> c_int;
 
 	/// Grows the stack size to top + sz elements, raising an error if the stack cannot grow to that size. msg is an additional text to go into the error message.
 	/// # Note
 	/// You may be looking for [lua_checkstack]
-	pub extern "C" fn luaL_checkstack(l: LuaState, size: c_int, msg: LuaString) -> ();
-}
+	pub extern "C-unwind" fn luaL_checkstack(l: LuaState, size: c_int, msg: LuaString) -> ();
 
-dyn_symbols! {
 	/// Returns 1 if the value at the given acceptable index is a number or a string convertible to a number, and 0 otherwise.
-	pub extern "C" fn lua_isnumber(l: LuaState, idx: c_int) -> c_int;
+	pub extern "C-unwind" fn lua_isnumber(l: LuaState, idx: c_int) -> c_int;
 
 	/// Returns 1 if the value at the given acceptable index is a string or a number (which is always convertible to a string), and 0 otherwise.
-	pub extern "C" fn lua_isstring(l: LuaState, idx: c_int) -> c_int;
+	pub extern "C-unwind" fn lua_isstring(l: LuaState, idx: c_int) -> c_int;
 
 	/// Returns 1 if the value at the given acceptable index is a C function, and 0 otherwise.
-	pub extern "C" fn lua_iscfunction(l: LuaState, idx: c_int) -> c_int;
+	pub extern "C-unwind" fn lua_iscfunction(l: LuaState, idx: c_int) -> c_int;
 
 	/// Returns 1 if the value at the given acceptable index is a userdata (either full or light), and 0 otherwise.
-	pub extern "C" fn lua_isuserdata(l: LuaState, idx: c_int) -> c_int;
+	pub extern "C-unwind" fn lua_isuserdata(l: LuaState, idx: c_int) -> c_int;
 }
 
 // Inline functions to mirror the C macros that come with the lua api
@@ -919,6 +978,11 @@ lua_macros! {
 		lua_pushcclosure(l, fnc, 0);
 	};
 
+	/// Index of the ``i``th upvalue of the running C closure, pushed with [lua_pushcclosure].
+	pub fn lua_upvalueindex(i: c_int) -> c_int {
+		lua::GLOBALSINDEX - i
+	};
+
 	/// Equivalent to ``lua_tolstring(l, idx, [std::ptr::null_mut()])``
 	/// This may return None if the value at ``idx`` is not a string or a number.
 	/// You should use [luaL_optstring] instead if you are unsure of the value, or [luaL_checkstring] for function arguments.
@@ -975,14 +1039,14 @@ lua_macros! {
 	/// Returns if the code was successfully executed
 	/// Error will be left on the stack if the code failed to execute
 	pub fn luaL_dostring(l: LuaState, str: LuaString) -> bool {
-		luaL_loadstring(l, str) == 0 || lua_pcall(l, 0, lua::MULTRET, 0) == 0
+		luaL_loadstring(l, str) == 0 && lua_pcall(l, 0, lua::MULTRET, 0) == 0
 	};
 
 	/// Loads and pcalls a file's lua code
 	/// Returns if the code was successfully executed
 	/// Error will be left on the stack if the code failed to execute
 	pub fn luaL_dofile(l: LuaState, filename: LuaString) -> bool {
-		luaL_loadfile(l, filename) == 0 || lua_pcall(l, 0, lua::MULTRET, 0) == 0
+		luaL_loadfile(l, filename) == 0 && lua_pcall(l, 0, lua::MULTRET, 0) == 0
 	};
 
 	/// Returns value at [crate::lua::REGISTRYINDEX] with name 'name'
@@ -1056,7 +1120,6 @@ lua_macros! {
 ///     1
 /// }
 /// ```
-
 pub fn lua_pushvector(l: LuaState, v: Vector) {
 	let ptr = lua_newuserdata(l, std::mem::size_of::<Userdata>());
 
@@ -1076,7 +1139,6 @@ pub fn lua_pushvector(l: LuaState, v: Vector) {
 }
 
 /// Pushes an angle onto the stack.
-
 pub fn lua_pushangle(l: LuaState, v: Angle) {
 	let ptr = lua_newuserdata(l, std::mem::size_of::<Userdata>());
 
@@ -1110,10 +1172,13 @@ pub fn luaL_opt<T, F: Fn(LuaState, c_int) -> T>(l: LuaState, arg: c_int, default
 /// Adapted from Lua 5.3, note this does not actually exist in gluajit
 #[allow(non_snake_case)]
 pub fn luaL_testudata(l: LuaState, arg: c_int, tname: LuaString) -> Option<*mut super::Userdata> {
-	if lua_isuserdata(l, arg) == 1 {
-		lua_getmetatable(l, arg); // Object metatable
+	if lua_isuserdata(l, arg) == 1 && lua_getmetatable(l, arg) != 0 {
+		// Object metatable
 		luaL_getmetatable(l, tname); // Desired global metatable
-		if lua_rawequal(l, -1, -2) == 1 {
+		let matches = lua_rawequal(l, -1, -2) == 1;
+		lua_pop(l, 2);
+
+		if matches {
 			return Some(lua_touserdata(l, arg) as *mut super::Userdata);
 		}
 	}
@@ -1122,16 +1187,14 @@ pub fn luaL_testudata(l: LuaState, arg: c_int, tname: LuaString) -> Option<*mut
 
 #[inline(always)]
 #[allow(non_snake_case)]
-
 /// Returns a [Vector] from the stack at index ``i``.
-pub fn lua_tovector<'a>(l: LuaState, i: c_int) -> Option<Vector> {
-	luaL_testudata(l, i, cstr!("Vector")).map(|x: *mut Userdata| unsafe { *(x as *mut Vector) })
+pub fn lua_tovector(l: LuaState, i: c_int) -> Option<Vector> {
+	luaL_testudata(l, i, cstr!("Vector")).map(|x: *mut Userdata| unsafe { *((*x).data as *mut Vector) })
 }
 
 #[inline(always)]
 #[allow(non_snake_case)]
-
 /// Returns an [Angle] from the stack at index ``i``.
 pub fn lua_toangle(l: LuaState, i: c_int) -> Option<Angle> {
-	luaL_testudata(l, i, cstr!("Angle")).map(|x: *mut Userdata| unsafe { *(x as *mut Angle) })
+	luaL_testudata(l, i, cstr!("Angle")).map(|x: *mut Userdata| unsafe { *((*x).data as *mut Angle) })
 }
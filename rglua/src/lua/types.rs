@@ -15,8 +15,9 @@ pub type LuaInteger = isize;
 pub type LuaState = *mut c_void; // Raw Lua state.
 
 /// Lua "C" Functions are C ABI functions that return the number returns that will be passed to the Lua stack.
-pub type LuaCFunction = extern "C" fn(LuaState) -> c_int;
-pub type LuaHook = extern "C" fn(LuaState, *mut LuaDebug) -> c_int;
+/// These are "C-unwind" since 64 bit LuaJIT raises errors by unwinding the stack, which would abort going through an ``extern "C"`` function.
+pub type LuaCFunction = extern "C-unwind" fn(LuaState) -> c_int;
+pub type LuaHook = extern "C-unwind" fn(LuaState, *mut LuaDebug) -> c_int;
 pub type LuaAlloc =
 	extern "C" fn(ud: *mut c_void, ptr: *mut c_void, osize: usize, nsize: usize) -> *mut c_void;
 
@@ -37,7 +38,7 @@ pub struct LuaReg {
 }
 
 pub type LuaJITProfileCallback =
-	extern "C" fn(data: *mut c_void, l: LuaState, samples: c_int, vmL: c_int) -> ();
+	extern "C" fn(data: *mut c_void, l: LuaState, samples: c_int, vm_l: c_int) -> ();
 
 #[repr(C)]
 pub struct LuaBuffer {
@@ -1,8 +1,14 @@
 pub use crate::lua::*;
 pub use crate::types::{LuaCFunction, LuaInteger, LuaNumber, LuaState, LuaString};
-pub use crate::userdata::{Angle, Vector};
+pub use crate::context::ModuleContext;
+pub use crate::convert::{FromLua, FromLuaError, FromLuaMulti, ToLua, ToLuaMulti};
+pub use crate::error::LuaError;
+pub use crate::reference::{LuaFunctionRef, LuaRef, LuaTableRef};
+pub use crate::state::{Lua, StackGuard};
+pub use crate::table::Table;
+pub use crate::userdata::{Angle, LuaUserData, Vector};
 
 pub use crate::util::dump_stack;
-pub use crate::{cstr, iface, printgm, reg, rstr, try_cstr, try_rstr};
+pub use crate::{cstr, iface, printgm, reg, rstr, table, try_cstr, try_rstr};
 
-pub use rglua_macros::{gmod_close, gmod_open, lua_function};
+pub use rglua_macros::{gmod_close, gmod_open, lua_function, lua_methods, LuaUserData};
@@ -0,0 +1,200 @@
+//! Running closures on the main thread from worker threads, see [MainThreadQueue].
+//!
+//! Lua can only be used from the thread running it, so work done on other threads has to be handed back
+//! to use its results. Queued closures run from gmod's ``Think`` hook, a few at a time so a burst of them can't stall a frame.
+//! # Examples
+//! ```rust
+//! use rglua::prelude::*;
+//! use rglua::queue::MainThreadQueue;
+//! use std::time::Duration;
+//!
+//! #[gmod_open]
+//! fn open(l: LuaState) -> Result<i32, LuaError> {
+//!     let queue = MainThreadQueue::install(l, Duration::from_millis(2))?;
+//!
+//!     std::thread::spawn(move || {
+//!         let body = String::from("fetched on a worker thread");
+//!
+//!         // Runs on the next tick
+//!         let _ = queue.queue(move |l| {
+//!             lua_pushlstring(l, body.as_ptr() as LuaString, body.len());
+//!             lua_setglobal(l, cstr!("FETCHED"));
+//!         });
+//!     });
+//!
+//!     Ok(0)
+//! }
+//!
+//! #[gmod_close]
+//! fn close(l: LuaState) -> i32 {
+//!     MainThreadQueue::uninstall(l);
+//!     0
+//! }
+//! ```
+use crate::__private::catch_panic_with_state;
+use crate::error::LuaError;
+use crate::lua::*;
+use crate::runtime::{add_think_hook, remove_think_hook, report};
+use crate::state::{protected, registry_key};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+
+type Job = Box<dyn FnOnce(LuaState) + Send>;
+
+/// Error from queueing a closure with [MainThreadQueue].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum QueueError {
+	/// The queue was uninstalled, so the closure will never run.
+	#[error("the main thread queue was uninstalled")]
+	Closed,
+
+	/// The closure panicked, with this message.
+	#[error("queued closure panicked: {0}")]
+	Panicked(String),
+
+	/// The closure raised a lua error.
+	#[error("queued closure errored: {0}")]
+	Lua(LuaError),
+
+	/// [MainThreadQueue::block_on_main] was called from the main thread, which would wait on itself forever.
+	#[error("can't block on the main thread from the main thread")]
+	OnMainThread
+}
+
+struct Drain {
+	jobs: Receiver<Job>,
+	sender: Sender<Job>,
+	main: ThreadId,
+	budget: Duration
+}
+
+thread_local! {
+	/// The queue of each state, by [registry_key].
+	static QUEUES: RefCell<HashMap<usize, Drain>> = RefCell::new(HashMap::new());
+}
+
+/// Name of the ``Think`` hook, unique to this module like the one of [crate::runtime].
+fn hook_name() -> String {
+	format!("rglua.queue.{:p}", drain_hook as *const ())
+}
+
+extern "C-unwind" fn drain_hook(l: LuaState) -> c_int {
+	MainThreadQueue::drain(l);
+	0
+}
+
+/// A handle to the queue of closures run on the main thread of a state, which can be sent to and cloned across threads.
+///
+/// Closures get the state the ``Think`` hook runs on, each in a protected call, so lua errors they raise are reported with ``ErrorNoHalt``.
+/// Panics are caught like in a ``#[lua_function]`` taking the state, so they're reported too, except on 64 bit unix, where they abort.
+#[derive(Debug, Clone)]
+pub struct MainThreadQueue {
+	sender: Sender<Job>,
+	main: ThreadId
+}
+
+impl MainThreadQueue {
+	/// Adds the ``Think`` hook draining the queue of the state of ``l`` with ``hook.Add``, returning a handle to it.
+	///
+	/// Each tick runs queued closures until ``budget`` is used up, and at least one.
+	/// If the queue is already installed, this only sets its budget.
+	pub fn install(l: LuaState, budget: Duration) -> Result<Self, LuaError> {
+		let key = registry_key(l);
+
+		let existing = QUEUES.with(|queues| {
+			queues.borrow_mut().get_mut(&key).map(|drain| {
+				drain.budget = budget;
+				Self {
+					sender: drain.sender.clone(),
+					main: drain.main
+				}
+			})
+		});
+
+		if let Some(queue) = existing {
+			return Ok(queue);
+		}
+
+		add_think_hook(l, &hook_name(), drain_hook)?;
+
+		let (sender, jobs) = mpsc::channel();
+		let main = thread::current().id();
+		let drain = Drain {
+			jobs,
+			sender: sender.clone(),
+			main,
+			budget
+		};
+
+		QUEUES.with(|queues| queues.borrow_mut().insert(key, drain));
+		Ok(Self { sender, main })
+	}
+
+	/// Removes the ``Think`` hook and drops the closures still queued, failing anything waiting on them in [Self::block_on_main].
+	/// Handles left on other threads get [QueueError::Closed] from then on.
+	pub fn uninstall(l: LuaState) {
+		let _ = remove_think_hook(l, &hook_name());
+
+		// Dropped after the borrow ends, as dropping closures runs arbitrary code.
+		let drain = QUEUES.with(|queues| queues.borrow_mut().remove(&registry_key(l)));
+		drop(drain);
+	}
+
+	/// Runs queued closures on ``l`` like a tick of the ``Think`` hook does, returning how many ran.
+	pub fn drain(l: LuaState) -> usize {
+		let key = registry_key(l);
+		let Some(budget) = QUEUES.with(|queues| queues.borrow().get(&key).map(|drain| drain.budget)) else {
+			return 0;
+		};
+
+		let start = Instant::now();
+		let mut ran = 0;
+
+		// Taken one at a time, so closures can use the queue themselves.
+		while let Some(job) = QUEUES.with(|queues| queues.borrow().get(&key).and_then(|drain| drain.jobs.try_recv().ok())) {
+			if let Err(e) = protected(l, job) {
+				report(l, &e.to_string());
+			}
+
+			ran += 1;
+			if start.elapsed() >= budget {
+				break;
+			}
+		}
+
+		ran
+	}
+
+	/// Queues ``f`` to run on the main thread on the next tick, after what was queued before it.
+	pub fn queue<F: FnOnce(LuaState) + Send + 'static>(&self, f: F) -> Result<(), QueueError> {
+		self.sender.send(Box::new(f)).map_err(|_| QueueError::Closed)
+	}
+
+	/// Queues ``f`` like [Self::queue], then blocks until it has run and returns what it returned.
+	/// A lua error it raises is returned as [QueueError::Lua], and a panic as [QueueError::Panicked] where it can be caught.
+	pub fn block_on_main<R, F>(&self, f: F) -> Result<R, QueueError>
+	where
+		R: Send + 'static,
+		F: FnOnce(LuaState) -> R + Send + 'static
+	{
+		if thread::current().id() == self.main {
+			return Err(QueueError::OnMainThread);
+		}
+
+		let (tx, rx) = mpsc::sync_channel(1);
+		self.queue(move |l| {
+			let result = match protected(l, move |l| catch_panic_with_state(move || f(l))) {
+				Ok(Ok(ret)) => Ok(ret),
+				Ok(Err(msg)) => Err(QueueError::Panicked(msg)),
+				Err(e) => Err(QueueError::Lua(e))
+			};
+			let _ = tx.send(result);
+		})?;
+
+		// Fails if the closure was dropped without running, by uninstalling.
+		rx.recv().map_err(|_| QueueError::Closed)?
+	}
+}
@@ -0,0 +1,226 @@
+//! Owned references to lua values, pinned in the registry with [luaL_ref] until dropped.
+//!
+//! Useful to keep callbacks or tables around across calls into the module.
+//! # Examples
+//! ```rust
+//! use rglua::prelude::*;
+//! use std::cell::RefCell;
+//!
+//! thread_local! {
+//!     static CALLBACK: RefCell<Option<LuaFunctionRef>> = RefCell::new(None);
+//! }
+//!
+//! #[lua_function]
+//! fn set_callback(f: LuaFunctionRef) {
+//!     CALLBACK.with(|c| *c.borrow_mut() = Some(f));
+//! }
+//!
+//! #[lua_function]
+//! fn run_callback(n: f64) -> Result<f64, LuaError> {
+//!     CALLBACK.with(|c| match &*c.borrow() {
+//!         Some(f) => f.call(n),
+//!         None => Ok(n)
+//!     })
+//! }
+//!
+//! #[gmod_close]
+//! fn close(_l: LuaState) -> i32 {
+//!     // References must be freed before the state is closed
+//!     CALLBACK.with(|c| c.borrow_mut().take());
+//!     0
+//! }
+//! ```
+use crate::convert::{FromLua, FromLuaError, FromLuaMulti, ToLua, ToLuaMulti};
+use crate::error::LuaError;
+use crate::lua::*;
+use crate::state::{main_thread, Lua};
+
+/// A value pinned in the registry, freed with [luaL_unref] when dropped.
+///
+/// The reference remembers the main thread of the state it was created with (see [main_thread]), which is used to push and free it,
+/// so it can outlive the coroutine it was created from. It must be dropped before the state is closed.
+#[derive(Debug)]
+pub struct LuaRef {
+	l: LuaState,
+	id: c_int
+}
+
+impl LuaRef {
+	/// References the value at ``idx``, without removing it from the stack.
+	pub fn new(l: LuaState, idx: c_int) -> Self {
+		lua_pushvalue(l, idx);
+		Self::pop(l)
+	}
+
+	/// References the value on top of the stack, popping it.
+	pub fn pop(l: LuaState) -> Self {
+		Self {
+			l: main_thread(l),
+			id: luaL_ref(l, REGISTRYINDEX)
+		}
+	}
+
+	/// Takes ownership of a reference made with [luaL_ref] in the registry of ``l``.
+	/// # Safety
+	/// ``id`` must be a reference in the registry that nothing else frees.
+	pub unsafe fn from_raw(l: LuaState, id: c_int) -> Self {
+		Self { l: main_thread(l), id }
+	}
+
+	/// Returns the reference without freeing it, to be freed with [luaL_unref] by the caller.
+	pub fn into_raw(self) -> c_int {
+		let id = self.id;
+		std::mem::forget(self);
+		id
+	}
+
+	/// The id of the reference in the registry. [REFNIL] if it references nil.
+	pub fn id(&self) -> c_int {
+		self.id
+	}
+
+	/// The main thread of the state the reference was created with.
+	pub fn state(&self) -> LuaState {
+		self.l
+	}
+
+	/// Pushes the referenced value.
+	pub fn push(&self) {
+		lua_rawgeti(self.l, REGISTRYINDEX, self.id)
+	}
+
+	/// Returns the type of the referenced value, like [TFUNCTION].
+	pub fn type_of(&self) -> c_int {
+		self.push();
+		let ty = lua_type(self.l, -1);
+		lua_pop(self.l, 1);
+		ty
+	}
+
+	/// Calls the referenced value with ``args``, catching errors. See [Lua::protected_call].
+	pub fn call<A: ToLuaMulti, R: FromLuaMulti>(&self, args: A) -> Result<R, LuaError> {
+		self.push();
+		unsafe { Lua::from_raw(self.l) }.protected_call(args)
+	}
+}
+
+impl Clone for LuaRef {
+	/// Makes a new reference to the same value.
+	fn clone(&self) -> Self {
+		self.push();
+		Self::pop(self.l)
+	}
+}
+
+impl Drop for LuaRef {
+	fn drop(&mut self) {
+		luaL_unref(self.l, REGISTRYINDEX, self.id)
+	}
+}
+
+impl ToLua for &LuaRef {
+	/// Pushes the referenced value onto ``l``, which must share the registry of the state it was created with (like a coroutine of it).
+	fn push_to_lua(self, l: LuaState) {
+		lua_rawgeti(l, REGISTRYINDEX, self.id)
+	}
+}
+
+impl ToLua for LuaRef {
+	fn push_to_lua(self, l: LuaState) {
+		(&self).push_to_lua(l)
+	}
+}
+
+impl FromLua for LuaRef {
+	/// References any value, including nil.
+	fn from_lua(l: LuaState, idx: c_int) -> Result<Self, FromLuaError> {
+		Ok(Self::new(l, idx))
+	}
+}
+
+macro_rules! typed_ref {
+	($(#[$meta:meta])* $name:ident, $ty:expr, $expected:literal) => {
+		$(#[$meta])*
+		#[derive(Debug, Clone)]
+		pub struct $name(LuaRef);
+
+		impl $name {
+			/// References the value at ``idx``, or returns an error if it's the wrong type.
+			pub fn new(l: LuaState, idx: c_int) -> Result<Self, FromLuaError> {
+				if lua_type(l, idx) != $ty {
+					return Err(FromLuaError::type_mismatch(l, idx, $expected));
+				}
+				Ok(Self(LuaRef::new(l, idx)))
+			}
+
+			/// Returns the untyped reference.
+			pub fn into_inner(self) -> LuaRef {
+				self.0
+			}
+		}
+
+		impl std::ops::Deref for $name {
+			type Target = LuaRef;
+
+			fn deref(&self) -> &LuaRef {
+				&self.0
+			}
+		}
+
+		impl ToLua for &$name {
+			fn push_to_lua(self, l: LuaState) {
+				(&self.0).push_to_lua(l)
+			}
+		}
+
+		impl ToLua for $name {
+			fn push_to_lua(self, l: LuaState) {
+				self.0.push_to_lua(l)
+			}
+		}
+
+		impl FromLua for $name {
+			fn from_lua(l: LuaState, idx: c_int) -> Result<Self, FromLuaError> {
+				Self::new(l, idx)
+			}
+		}
+	};
+}
+
+typed_ref!(
+	/// A [LuaRef] to a function. Call it with [LuaRef::call].
+	LuaFunctionRef,
+	TFUNCTION,
+	"function"
+);
+
+typed_ref!(
+	/// A [LuaRef] to a table.
+	LuaTableRef,
+	TTABLE,
+	"table"
+);
+
+impl LuaTableRef {
+	/// Returns ``t[k]``, which may call the ``__index`` metamethod.
+	pub fn get<K: ToLua, V: FromLua>(&self, k: K) -> Result<V, FromLuaError> {
+		let l = self.state();
+		self.push();
+		k.push_to_lua(l);
+		lua_gettable(l, -2);
+
+		let v = V::from_lua(l, -1);
+		lua_pop(l, 2);
+		v
+	}
+
+	/// Sets ``t[k] = v``, which may call the ``__newindex`` metamethod.
+	pub fn set<K: ToLua, V: ToLua>(&self, k: K, v: V) {
+		let l = self.state();
+		self.push();
+		k.push_to_lua(l);
+		v.push_to_lua(l);
+		lua_settable(l, -3);
+		lua_pop(l, 1);
+	}
+}
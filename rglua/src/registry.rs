@@ -0,0 +1,99 @@
+//! Functions collected by ``#[lua_function(name = "..", lib = "..")]``, to be registered all at once.
+//! [register_all] is called for you at the start of ``#[gmod_open]``.
+//! # Examples
+//! ```rust
+//! use rglua::prelude::*;
+//!
+//! // Becomes mylib.add in lua
+//! #[lua_function(lib = "mylib")]
+//! fn add(a: f64, b: f64) -> f64 {
+//!     a + b
+//! }
+//!
+//! // Becomes the global MyPrint
+//! #[lua_function(name = "MyPrint")]
+//! fn my_print(l: LuaState, msg: String) {
+//!     printgm!(l, "{}", msg);
+//! }
+//!
+//! #[gmod_open]
+//! fn open(l: LuaState) -> i32 {
+//!     // Everything above has been registered by now.
+//!     0
+//! }
+//! ```
+use crate::lua::*;
+use crate::runtime::report;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::CString;
+
+#[doc(hidden)]
+pub use linkme;
+
+/// A function registered with ``#[lua_function(name = "..", lib = "..")]``.
+#[derive(Debug, Clone, Copy)]
+pub struct LuaFunction {
+	/// Library table the function goes into, or None for a global.
+	pub lib: Option<&'static str>,
+	pub name: &'static str,
+	pub func: LuaCFunction
+}
+
+/// Every registered function in the final binary, including ones from dependencies that use rglua.
+#[linkme::distributed_slice]
+pub static LUA_FUNCTIONS: [LuaFunction] = [..];
+
+/// Returns every function registered with ``#[lua_function(name = "..", lib = "..")]``.
+pub fn functions() -> &'static [LuaFunction] {
+	&LUA_FUNCTIONS
+}
+
+/// Registers every function from [functions] into its library table (created if it doesn't exist, like [luaL_register]) or the globals.
+/// A name registered more than once in the same library is reported with ``ErrorNoHalt``, and only the first function is registered.
+/// Leaves the stack as it was.
+pub fn register_all(l: LuaState) {
+	let mut libs: BTreeMap<Option<&str>, Vec<&LuaFunction>> = BTreeMap::new();
+	let mut seen = BTreeSet::new();
+	for f in functions() {
+		if seen.insert((f.lib, f.name)) {
+			libs.entry(f.lib).or_default().push(f);
+		} else {
+			let name = f.lib.map_or_else(|| f.name.to_owned(), |lib| format!("{lib}.{}", f.name));
+			report(l, &format!("lua function {name} is registered more than once, only the first is registered"));
+		}
+	}
+
+	for (lib, funcs) in libs {
+		let names: Vec<CString> = funcs
+			.iter()
+			.map(|f| CString::new(f.name).expect("lua function name contains a null byte"))
+			.collect();
+
+		let mut regs: Vec<LuaReg> = funcs
+			.iter()
+			.zip(&names)
+			.map(|(f, name)| LuaReg {
+				name: name.as_ptr(),
+				func: Some(f.func)
+			})
+			.collect();
+
+		regs.push(LuaReg {
+			name: std::ptr::null(),
+			func: None
+		});
+
+		match lib {
+			Some(lib) => {
+				let lib = CString::new(lib).expect("lua library name contains a null byte");
+				luaL_register(l, lib.as_ptr(), regs.as_ptr());
+			}
+			None => {
+				lua_pushvalue(l, GLOBALSINDEX);
+				luaL_register(l, std::ptr::null(), regs.as_ptr());
+			}
+		}
+
+		lua_pop(l, 1);
+	}
+}
@@ -0,0 +1,242 @@
+//! A single threaded executor for Rust futures, polled from gmod's ``Think`` hook.
+//!
+//! Call [install] in ``#[gmod_open]`` and [uninstall] in ``#[gmod_close]``. Futures are then run with [spawn],
+//! or returned from ``async`` lua functions (see below), and polled on the main thread once per tick,
+//! so they can use the lua state between ``.await``s. Do the actual work elsewhere (like on a thread pool or an async runtime
+//! of its own) and await the result, as anything blocking here blocks the game.
+//!
+//! A ``#[lua_function]`` can be an ``async fn``. Its arguments are read when it's called, and what it returns is given back to lua
+//! once it completes:
+//! * If it was called with a function as the last argument, after its own, that is called with the values, like a callback.
+//! * Otherwise, if it was called from a coroutine, the coroutine yields and is resumed with the values.
+//!   That coroutine should only be resumed by the runtime while waiting.
+//!
+//! If it returns an ``Err``, they get ``nil`` and the error message instead.
+//! If it takes the state, it gets the main thread, since the coroutine it was called from may be gone by the time it runs.
+//! Errors from callbacks and resumed coroutines are reported with ``ErrorNoHalt``.
+//! # Examples
+//! ```rust
+//! use rglua::prelude::*;
+//! use rglua::runtime;
+//!
+//! #[lua_function(name = "fetch_name", lib = "mylib")]
+//! async fn fetch_name(id: f64) -> Result<String, std::io::Error> {
+//!     // Would come from a worker thread, through a channel like futures::channel::oneshot
+//!     Ok(format!("player {id}"))
+//! }
+//!
+//! #[gmod_open]
+//! fn open(l: LuaState) -> Result<i32, LuaError> {
+//!     // Lua can now do mylib.fetch_name(5, function(name, err) print(name) end)
+//!     runtime::install(l)?;
+//!     Ok(0)
+//! }
+//!
+//! #[gmod_close]
+//! fn close(l: LuaState) -> i32 {
+//!     runtime::uninstall(l);
+//!     0
+//! }
+//! ```
+use crate::__private::{catch_panic_with_state, Raise};
+use crate::convert::ToLuaMulti;
+use crate::error::LuaError;
+use crate::lua::*;
+use crate::reference::LuaFunctionRef;
+use crate::state::{call_library, main_thread, registry_key};
+use crate::thread::{Resumed, Thread};
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+/// Set when a task is woken, possibly from another thread, and checked on the next tick.
+struct Woken(AtomicBool);
+
+impl Wake for Woken {
+	fn wake(self: Arc<Self>) {
+		self.0.store(true, Ordering::Release);
+	}
+
+	fn wake_by_ref(self: &Arc<Self>) {
+		self.0.store(true, Ordering::Release);
+	}
+}
+
+struct Task {
+	woken: Arc<Woken>,
+	/// Errors that have nowhere else to go, which are reported when polled.
+	future: Pin<Box<dyn Future<Output = Result<(), String>>>>
+}
+
+thread_local! {
+	/// Tasks of each state, by [registry_key].
+	static TASKS: RefCell<HashMap<usize, VecDeque<Task>>> = RefCell::new(HashMap::new());
+}
+
+/// Name of the ``Think`` hook, unique to this module so several modules using rglua don't replace each other's.
+fn hook_name() -> String {
+	format!("rglua.runtime.{:p}", tick_hook as *const ())
+}
+
+/// Registers [tick] to run every ``Think`` with ``hook.Add``.
+pub fn install(l: LuaState) -> Result<(), LuaError> {
+	add_think_hook(l, &hook_name(), tick_hook)
+}
+
+/// Removes the ``Think`` hook and drops all of the tasks of the state, which are never completed.
+pub fn uninstall(l: LuaState) {
+	let _ = remove_think_hook(l, &hook_name());
+
+	// Dropped after the borrow ends, in case dropping them spawns tasks.
+	let tasks = TASKS.with(|tasks| tasks.borrow_mut().remove(&registry_key(l)));
+	drop(tasks);
+}
+
+/// Runs ``func`` every ``Think`` as the hook ``name``, with ``hook.Add``.
+pub(crate) fn add_think_hook(l: LuaState, name: &str, func: LuaCFunction) -> Result<(), LuaError> {
+	call_library(l, "hook", "Add", ("Think", name, func))
+}
+
+pub(crate) fn remove_think_hook(l: LuaState, name: &str) -> Result<(), LuaError> {
+	call_library(l, "hook", "Remove", ("Think", name))
+}
+
+extern "C-unwind" fn tick_hook(l: LuaState) -> c_int {
+	tick(l);
+	0
+}
+
+/// Runs ``future`` on the main thread of the state of ``l``, polling it on [tick] whenever it's woken.
+pub fn spawn<F: Future<Output = ()> + 'static>(l: LuaState, future: F) {
+	spawn_task(l, async move {
+		future.await;
+		Ok(())
+	});
+}
+
+fn spawn_task<F: Future<Output = Result<(), String>> + 'static>(l: LuaState, future: F) {
+	let task = Task {
+		woken: Arc::new(Woken(AtomicBool::new(true))),
+		future: Box::pin(future)
+	};
+
+	TASKS.with(|tasks| tasks.borrow_mut().entry(registry_key(l)).or_default().push_back(task));
+}
+
+/// Polls the tasks of the state of ``l`` that were woken since the last tick, returning how many tasks are left.
+/// They're polled on the main thread of the state (see [main_thread]), whichever thread ``l`` is.
+///
+/// This is what the ``Think`` hook added by [install] runs, and can be called by hand (like in tests).
+pub fn tick(l: LuaState) -> usize {
+	let l = main_thread(l);
+	let key = registry_key(l);
+	let count = |tasks: &RefCell<HashMap<usize, VecDeque<Task>>>| tasks.borrow().get(&key).map_or(0, VecDeque::len);
+
+	// Taken out one at a time while polled, so tasks can spawn more (which wait for the next tick),
+	// and a lua error unwinding out of one only loses that task.
+	for _ in 0..TASKS.with(count) {
+		let Some(mut task) = TASKS.with(|tasks| tasks.borrow_mut().get_mut(&key).and_then(VecDeque::pop_front)) else {
+			break;
+		};
+
+		if task.woken.0.swap(false, Ordering::Acquire) {
+			let waker = Waker::from(task.woken.clone());
+			let mut cx = Context::from_waker(&waker);
+
+			match catch_panic_with_state(|| task.future.as_mut().poll(&mut cx)) {
+				Ok(Poll::Ready(Ok(()))) => continue,
+				Ok(Poll::Ready(Err(msg))) | Err(msg) => {
+					report(l, &msg);
+					continue;
+				}
+				Ok(Poll::Pending) => ()
+			}
+		}
+
+		TASKS.with(|tasks| tasks.borrow_mut().entry(key).or_default().push_back(task));
+	}
+
+	TASKS.with(count)
+}
+
+/// Reports an error that has nowhere to go with ``ErrorNoHalt``, or to stderr outside of gmod.
+pub(crate) fn report(l: LuaState, msg: &str) {
+	lua_getglobal(l, cstr!("ErrorNoHalt"));
+	if lua_type(l, -1) != TFUNCTION {
+		lua_pop(l, 1);
+		eprintln!("{msg}");
+		return;
+	}
+
+	let line = format!("{msg}\n");
+	lua_pushlstring(l, line.as_ptr() as LuaString, line.len());
+	if lua_pcall(l, 1, 0, 0) != OK {
+		lua_pop(l, 1);
+		eprintln!("{msg}");
+	}
+}
+
+/// Where the result of an async lua function goes.
+enum Resolve {
+	Callback(LuaFunctionRef),
+	Coroutine(Thread)
+}
+
+/// Spawns the future of an async lua function with ``nargs`` arguments, and returns what the lua function should return.
+/// The callback is the last argument, if it was given more than ``nargs``, so one passed in place of an argument isn't mistaken for it.
+/// It or the coroutine is referenced from the main thread, as the thread ``l`` may be gone once the future completes.
+#[doc(hidden)]
+pub fn __resolve<T, F>(l: LuaState, nargs: c_int, future: F) -> Result<c_int, Raise>
+where
+	T: ToLuaMulti + 'static,
+	F: Future<Output = Result<T, String>> + 'static
+{
+	let top = lua_gettop(l);
+	let resolve = if top > nargs && lua_type(l, top) == TFUNCTION {
+		Resolve::Callback(LuaFunctionRef::new(l, top).expect("checked it's a function"))
+	} else if lua_pushthread(l) == 0 {
+		let thread = Thread::from_thread(l, -1).expect("just pushed a thread");
+		lua_pop(l, 1);
+		Resolve::Coroutine(thread)
+	} else {
+		lua_pop(l, 1);
+		let msg = String::from("async function needs a callback after its arguments, or to be called from a coroutine");
+		return Err(Raise::error(l, msg));
+	};
+
+	let yields = matches!(resolve, Resolve::Coroutine(_));
+	spawn_task(l, async move {
+		let result = future.await;
+
+		let error = match resolve {
+			Resolve::Callback(callback) => {
+				let result = match result {
+					Ok(values) => callback.call::<_, ()>(values),
+					Err(msg) => callback.call::<_, ()>((None::<bool>, msg))
+				};
+				result.err()
+			}
+			Resolve::Coroutine(thread) => {
+				let resumed = match result {
+					Ok(values) => thread.resume::<_, ()>(values),
+					Err(msg) => thread.resume::<_, ()>((None::<bool>, msg))
+				};
+
+				match resumed {
+					Resumed::Error(e) => Some(e),
+					_ => None
+				}
+			}
+		};
+
+		// Reported from the state running the tick, as this one may be a coroutine that's dead by now.
+		error.map_or(Ok(()), |e| Err(e.to_string()))
+	});
+
+	// The callback gets the values, so nothing is returned to the caller.
+	Ok(if yields { lua_yield(l, 0) } else { 0 })
+}
@@ -0,0 +1,309 @@
+//! Restricted environments for untrusted chunks, built with [Sandbox] from an allowlist of globals.
+//! # Examples
+//! ```rust
+//! use rglua::prelude::*;
+//! use rglua::sandbox::{Preset, Sandbox};
+//!
+//! #[lua_function]
+//! fn run_untrusted(l: LuaState, code: String) -> Result<(), LuaError> {
+//!     let env = Sandbox::preset(Preset::MathString).allow("print").read_only(true).build(l);
+//!
+//!     env.load(code.as_bytes(), "=untrusted")?;
+//!     unsafe { Lua::from_raw(l) }.pcall(0, 0)
+//! }
+//! ```
+use crate::__private::Raise;
+use crate::error::LuaError;
+use crate::lua::*;
+use crate::reference::LuaTableRef;
+use crate::state::{abs_index, Lua};
+use std::str::FromStr;
+
+/// Allowlists to start a [Sandbox] from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+	/// "pure", basic functions that can't reach outside the environment, like ``pairs``, ``tostring`` and ``pcall``.
+	Pure,
+	/// "math+string", [Preset::Pure] with the ``math`` and ``string`` libraries.
+	MathString,
+	/// "gmod-safe", [Preset::MathString] with ``table``, ``bit``, the time functions of ``os``
+	/// and gmod's own value types and helpers, like ``Vector`` and ``isstring``.
+	GmodSafe
+}
+
+const PURE: &[&str] = &[
+	"_VERSION", "assert", "error", "ipairs", "next", "pairs", "pcall", "rawequal", "select", "tonumber", "tostring",
+	"type", "unpack", "xpcall"
+];
+
+const MATH_STRING: &[&str] = &["math", "string"];
+
+const GMOD_SAFE: &[&str] = &[
+	"table", "bit", "utf8", "os.time", "os.clock", "os.date", "os.difftime", "Vector", "Angle", "Color", "Lerp",
+	"isnumber", "isstring", "istable", "isfunction", "isbool", "isvector", "isangle", "tobool", "CurTime", "RealTime",
+	"SysTime", "FrameTime"
+];
+
+impl Preset {
+	/// The name of the preset, like "math+string".
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Pure => "pure",
+			Self::MathString => "math+string",
+			Self::GmodSafe => "gmod-safe"
+		}
+	}
+
+	/// The globals the preset allows, including those of the presets it builds on.
+	pub fn globals(&self) -> Vec<&'static str> {
+		let lists: &[&[&str]] = match self {
+			Self::Pure => &[PURE],
+			Self::MathString => &[PURE, MATH_STRING],
+			Self::GmodSafe => &[PURE, MATH_STRING, GMOD_SAFE]
+		};
+		lists.concat()
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("unknown sandbox preset {0:?}")]
+pub struct UnknownPreset(pub String);
+
+impl FromStr for Preset {
+	type Err = UnknownPreset;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"pure" => Ok(Self::Pure),
+			"math+string" => Ok(Self::MathString),
+			"gmod-safe" => Ok(Self::GmodSafe),
+			_ => Err(UnknownPreset(s.to_owned()))
+		}
+	}
+}
+
+/// Builds an environment table holding only the allowed globals.
+///
+/// Names are globals like "print", or fields of a global table like "os.time", which puts an ``os`` table in the environment
+/// with just that field. Globals that don't exist are left out. Tables are deep copied, so scripts can't change the originals
+/// (their metatables aren't copied). The environment's ``_G`` is the environment itself.
+#[derive(Debug, Clone, Default)]
+pub struct Sandbox {
+	globals: Vec<String>,
+	read_only: bool
+}
+
+impl Sandbox {
+	/// A sandbox that allows nothing.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// A sandbox that allows the globals of ``preset``.
+	pub fn preset(preset: Preset) -> Self {
+		Self::new().allow_all(preset.globals())
+	}
+
+	/// Allows ``name``, like "print" or "os.time".
+	pub fn allow(mut self, name: &str) -> Self {
+		self.globals.push(name.to_owned());
+		self
+	}
+
+	pub fn allow_all<S: AsRef<str>>(self, names: impl IntoIterator<Item = S>) -> Self {
+		names.into_iter().fold(self, |sandbox, name| sandbox.allow(name.as_ref()))
+	}
+
+	/// Makes the environment and the library tables in it read-only, so scripts can't set globals.
+	///
+	/// They're replaced by empty tables that read through to the real ones with ``__index``, so ``pairs`` and ``#``
+	/// don't see their contents.
+	pub fn read_only(mut self, read_only: bool) -> Self {
+		self.read_only = read_only;
+		self
+	}
+
+	/// Creates the environment from the globals of ``l``.
+	pub fn build(&self, l: LuaState) -> Environment {
+		let top = lua_gettop(l);
+
+		lua_createtable(l, 0, 0);
+		let seen = lua_gettop(l);
+		lua_createtable(l, 0, self.globals.len() as c_int);
+		let env = lua_gettop(l);
+
+		for name in &self.globals {
+			let (table, field) = match name.split_once('.') {
+				Some((table, field)) => (table, Some(field)),
+				None => (name.as_str(), None)
+			};
+
+			push_str(l, table);
+			lua_rawget(l, GLOBALSINDEX);
+
+			let value = match field {
+				None => lua_gettop(l),
+				Some(field) if lua_istable(l, -1) => {
+					push_str(l, field);
+					lua_gettable(l, -2);
+					lua_gettop(l)
+				}
+				Some(_) => {
+					lua_settop(l, env);
+					continue;
+				}
+			};
+
+			if lua_isnil(l, value) {
+				lua_settop(l, env);
+				continue;
+			}
+
+			if lua_istable(l, value) {
+				deep_copy(l, value, seen);
+				lua_replace(l, value);
+			}
+
+			match field {
+				None => {
+					push_str(l, table);
+					lua_pushvalue(l, value);
+					lua_rawset(l, env);
+				}
+				Some(field) => {
+					// The table holding only the allowed fields
+					push_str(l, table);
+					lua_rawget(l, env);
+					if !lua_istable(l, -1) {
+						lua_pop(l, 1);
+						lua_createtable(l, 0, 1);
+						push_str(l, table);
+						lua_pushvalue(l, -2);
+						lua_rawset(l, env);
+					}
+
+					push_str(l, field);
+					lua_pushvalue(l, value);
+					lua_rawset(l, -3);
+				}
+			}
+
+			lua_settop(l, env);
+		}
+
+		if self.read_only {
+			// Tables in the environment first, while it can still be written to.
+			lua_pushnil(l);
+			while lua_next(l, env) != 0 {
+				if lua_istable(l, -1) {
+					push_read_only(l);
+					lua_pushvalue(l, -2);
+					lua_insert(l, -2);
+					lua_rawset(l, env);
+				} else {
+					lua_pop(l, 1);
+				}
+			}
+
+			lua_pushvalue(l, env);
+			push_read_only(l);
+			lua_pushvalue(l, -1);
+			lua_setfield(l, env, cstr!("_G"));
+			lua_replace(l, env);
+		} else {
+			lua_pushvalue(l, env);
+			lua_setfield(l, env, cstr!("_G"));
+		}
+
+		lua_pushvalue(l, env);
+		let env = LuaTableRef::new(l, -1).expect("just created a table");
+		lua_settop(l, top);
+
+		Environment(env)
+	}
+}
+
+/// An environment built with [Sandbox::build], which can be given to any number of chunks.
+#[derive(Debug, Clone)]
+pub struct Environment(LuaTableRef);
+
+impl Environment {
+	/// Loads ``code`` as text (not bytecode, which can break out of any sandbox), pushing it as a function
+	/// that runs in this environment.
+	pub fn load(&self, code: &[u8], name: &str) -> Result<(), LuaError> {
+		let lua = unsafe { Lua::from_raw(self.0.state()) };
+		lua.load_buffer(code, name, Some("t"))?;
+		self.apply(-1);
+		Ok(())
+	}
+
+	/// Sets this as the environment of the function at ``idx``. Returns false if it isn't a lua function.
+	pub fn apply(&self, idx: c_int) -> bool {
+		let l = self.0.state();
+		let idx = abs_index(l, idx);
+
+		self.0.push();
+		lua_setfenv(l, idx) != 0
+	}
+
+	/// The environment table. If it's read-only, this is the empty table scripts see.
+	pub fn table(&self) -> &LuaTableRef {
+		&self.0
+	}
+}
+
+fn push_str(l: LuaState, s: &str) {
+	lua_pushlstring(l, s.as_ptr() as LuaString, s.len());
+}
+
+/// Pushes a copy of the table at ``idx``, and of the tables in it. Copies are kept in the table at ``seen``,
+/// so tables referenced more than once (or by themselves) are only copied once.
+fn deep_copy(l: LuaState, idx: c_int, seen: c_int) {
+	let idx = abs_index(l, idx);
+
+	lua_pushvalue(l, idx);
+	lua_rawget(l, seen);
+	if !lua_isnil(l, -1) {
+		return;
+	}
+	lua_pop(l, 1);
+
+	lua_checkstack(l, 4);
+	lua_createtable(l, 0, 0);
+	let copy = lua_gettop(l);
+	lua_pushvalue(l, idx);
+	lua_pushvalue(l, copy);
+	lua_rawset(l, seen);
+
+	lua_pushnil(l);
+	while lua_next(l, idx) != 0 {
+		if lua_istable(l, -1) {
+			deep_copy(l, -1, seen);
+			lua_remove(l, -2);
+		}
+
+		lua_pushvalue(l, -2);
+		lua_insert(l, -2);
+		lua_rawset(l, copy);
+	}
+}
+
+/// Replaces the table on top of the stack with an empty table reading from it, which errors when written to.
+fn push_read_only(l: LuaState) {
+	lua_createtable(l, 0, 0);
+
+	lua_createtable(l, 0, 3);
+	lua_pushvalue(l, -3);
+	lua_setfield(l, -2, cstr!("__index"));
+	lua_pushcfunction(l, read_only_newindex);
+	lua_setfield(l, -2, cstr!("__newindex"));
+	lua_pushboolean(l, 0);
+	lua_setfield(l, -2, cstr!("__metatable"));
+	lua_setmetatable(l, -2);
+
+	lua_replace(l, -2);
+}
+
+extern "C-unwind" fn read_only_newindex(l: LuaState) -> c_int {
+	Raise::error(l, String::from("attempt to modify a read-only table")).raise(l)
+}
@@ -0,0 +1,524 @@
+//! A safe handle around [LuaState], and [StackGuard] to keep the stack balanced.
+//! The raw functions in [crate::lua] are still there for anything this doesn't cover.
+use crate::__private::catch_panic_with_state;
+use crate::convert::{FromLua, FromLuaError, FromLuaMulti, ToLua, ToLuaMulti};
+use crate::error::LuaError;
+use crate::lua::{self, *};
+use std::cell::Cell;
+use std::marker::PhantomData;
+
+thread_local! {
+	/// How many calls made through [Lua::raising] haven't returned yet.
+	/// One that never returns had a lua error unwind through it, which [StackGuard] checks for.
+	static RAISING: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Safe handle to a [LuaState], valid for the lifetime ``'a``.
+/// Values that can't be represented in Rust (tables, functions, ..) are worked with through stack indices, like the C api.
+/// # Examples
+/// ```rust
+/// use rglua::prelude::*;
+///
+/// #[lua_function]
+/// fn greet(l: LuaState) -> i32 {
+///     let lua = unsafe { Lua::from_raw(l) };
+///     let name = lua.check_string(1);
+///
+///     lua.push_string(&format!("Hello, {name}!"));
+///     1
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Lua<'a> {
+	l: LuaState,
+	_marker: PhantomData<&'a ()>
+}
+
+impl<'a> Lua<'a> {
+	/// Wraps a raw [LuaState].
+	/// # Safety
+	/// ``l`` must be a valid lua state (or thread) for all of ``'a``.
+	/// The one given to a lua function is valid for the duration of the call.
+	pub unsafe fn from_raw(l: LuaState) -> Self {
+		Self {
+			l,
+			_marker: PhantomData
+		}
+	}
+
+	/// Returns the raw state, to use with the functions in [crate::lua].
+	pub fn as_raw(&self) -> LuaState {
+		self.l
+	}
+
+	/// Records the current stack top, restoring it when the returned guard is dropped.
+	pub fn guard(&self) -> StackGuard<'a> {
+		StackGuard::new(*self)
+	}
+
+	/// Returns the index of the top element of the stack, which is also the number of elements in it.
+	pub fn top(&self) -> c_int {
+		lua_gettop(self.l)
+	}
+
+	/// Sets the top of the stack to ``idx``, filling new elements with nil or removing ones past it.
+	pub fn set_top(&self, idx: c_int) {
+		lua_settop(self.l, idx)
+	}
+
+	/// Pops ``n`` elements from the stack.
+	pub fn pop(&self, n: c_int) {
+		lua_pop(self.l, n)
+	}
+
+	/// Converts a relative (negative) index into an absolute one, so it stays valid as the stack changes.
+	/// Pseudo indices like [REGISTRYINDEX] are returned as is.
+	pub fn abs_index(&self, idx: c_int) -> c_int {
+		abs_index(self.l, idx)
+	}
+
+	/// Makes sure there is room for ``extra`` more elements on the stack, returning false if it couldn't grow.
+	pub fn check_stack(&self, extra: c_int) -> bool {
+		lua_checkstack(self.l, extra) != 0
+	}
+
+	/// Returns the type of the value at ``idx``, one of [TNIL], [TNUMBER], .. or [TNONE] if the index is invalid.
+	pub fn type_of(&self, idx: c_int) -> c_int {
+		lua_type(self.l, idx)
+	}
+
+	/// Returns the name of the type of the value at ``idx``, like "number".
+	pub fn type_name(&self, idx: c_int) -> &'static str {
+		type_name(self.l, idx)
+	}
+
+	/// Pushes any [ToLua] value.
+	pub fn push<T: ToLua>(&self, v: T) {
+		v.push_to_lua(self.l)
+	}
+
+	/// Reads the value at ``idx`` as any [FromLua] type.
+	pub fn get<T: FromLua>(&self, idx: c_int) -> Result<T, FromLuaError> {
+		T::from_lua(self.l, idx)
+	}
+
+	pub fn push_nil(&self) {
+		lua_pushnil(self.l)
+	}
+
+	pub fn push_bool(&self, b: bool) {
+		lua_pushboolean(self.l, b as c_int)
+	}
+
+	pub fn push_number(&self, n: LuaNumber) {
+		lua_pushnumber(self.l, n)
+	}
+
+	pub fn push_integer(&self, n: LuaInteger) {
+		lua_pushinteger(self.l, n)
+	}
+
+	/// Pushes a copy of ``s``, which may contain nulls.
+	pub fn push_string(&self, s: &str) {
+		self.push_bytes(s.as_bytes())
+	}
+
+	/// Pushes a copy of ``bytes`` as a lua string.
+	pub fn push_bytes(&self, bytes: &[u8]) {
+		lua_pushlstring(self.l, bytes.as_ptr() as LuaString, bytes.len())
+	}
+
+	pub fn push_cfunction(&self, f: LuaCFunction) {
+		lua_pushcfunction(self.l, f)
+	}
+
+	pub fn push_vector(&self, v: crate::userdata::Vector) {
+		lua_pushvector(self.l, v)
+	}
+
+	pub fn push_angle(&self, a: crate::userdata::Angle) {
+		lua_pushangle(self.l, a)
+	}
+
+	/// Pushes a copy of the value at ``idx``.
+	pub fn push_value(&self, idx: c_int) {
+		lua_pushvalue(self.l, idx)
+	}
+
+	/// Creates a table with room for ``narr`` array and ``nrec`` hash elements and pushes it.
+	pub fn new_table(&self, narr: c_int, nrec: c_int) {
+		lua_createtable(self.l, narr, nrec)
+	}
+
+	/// Returns the value at ``idx`` as a boolean, following lua's truthiness (only nil and false are false).
+	pub fn get_bool(&self, idx: c_int) -> bool {
+		lua_toboolean(self.l, idx) != 0
+	}
+
+	/// Returns the number at ``idx``, or None if it isn't a number or a string convertible to one.
+	pub fn get_number(&self, idx: c_int) -> Option<LuaNumber> {
+		let mut isnum = 0;
+		let n = lua_tonumberx(self.l, idx, &mut isnum);
+		(isnum != 0).then_some(n)
+	}
+
+	/// Returns the number at ``idx`` as an integer, or None if it isn't a number or a string convertible to one.
+	pub fn get_integer(&self, idx: c_int) -> Option<LuaInteger> {
+		let mut isnum = 0;
+		let n = lua_tointegerx(self.l, idx, &mut isnum);
+		(isnum != 0).then_some(n)
+	}
+
+	/// Returns a copy of the string at ``idx``, or None if it isn't a string or number.
+	/// Unlike [lua_tostring], this won't convert a number in place.
+	pub fn get_bytes(&self, idx: c_int) -> Option<Vec<u8>> {
+		to_bytes(self.l, idx)
+	}
+
+	/// Like [Lua::get_bytes], but also None if the string is not valid utf-8.
+	pub fn get_string(&self, idx: c_int) -> Option<String> {
+		self.get_bytes(idx).and_then(|b| String::from_utf8(b).ok())
+	}
+
+	/// Returns the number at argument ``arg``, raising a lua error if it isn't one.
+	pub fn check_number(&self, arg: c_int) -> LuaNumber {
+		self.raising(|| luaL_checknumber(self.l, arg))
+	}
+
+	/// Returns the integer at argument ``arg``, raising a lua error if it isn't a number.
+	pub fn check_integer(&self, arg: c_int) -> LuaInteger {
+		self.raising(|| luaL_checkinteger(self.l, arg))
+	}
+
+	/// Returns the string at argument ``arg``, raising a lua error if it isn't a string or number.
+	/// Invalid utf-8 is replaced with U+FFFD.
+	pub fn check_string(&self, arg: c_int) -> String {
+		let mut len = 0;
+		let ptr = self.raising(|| luaL_checklstring(self.l, arg, &mut len));
+		let bytes = unsafe { std::slice::from_raw_parts(ptr as *const u8, len) };
+		String::from_utf8_lossy(bytes).into_owned()
+	}
+
+	/// Pushes ``t[k]`` where t is the table at ``idx``. May call the ``__index`` metamethod.
+	pub fn get_field(&self, idx: c_int, k: &str) {
+		let idx = self.abs_index(idx);
+		self.push_string(k);
+		self.raising(|| lua_gettable(self.l, idx));
+	}
+
+	/// Pops a value and sets it as ``t[k]``, where t is the table at ``idx``. May call the ``__newindex`` metamethod.
+	pub fn set_field(&self, idx: c_int, k: &str) {
+		let idx = self.abs_index(idx);
+		self.push_string(k);
+		lua_insert(self.l, -2);
+		self.raising(|| lua_settable(self.l, idx));
+	}
+
+	/// Pushes the global ``name``.
+	pub fn get_global(&self, name: &str) {
+		self.get_field(GLOBALSINDEX, name)
+	}
+
+	/// Pops a value and sets it as the global ``name``.
+	pub fn set_global(&self, name: &str) {
+		self.set_field(GLOBALSINDEX, name)
+	}
+
+	/// Calls the function below ``nargs`` arguments on the stack, leaving ``nresults`` results (or all with [MULTRET]).
+	/// Errors propagate up to whatever called into Rust; see [Lua::pcall] to catch them.
+	pub fn call(&self, nargs: c_int, nresults: c_int) {
+		self.raising(|| lua_call(self.l, nargs, nresults));
+	}
+
+	/// Like [Lua::call], but catches errors, returning them with the traceback of where they were raised.
+	pub fn pcall(&self, nargs: c_int, nresults: c_int) -> Result<(), LuaError> {
+		let base = self.top() - nargs;
+		lua_pushcfunction(self.l, crate::error::traceback);
+		lua_insert(self.l, base);
+
+		let status = lua_pcall(self.l, nargs, nresults, base);
+		lua_remove(self.l, base);
+
+		if status == lua::OK {
+			Ok(())
+		} else {
+			Err(LuaError::pop(self.l, status))
+		}
+	}
+
+	/// Calls the function on top of the stack (popping it) with ``args``, catching errors and converting what it returned.
+	/// The stack is left how it was before the function was pushed.
+	pub fn protected_call<A: ToLuaMulti, R: FromLuaMulti>(&self, args: A) -> Result<R, LuaError> {
+		let guard = StackGuard::with_top(*self, self.top() - 1);
+
+		let nargs = args.push_multi(self.l);
+		self.pcall(nargs, R::COUNT)?;
+
+		Ok(R::from_lua_multi(self.l, guard.top() + 1)?)
+	}
+
+	/// Loads ``code`` as a chunk named ``name``, pushing it as a function.
+	/// ``mode`` is "t" to only allow text, "b" for only bytecode or "bt" (the default) for both.
+	pub fn load_buffer(&self, code: &[u8], name: &str, mode: Option<&str>) -> Result<(), LuaError> {
+		load_buffer(self.l, code, name, mode)
+	}
+
+	/// Loads ``code`` with [luaL_loadstring], pushing it as a function. The chunk is named after the code itself.
+	pub fn load_string(&self, code: &str) -> Result<(), LuaError> {
+		let code = to_cstring(code);
+
+		match luaL_loadstring(self.l, code.as_ptr()) {
+			lua::OK => Ok(()),
+			status => Err(LuaError::pop(self.l, status))
+		}
+	}
+
+	/// Raises a lua error with ``msg``, prefixed with the current position like [luaL_error].
+	pub fn error(&self, msg: &str) -> ! {
+		luaL_where(self.l, 1);
+		self.push_string(msg);
+		lua_concat(self.l, 2);
+		self.raising(|| lua_error(self.l))
+	}
+
+	/// Runs ``f``, which may raise a lua error, so a [StackGuard] it unwinds through knows not to touch the stack.
+	fn raising<R>(&self, f: impl FnOnce() -> R) -> R {
+		RAISING.with(|n| n.set(n.get() + 1));
+		let out = f();
+		RAISING.with(|n| n.set(n.get() - 1));
+		out
+	}
+}
+
+// Shared with crate::convert, which works on raw states.
+/// Strings passed to lua as C strings are cut at the first null byte.
+pub(crate) fn to_cstring(s: &str) -> std::ffi::CString {
+	let bytes = s.as_bytes();
+	let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+	std::ffi::CString::new(&bytes[..end]).expect("cut at the first null")
+}
+
+/// Identifies the state ``l`` belongs to, which is shared by all of its threads (coroutines), by its registry.
+pub(crate) fn registry_key(l: LuaState) -> usize {
+	lua_topointer(l, REGISTRYINDEX) as usize
+}
+
+/// Returns the main thread of the state ``l`` belongs to, which lives as long as the state (unlike a coroutine).
+///
+/// Lua 5.1 can't find it from a coroutine, so it's remembered in the registry the first time this is called on it,
+/// which ``#[gmod_open]`` does. Until then, ``l`` itself is returned.
+pub fn main_thread(l: LuaState) -> LuaState {
+	lua_getfield(l, REGISTRYINDEX, cstr!("rglua.mainthread"));
+	let main = lua_tothread(l, -1);
+	lua_pop(l, 1);
+	if !main.is_null() {
+		return main;
+	}
+
+	if lua_pushthread(l) == 1 {
+		lua_setfield(l, REGISTRYINDEX, cstr!("rglua.mainthread"));
+	} else {
+		lua_pop(l, 1);
+	}
+	l
+}
+
+/// Calls the function ``func`` of the global table ``lib``, like ``hook.Add``, with ``args``.
+/// Returns what it returned, converted to ``R``.
+pub(crate) fn call_library<A: ToLuaMulti, R: FromLuaMulti>(l: LuaState, lib: &str, func: &str, args: A) -> Result<R, LuaError> {
+	let lua = unsafe { Lua::from_raw(l) };
+	let _guard = lua.guard();
+
+	lua.get_global(lib);
+	if !lua_istable(l, -1) {
+		return Err(LuaError::Runtime {
+			message: format!("the {lib} library isn't available"),
+			traceback: vec![]
+		});
+	}
+
+	lua.get_field(-1, func);
+	if lua_type(l, -1) != TFUNCTION {
+		return Err(LuaError::Runtime {
+			message: format!("{lib}.{func} isn't a function"),
+			traceback: vec![]
+		});
+	}
+
+	lua.protected_call(args)
+}
+
+/// Runs ``f`` inside [lua_cpcall], so a lua error it raises is returned instead of unwinding further.
+/// ``f`` starts with an empty stack, and panics are caught like with [catch_panic_with_state], becoming runtime errors.
+pub(crate) fn protected<T, F: FnOnce(LuaState) -> T>(l: LuaState, f: F) -> Result<T, LuaError> {
+	struct Call<F, T> {
+		f: Option<F>,
+		out: Option<Result<T, String>>
+	}
+
+	extern "C-unwind" fn run<T, F: FnOnce(LuaState) -> T>(l: LuaState) -> c_int {
+		let call = unsafe { &mut *(lua_touserdata(l, 1) as *mut Call<F, T>) };
+		lua_pop(l, 1);
+
+		let f = call.f.take().expect("only called once");
+		call.out = Some(catch_panic_with_state(|| f(l)));
+		0
+	}
+
+	let mut call = Call { f: Some(f), out: None };
+	match lua_cpcall(l, run::<T, F>, &mut call as *mut Call<F, T> as *mut c_void) {
+		lua::OK => call.out.expect("ran to the end").map_err(|msg| LuaError::new(ERRRUN, msg)),
+		status => Err(LuaError::pop(l, status))
+	}
+}
+
+pub(crate) fn load_buffer(l: LuaState, code: &[u8], name: &str, mode: Option<&str>) -> Result<(), LuaError> {
+	let name = to_cstring(name);
+	let mode = mode.map(to_cstring);
+
+	let status = luaL_loadbufferx(
+		l,
+		code.as_ptr() as LuaString,
+		code.len(),
+		name.as_ptr(),
+		mode.as_ref().map_or(std::ptr::null(), |m| m.as_ptr())
+	);
+
+	match status {
+		lua::OK => Ok(()),
+		status => Err(LuaError::pop(l, status))
+	}
+}
+
+pub(crate) fn abs_index(l: LuaState, idx: c_int) -> c_int {
+	if idx > 0 || idx <= REGISTRYINDEX {
+		idx
+	} else {
+		lua_gettop(l) + idx + 1
+	}
+}
+
+pub(crate) fn type_name(l: LuaState, idx: c_int) -> &'static str {
+	let name = luaL_typename(l, idx);
+	unsafe { std::ffi::CStr::from_ptr(name) }
+		.to_str()
+		.unwrap_or("?")
+}
+
+pub(crate) fn to_bytes(l: LuaState, idx: c_int) -> Option<Vec<u8>> {
+	// Convert a copy of numbers so lua_tolstring doesn't change the original (which would confuse lua_next)
+	let is_number = lua_type(l, idx) == TNUMBER;
+	if is_number {
+		lua_pushvalue(l, idx);
+	}
+
+	let mut len = 0;
+	let ptr = lua_tolstring(l, if is_number { -1 } else { idx }, &mut len);
+	let out = (!ptr.is_null()).then(|| unsafe { std::slice::from_raw_parts(ptr as *const u8, len) }.to_vec());
+
+	if is_number {
+		lua_pop(l, 1);
+	}
+
+	out
+}
+
+/// Records the top of the stack when created and restores it when dropped, so values pushed in between can't leak.
+///
+/// In debug builds this asserts the stack didn't shrink below where it started, as that means something popped values it didn't own.
+///
+/// It's left alone when a lua error raised by a method of [Lua] unwinds through the guard, as the error is on top of it.
+/// Errors raised by the functions in [crate::lua] (like [luaL_checkinteger]) aren't seen, so use the methods while guarded.
+/// # Examples
+/// ```rust
+/// use rglua::prelude::*;
+///
+/// #[lua_function]
+/// fn name_of_player(l: LuaState) -> i32 {
+///     let lua = unsafe { Lua::from_raw(l) };
+///     let guard = lua.guard();
+///
+///     lua.get_global("LocalPlayer");
+///     lua.call(0, 1);
+///     lua.get_field(-1, "Nick");
+///     lua.push_value(-2);
+///     lua.call(1, 1);
+///
+///     // Pops everything but the name
+///     guard.keep(1)
+/// }
+/// ```
+pub struct StackGuard<'a> {
+	lua: Lua<'a>,
+	top: c_int,
+	/// [RAISING] when created.
+	raising: usize
+}
+
+impl<'a> StackGuard<'a> {
+	pub fn new(lua: Lua<'a>) -> Self {
+		Self::with_top(lua, lua.top())
+	}
+
+	fn with_top(lua: Lua<'a>, top: c_int) -> Self {
+		Self {
+			lua,
+			top,
+			raising: RAISING.with(Cell::get)
+		}
+	}
+
+	/// The stack top this guard restores to.
+	pub fn top(&self) -> c_int {
+		self.top
+	}
+
+	/// Restores the stack, but keeps the ``n`` values on top of it (moving them down where the stack started).
+	/// Returns ``n``, so it can be returned straight from a lua function.
+	pub fn keep(self, n: c_int) -> c_int {
+		let l = self.lua.as_raw();
+		let now = lua_gettop(l);
+
+		debug_assert!(
+			now - self.top >= n,
+			"StackGuard::keep({n}) with only {} values pushed",
+			now - self.top
+		);
+
+		if now > self.top + n {
+			for i in 1..=n {
+				lua_pushvalue(l, now - n + i);
+				lua_replace(l, self.top + i);
+			}
+		}
+
+		lua_settop(l, self.top + n);
+		std::mem::forget(self);
+		n
+	}
+}
+
+impl Drop for StackGuard<'_> {
+	fn drop(&mut self) {
+		// A call made while guarded never returned, so a lua error is unwinding through here, which panicking() doesn't see.
+		// Those calls are gone either way, so they stop counting.
+		let unwinding = RAISING.with(|n| n.replace(self.raising)) > self.raising;
+
+		// Don't touch the stack mid-unwind (which would cut off the error), or turn a panic into an abort with the assert below.
+		if unwinding || std::thread::panicking() {
+			return;
+		}
+
+		let now = self.lua.top();
+
+		debug_assert!(
+			now >= self.top,
+			"Stack shrank from {} to {now} while guarded, something popped values it didn't push",
+			self.top
+		);
+
+		if now != self.top {
+			self.lua.set_top(self.top);
+		}
+	}
+}
@@ -0,0 +1,301 @@
+//! [Table], a view of a table on the stack, and [TableBuilder] to create them (see the [table!](crate::table!) macro).
+use crate::convert::{FromLua, FromLuaError, ToLua};
+use crate::lua::*;
+use crate::state::abs_index;
+use std::marker::PhantomData;
+
+/// A table on the stack at a fixed index, valid as long as the table stays there.
+/// # Examples
+/// ```rust
+/// use rglua::prelude::*;
+///
+/// #[lua_function]
+/// fn sum_values(l: LuaState) -> Result<f64, FromLuaError> {
+///     let t = Table::new(l, 1)?;
+///
+///     let mut sum = 0.0;
+///     for pair in t.iter::<String, f64>() {
+///         let (_key, value) = pair?;
+///         sum += value;
+///     }
+///     Ok(sum)
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Table<'a> {
+	l: LuaState,
+	idx: c_int,
+	_marker: PhantomData<&'a ()>
+}
+
+impl<'a> Table<'a> {
+	/// Views the table at ``idx``, or returns an error if it isn't a table.
+	pub fn new(l: LuaState, idx: c_int) -> Result<Self, FromLuaError> {
+		if lua_type(l, idx) != TTABLE {
+			return Err(FromLuaError::type_mismatch(l, idx, "table"));
+		}
+
+		Ok(Self {
+			l,
+			idx: abs_index(l, idx),
+			_marker: PhantomData
+		})
+	}
+
+	/// Creates a table with room for ``narr`` array and ``nrec`` hash elements, pushes it and views it.
+	pub fn create(l: LuaState, narr: c_int, nrec: c_int) -> Self {
+		lua_createtable(l, narr, nrec);
+		Self {
+			l,
+			idx: lua_gettop(l),
+			_marker: PhantomData
+		}
+	}
+
+	/// The absolute stack index of the table.
+	pub fn index(&self) -> c_int {
+		self.idx
+	}
+
+	pub fn state(&self) -> LuaState {
+		self.l
+	}
+
+	/// Returns ``t[k]``, which may call the ``__index`` metamethod.
+	pub fn get<K: ToLua, V: FromLua>(&self, k: K) -> Result<V, FromLuaError> {
+		k.push_to_lua(self.l);
+		lua_gettable(self.l, self.idx);
+		self.pop_value()
+	}
+
+	/// Sets ``t[k] = v``, which may call the ``__newindex`` metamethod.
+	pub fn set<K: ToLua, V: ToLua>(&self, k: K, v: V) {
+		k.push_to_lua(self.l);
+		v.push_to_lua(self.l);
+		lua_settable(self.l, self.idx);
+	}
+
+	/// Returns ``t[k]`` without invoking metamethods.
+	pub fn raw_get<K: ToLua, V: FromLua>(&self, k: K) -> Result<V, FromLuaError> {
+		k.push_to_lua(self.l);
+		lua_rawget(self.l, self.idx);
+		self.pop_value()
+	}
+
+	/// Sets ``t[k] = v`` without invoking metamethods.
+	pub fn raw_set<K: ToLua, V: ToLua>(&self, k: K, v: V) {
+		k.push_to_lua(self.l);
+		v.push_to_lua(self.l);
+		lua_rawset(self.l, self.idx);
+	}
+
+	/// Returns ``t[i]`` without invoking metamethods.
+	pub fn raw_get_index<V: FromLua>(&self, i: c_int) -> Result<V, FromLuaError> {
+		lua_rawgeti(self.l, self.idx, i);
+		self.pop_value()
+	}
+
+	/// Sets ``t[i] = v`` without invoking metamethods.
+	pub fn raw_set_index<V: ToLua>(&self, i: c_int, v: V) {
+		v.push_to_lua(self.l);
+		lua_rawseti(self.l, self.idx, i);
+	}
+
+	/// Appends ``v`` to the end of the sequence part, like ``table.insert(t, v)``.
+	pub fn push<V: ToLua>(&self, v: V) {
+		self.raw_set_index(self.len() as c_int + 1, v)
+	}
+
+	/// The length of the table, like the ``#`` operator (without the ``__len`` metamethod).
+	pub fn len(&self) -> usize {
+		lua_objlen(self.l, self.idx)
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Iterates over all key/value pairs, in no particular order, with [lua_next].
+	/// Pairs that fail to convert are yielded as errors without stopping the iteration.
+	///
+	/// The key being iterated is kept on the stack and popped once the iterator is done or dropped.
+	pub fn iter<K: FromLua, V: FromLua>(&self) -> Pairs<'a, K, V> {
+		Pairs {
+			table: *self,
+			state: IterState::Start,
+			_marker: PhantomData
+		}
+	}
+
+	/// Iterates over ``t[1]`` to ``t[#t]``, without invoking metamethods.
+	pub fn sequence<V: FromLua>(&self) -> Sequence<'a, V> {
+		Sequence {
+			table: *self,
+			i: 1,
+			len: self.len() as c_int,
+			_marker: PhantomData
+		}
+	}
+
+	fn pop_value<V: FromLua>(&self) -> Result<V, FromLuaError> {
+		let v = V::from_lua(self.l, -1);
+		lua_pop(self.l, 1);
+		v
+	}
+}
+
+impl ToLua for Table<'_> {
+	/// Pushes the table again.
+	fn push_to_lua(self, l: LuaState) {
+		lua_pushvalue(l, self.idx)
+	}
+}
+
+impl FromLua for Table<'_> {
+	fn from_lua(l: LuaState, idx: c_int) -> Result<Self, FromLuaError> {
+		Self::new(l, idx)
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum IterState {
+	Start,
+	/// The last key is on top of the stack.
+	Iterating,
+	Done
+}
+
+/// Iterator over the pairs of a [Table], see [Table::iter].
+pub struct Pairs<'a, K, V> {
+	table: Table<'a>,
+	state: IterState,
+	_marker: PhantomData<(K, V)>
+}
+
+impl<K: FromLua, V: FromLua> Iterator for Pairs<'_, K, V> {
+	type Item = Result<(K, V), FromLuaError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let l = self.table.l;
+
+		match self.state {
+			IterState::Done => return None,
+			IterState::Start => lua_pushnil(l),
+			IterState::Iterating => ()
+		}
+
+		// Pops the key, pushing the next key and value if there are any left.
+		if lua_next(l, self.table.idx) == 0 {
+			self.state = IterState::Done;
+			return None;
+		}
+		self.state = IterState::Iterating;
+
+		let pair = K::from_lua(l, -2).and_then(|k| Ok((k, V::from_lua(l, -1)?)));
+		lua_pop(l, 1);
+		Some(pair)
+	}
+}
+
+impl<K, V> Drop for Pairs<'_, K, V> {
+	fn drop(&mut self) {
+		if self.state == IterState::Iterating {
+			lua_pop(self.table.l, 1);
+		}
+	}
+}
+
+/// Iterator over the sequence part of a [Table], see [Table::sequence].
+pub struct Sequence<'a, V> {
+	table: Table<'a>,
+	i: c_int,
+	len: c_int,
+	_marker: PhantomData<V>
+}
+
+impl<V: FromLua> Iterator for Sequence<'_, V> {
+	type Item = Result<V, FromLuaError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.i > self.len {
+			return None;
+		}
+
+		let v = self.table.raw_get_index(self.i);
+		self.i += 1;
+		Some(v)
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		let left = (self.len - self.i + 1).max(0) as usize;
+		(left, Some(left))
+	}
+}
+
+impl<V: FromLua> ExactSizeIterator for Sequence<'_, V> {}
+
+/// Fields of a table to create when pushed, used by the [table!](crate::table!) macro.
+pub struct TableBuilder<'a> {
+	narr: c_int,
+	nrec: c_int,
+	fields: Vec<Box<dyn FnOnce(LuaState, c_int) + 'a>>
+}
+
+impl<'a> TableBuilder<'a> {
+	pub fn new() -> Self {
+		Self {
+			narr: 0,
+			nrec: 0,
+			fields: Vec::new()
+		}
+	}
+
+	/// Sets ``t[k] = v``.
+	pub fn field<K: ToLua + 'a, V: ToLua + 'a>(mut self, k: K, v: V) -> Self {
+		self.nrec += 1;
+		self.fields.push(Box::new(move |l, t| {
+			k.push_to_lua(l);
+			v.push_to_lua(l);
+			lua_rawset(l, t);
+		}));
+		self
+	}
+
+	/// Appends ``v`` to the sequence part.
+	pub fn push<V: ToLua + 'a>(mut self, v: V) -> Self {
+		self.narr += 1;
+		let i = self.narr;
+		self.fields.push(Box::new(move |l, t| {
+			v.push_to_lua(l);
+			lua_rawseti(l, t, i);
+		}));
+		self
+	}
+
+	/// Pushes the table and returns a view of it.
+	pub fn build<'t>(self, l: LuaState) -> Table<'t> {
+		self.push_to_lua(l);
+		Table {
+			l,
+			idx: lua_gettop(l),
+			_marker: PhantomData
+		}
+	}
+}
+
+impl Default for TableBuilder<'_> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl ToLua for TableBuilder<'_> {
+	fn push_to_lua(self, l: LuaState) {
+		lua_createtable(l, self.narr, self.nrec);
+		let t = lua_gettop(l);
+
+		for field in self.fields {
+			field(l, t);
+		}
+	}
+}
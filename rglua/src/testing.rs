@@ -0,0 +1,140 @@
+//! Support for running rglua against a stock LuaJIT instead of gmod's lua_shared, so code using it can be tested outside of the game.
+//! Requires the ``testing`` feature.
+//!
+//! The library is found through the [LUA_SHARED_ENV] environment variable, or by trying [LUAJIT_NAMES] with the system's dynamic loader.
+//! Functions that only exist in gmod's lua_shared, like [luaL_newmetatable_type], are filled in by [LuaSharedFns::resolve].
+//! # Examples
+//! ```rust
+//! use rglua::prelude::*;
+//! use rglua::testing::TestState;
+//!
+//! // Skips the test if LuaJIT isn't installed.
+//! let Some(l) = TestState::new() else { return };
+//! lua_pushnumber(*l, 5.0);
+//! assert_eq!(lua_gettop(*l), 1);
+//! ```
+use crate::lua::{self, *};
+use once_cell::sync::OnceCell;
+
+/// Library names of LuaJIT to try if [LUA_SHARED_ENV] isn't set.
+pub const LUAJIT_NAMES: &[&str] = &[
+	"libluajit-5.1.so.2",
+	"libluajit-5.1.so",
+	"libluajit-5.1.2.dylib",
+	"libluajit-5.1.dylib",
+	"lua51.dll"
+];
+
+/// Environment variable that, when set, makes [TestState::new] panic instead of skipping when LuaJIT couldn't be loaded.
+/// Set this in CI so a missing library doesn't silently skip every test.
+pub const REQUIRE_ENV: &str = "RGLUA_REQUIRE_LUAJIT";
+
+static INIT: OnceCell<Result<(), LuaSharedError>> = OnceCell::new();
+
+/// Loads LuaJIT as lua_shared and creates the table of its functions.
+/// This is called for you by [TestState::new], and only does anything the first time it's called.
+/// If lua_shared was already loaded (by [init_lua_shared] for example), that library is used instead.
+pub fn init() -> Result<(), LuaSharedError> {
+	INIT.get_or_init(|| {
+		let lib = match lua_shared_loaded() {
+			Some(lib) => lib,
+			None => {
+				let mut loader = LuaSharedLoader::new()
+					.env(None)
+					.reuse_loaded(false)
+					.search_defaults(false);
+
+				if let Some(path) = std::env::var_os(LUA_SHARED_ENV) {
+					loader = loader.path(path);
+				}
+
+				for name in LUAJIT_NAMES {
+					loader = loader.path(name);
+				}
+
+				loader.init()?
+			}
+		};
+
+		init_lua_shared_fns(LuaSharedFns::resolve(lib)).map(|_| ())
+	})
+	.clone()
+}
+
+/// A fresh lua state created with [luaL_newstate], closed when dropped.
+/// It has the standard libraries opened, and gmod's ``Vector`` and ``Angle`` metatables registered so [lua_pushvector] and friends work.
+/// It is also remembered as the main thread (see [crate::state::main_thread]), and its ``string.dump`` for stripping bytecode.
+///
+/// Dereferences to the [LuaState] to pass to lua functions.
+pub struct TestState(LuaState);
+
+impl TestState {
+	/// Creates a new state, or returns None (printing why) if LuaJIT couldn't be loaded.
+	/// # Panics
+	/// If LuaJIT couldn't be loaded and [REQUIRE_ENV] is set.
+	pub fn new() -> Option<Self> {
+		if let Err(why) = init() {
+			if std::env::var_os(REQUIRE_ENV).is_some() {
+				panic!("{why}");
+			}
+
+			eprintln!("Skipping, LuaJIT isn't available: {why}");
+			return None;
+		}
+
+		let l = luaL_newstate();
+		assert!(!l.is_null(), "Couldn't allocate lua state");
+
+		luaL_openlibs(l);
+
+		luaL_newmetatable_type(l, cstr!("Vector"), LuaType::Vector as c_int);
+		luaL_newmetatable_type(l, cstr!("Angle"), LuaType::Angle as c_int);
+		lua_pop(l, 2);
+
+		// Like #[gmod_open], so references made from coroutines are anchored to it.
+		crate::state::main_thread(l);
+		crate::bytecode::remember_string_dump(l);
+
+		Some(Self(l))
+	}
+
+	/// Loads and runs ``code``, returning the error message if it failed to load or errored.
+	/// The stack is left how it was either way.
+	pub fn exec(&self, code: &str) -> Result<(), String> {
+		let l = self.0;
+		let top = lua_gettop(l);
+
+		let ok = luaL_loadbuffer(l, code.as_ptr() as LuaString, code.len(), cstr!("=exec")) == lua::OK
+			&& lua_pcall(l, 0, 0, 0) == lua::OK;
+
+		let result = if ok {
+			Ok(())
+		} else {
+			let mut len = 0;
+			let msg = lua_tolstring(l, -1, &mut len);
+			if msg.is_null() {
+				Err(String::from("(error object is not a string)"))
+			} else {
+				let bytes = unsafe { std::slice::from_raw_parts(msg as *const u8, len) };
+				Err(String::from_utf8_lossy(bytes).into_owned())
+			}
+		};
+
+		lua_settop(l, top);
+		result
+	}
+}
+
+impl std::ops::Deref for TestState {
+	type Target = LuaState;
+
+	fn deref(&self) -> &LuaState {
+		&self.0
+	}
+}
+
+impl Drop for TestState {
+	fn drop(&mut self) {
+		lua_close(self.0);
+	}
+}
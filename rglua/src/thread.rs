@@ -0,0 +1,225 @@
+//! Coroutines driven from Rust, see [Thread].
+//! # Examples
+//! ```rust
+//! use rglua::prelude::*;
+//! use rglua::thread::Thread;
+//!
+//! #[lua_function]
+//! fn sum_yields(l: LuaState) -> Result<f64, Box<dyn std::error::Error>> {
+//!     // Called with a function that yields numbers
+//!     let thread = Thread::new(l, 1)?;
+//!
+//!     let mut sum = 0.0;
+//!     for n in thread.iter::<f64>() {
+//!         sum += n?;
+//!     }
+//!     Ok(sum)
+//! }
+//! ```
+use crate::convert::{FromLua, FromLuaError, FromLuaMulti, ToLua, ToLuaMulti};
+use crate::error::LuaError;
+use crate::lua::*;
+use crate::reference::LuaRef;
+use std::marker::PhantomData;
+
+/// What a coroutine did when resumed, see [Thread::resume].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resumed<T> {
+	/// It yielded these values, and can be resumed again.
+	Yielded(T),
+	/// It returned these values, and is dead.
+	Finished(T),
+	/// It errored (and is dead), couldn't be resumed, or what it yielded or returned couldn't be converted.
+	Error(LuaError)
+}
+
+/// The status of a coroutine, like ``coroutine.status``.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadStatus {
+	/// Not started yet or yielded, so it can be resumed.
+	Suspended,
+	/// Running, or resumed another coroutine.
+	Active,
+	/// Returned or errored.
+	Dead
+}
+
+/// A coroutine, kept alive by a [LuaRef] to it.
+#[derive(Debug, Clone)]
+pub struct Thread {
+	thread: LuaState,
+	reference: LuaRef
+}
+
+impl Thread {
+	/// Creates a coroutine that runs the function at ``idx``, like ``coroutine.create``.
+	pub fn new(l: LuaState, idx: c_int) -> Result<Self, FromLuaError> {
+		if lua_type(l, idx) != TFUNCTION {
+			return Err(FromLuaError::type_mismatch(l, idx, "function"));
+		}
+
+		lua_pushvalue(l, idx);
+		let thread = lua_newthread(l);
+		lua_insert(l, -2);
+		lua_xmove(l, thread, 1);
+
+		Ok(Self {
+			thread,
+			reference: LuaRef::pop(l)
+		})
+	}
+
+	/// References the coroutine at ``idx``, or returns an error if it isn't one.
+	pub fn from_thread(l: LuaState, idx: c_int) -> Result<Self, FromLuaError> {
+		if lua_type(l, idx) != TTHREAD {
+			return Err(FromLuaError::type_mismatch(l, idx, "thread"));
+		}
+
+		Ok(Self {
+			thread: lua_tothread(l, idx),
+			reference: LuaRef::new(l, idx)
+		})
+	}
+
+	/// The state of the coroutine itself.
+	pub fn thread(&self) -> LuaState {
+		self.thread
+	}
+
+	/// The reference keeping the coroutine alive, anchored to the main thread like any [LuaRef].
+	pub fn reference(&self) -> &LuaRef {
+		&self.reference
+	}
+
+	pub fn status(&self) -> ThreadStatus {
+		let mut ar = LuaDebug::default();
+
+		match lua_status(self.thread) {
+			YIELD => ThreadStatus::Suspended,
+			OK if lua_getstack(self.thread, 0, &mut ar) != 0 => ThreadStatus::Active,
+			// Not started, so the function is all that's on the stack
+			OK if lua_gettop(self.thread) > 0 => ThreadStatus::Suspended,
+			_ => ThreadStatus::Dead
+		}
+	}
+
+	/// Resumes the coroutine with ``args``, which are what ``coroutine.yield`` returns, or the arguments to the function on the first resume.
+	/// Returns what it yielded or returned, converted to ``R``.
+	///
+	/// Errors come with a traceback of the coroutine.
+	pub fn resume<A: ToLuaMulti, R: FromLuaMulti>(&self, args: A) -> Resumed<R> {
+		let status = match self.resume_raw(args) {
+			Ok(status) => status,
+			Err(e) => return Resumed::Error(e)
+		};
+
+		let values = R::from_lua_multi(self.thread, 1);
+		lua_settop(self.thread, 0);
+
+		match (values, status) {
+			(Ok(values), YIELD) => Resumed::Yielded(values),
+			(Ok(values), _) => Resumed::Finished(values),
+			(Err(why), _) => Resumed::Error(why.into())
+		}
+	}
+
+	/// Resumes the coroutine, leaving what it yielded or returned as the only values on its stack.
+	/// Returns [YIELD] or [OK] if it finished.
+	fn resume_raw<A: ToLuaMulti>(&self, args: A) -> Result<c_int, LuaError> {
+		let co = self.thread;
+
+		let why = match self.status() {
+			ThreadStatus::Suspended => None,
+			ThreadStatus::Active => Some("cannot resume non-suspended coroutine"),
+			ThreadStatus::Dead => Some("cannot resume dead coroutine")
+		};
+
+		if let Some(why) = why {
+			return Err(LuaError::Runtime {
+				message: why.to_owned(),
+				traceback: vec![]
+			});
+		}
+
+		let nargs = args.push_multi(co);
+		match lua_resume(co, nargs) {
+			status @ (OK | YIELD) => Ok(status),
+			status => {
+				// The stack of the coroutine is left as it was when it errored, so the traceback can still be made.
+				// It goes on the main thread, as the coroutine this was created from may be gone.
+				let l = self.reference.state();
+				if lua_type(co, -1) == TSTRING {
+					luaL_traceback(l, co, lua_tostring(co, -1), 0);
+					lua_pop(co, 1);
+				} else {
+					lua_xmove(co, l, 1);
+				}
+
+				Err(LuaError::pop(l, status))
+			}
+		}
+	}
+
+	/// Iterates over what the coroutine yields, resuming it without arguments each time.
+	/// Stops once it returns (ignoring the values it returned) or after yielding its error.
+	pub fn iter<T: FromLuaMulti>(&self) -> Yields<'_, T> {
+		Yields {
+			thread: self,
+			done: false,
+			_marker: PhantomData
+		}
+	}
+}
+
+impl ToLua for &Thread {
+	fn push_to_lua(self, l: LuaState) {
+		(&self.reference).push_to_lua(l)
+	}
+}
+
+impl ToLua for Thread {
+	fn push_to_lua(self, l: LuaState) {
+		(&self).push_to_lua(l)
+	}
+}
+
+impl FromLua for Thread {
+	fn from_lua(l: LuaState, idx: c_int) -> Result<Self, FromLuaError> {
+		Self::from_thread(l, idx)
+	}
+}
+
+/// Iterator over what a coroutine yields, see [Thread::iter].
+pub struct Yields<'a, T> {
+	thread: &'a Thread,
+	done: bool,
+	_marker: PhantomData<T>
+}
+
+impl<T: FromLuaMulti> Iterator for Yields<'_, T> {
+	type Item = Result<T, LuaError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.done {
+			return None;
+		}
+
+		let co = self.thread.thread;
+		match self.thread.resume_raw(()) {
+			Ok(YIELD) => {
+				let values = T::from_lua_multi(co, 1).map_err(LuaError::from);
+				lua_settop(co, 0);
+				Some(values)
+			}
+			Ok(_) => {
+				lua_settop(co, 0);
+				self.done = true;
+				None
+			}
+			Err(e) => {
+				self.done = true;
+				Some(Err(e))
+			}
+		}
+	}
+}
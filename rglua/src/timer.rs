@@ -0,0 +1,66 @@
+//! Running Rust closures with gmod's ``timer`` library.
+//!
+//! Timers created here are recorded in the [ModuleContext] of the state, so they're removed when the module is closed.
+//! # Examples
+//! ```rust
+//! use rglua::prelude::*;
+//! use rglua::timer;
+//!
+//! #[gmod_open]
+//! fn open(l: LuaState) -> Result<i32, LuaError> {
+//!     timer::create(l, "mymodule.autosave", 60.0, 0, |l| printgm!(l, "saving"))?;
+//!     timer::simple(l, 5.0, |l| printgm!(l, "loaded 5 seconds ago"))?;
+//!     Ok(0)
+//! }
+//! ```
+use crate::closure::push_closure;
+use crate::context::{remove_timer, timer_key, untrack, ModuleContext};
+use crate::error::LuaError;
+use crate::lua::*;
+use crate::reference::LuaRef;
+use crate::state::call_library;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Creates the timer ``name`` with ``timer.Create``, replacing any timer with the same name.
+/// It runs ``f`` every ``delay`` seconds, ``repetitions`` times (or forever if 0).
+pub fn create<F: Fn(LuaState) + 'static>(l: LuaState, name: &str, delay: f64, repetitions: u32, f: F) -> Result<(), LuaError> {
+	push_closure(l, move |l, ()| f(l));
+	start(l, name, delay, repetitions)
+}
+
+/// Runs ``f`` once after ``delay`` seconds, like ``timer.Simple``.
+///
+/// It's made with ``timer.Create`` under a generated name instead, so it can still be removed if the module closes before it runs.
+pub fn simple<F: FnOnce(LuaState) + 'static>(l: LuaState, delay: f64, f: F) -> Result<(), LuaError> {
+	static NEXT: AtomicU64 = AtomicU64::new(0);
+
+	let name = format!("rglua.timer.{:p}.{}", simple::<F> as *const (), NEXT.fetch_add(1, Ordering::Relaxed));
+	let key = timer_key(&name);
+	let f = Cell::new(Some(f));
+
+	push_closure(l, move |l, ()| {
+		untrack(l, &key);
+
+		if let Some(f) = f.take() {
+			f(l);
+		}
+	});
+	start(l, &name, delay, 1)
+}
+
+/// Creates the timer with the closure on top of the stack, popping it.
+fn start(l: LuaState, name: &str, delay: f64, repetitions: u32) -> Result<(), LuaError> {
+	let func = LuaRef::pop(l);
+	call_library::<_, ()>(l, "timer", "Create", (name, delay, repetitions, &func))?;
+
+	ModuleContext::get(l).track(timer_key(name), remove_timer(name));
+	Ok(())
+}
+
+/// Removes the timer ``name`` with ``timer.Remove``.
+pub fn remove(l: LuaState, name: &str) -> Result<(), LuaError> {
+	untrack(l, &timer_key(name));
+
+	call_library(l, "timer", "Remove", name)
+}
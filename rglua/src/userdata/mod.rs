@@ -1,3 +1,6 @@
+mod typed;
+pub use typed::*;
+
 macro_rules! udata {
 	(
 		$(#[$outer:meta])*
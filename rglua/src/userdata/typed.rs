@@ -0,0 +1,182 @@
+//! Rust types exposed to lua as userdata, see [LuaUserData].
+use crate::__private::{catch_panic, Raise};
+use crate::convert::FromLuaError;
+use crate::lua::*;
+use crate::state::abs_index;
+use std::cell::{Ref, RefCell, RefMut};
+use std::ffi::CStr;
+
+/// Largest alignment lua guarantees for the memory of a userdata.
+pub const MAX_ALIGN: usize = 8;
+
+/// A method of a [LuaUserData] type, generated by ``#[lua_methods]``.
+#[derive(Debug, Clone, Copy)]
+pub struct LuaMethod {
+	/// Name in lua. Names starting with ``__`` are metamethods, like ``__add``.
+	pub name: &'static str,
+	pub func: LuaCFunction
+}
+
+/// A Rust type that can be pushed to lua as a userdata, with a metatable registered under [LuaUserData::NAME].
+///
+/// Implement it with ``#[derive(LuaUserData)]``, and give it methods with a ``#[lua_methods]`` impl block.
+/// The metatable is created the first time a value is pushed. It has an ``__index`` table with the methods,
+/// a ``__gc`` that drops the value, and a ``__tostring`` (unless one is defined) showing the name and address.
+///
+/// Values are stored in a [RefCell], and borrowed back from lua with [LuaUserData::check] / [LuaUserData::test],
+/// or by taking ``&T`` / ``&mut T`` arguments in a ``#[lua_function]``.
+/// Taking the same value mutably twice (like ``a:swap(a)`` with ``&mut self`` and ``&mut Self``) is an argument error.
+/// # Examples
+/// ```rust
+/// use rglua::prelude::*;
+///
+/// #[derive(LuaUserData)]
+/// #[lua(name = "Counter")]
+/// struct Counter {
+///     count: u32
+/// }
+///
+/// #[lua_methods]
+/// impl Counter {
+///     fn increment(&mut self, by: Option<u32>) -> u32 {
+///         self.count += by.unwrap_or(1);
+///         self.count
+///     }
+///
+///     #[lua(name = "__len")]
+///     fn len(&self) -> u32 {
+///         self.count
+///     }
+/// }
+///
+/// #[lua_function(name = "Counter")]
+/// fn new_counter() -> Counter {
+///     Counter { count: 0 }
+/// }
+/// ```
+pub trait LuaUserData: Sized + 'static {
+	/// Name of the metatable in the registry, also used in error messages.
+	const NAME: &'static CStr;
+
+	/// Methods and metamethods of the type.
+	fn methods() -> &'static [LuaMethod] {
+		&[]
+	}
+
+	/// Name of the type as a string, for error messages.
+	fn type_name() -> &'static str {
+		Self::NAME.to_str().unwrap_or("userdata")
+	}
+
+	/// Pushes the metatable of this type, creating it if it doesn't exist yet.
+	fn push_metatable(l: LuaState) {
+		if luaL_newmetatable(l, Self::NAME.as_ptr()) == 0 {
+			return;
+		}
+
+		let methods = Self::methods();
+
+		lua_createtable(l, 0, methods.len() as c_int);
+		for method in methods.iter().filter(|m| !m.name.starts_with("__")) {
+			lua_pushlstring(l, method.name.as_ptr() as LuaString, method.name.len());
+			lua_pushcfunction(l, method.func);
+			lua_rawset(l, -3);
+		}
+		lua_setfield(l, -2, cstr!("__index"));
+
+		lua_pushcfunction(l, tostring::<Self>);
+		lua_setfield(l, -2, cstr!("__tostring"));
+
+		// Metamethods go after the defaults so they can replace them, including __index.
+		for method in methods.iter().filter(|m| m.name.starts_with("__")) {
+			lua_pushlstring(l, method.name.as_ptr() as LuaString, method.name.len());
+			lua_pushcfunction(l, method.func);
+			lua_rawset(l, -3);
+		}
+
+		// Always ours, so the value is dropped exactly once.
+		lua_pushcfunction(l, gc::<Self>);
+		lua_setfield(l, -2, cstr!("__gc"));
+	}
+
+	/// Moves the value into a new userdata and pushes it.
+	fn push_userdata(self, l: LuaState) {
+		assert!(
+			std::mem::align_of::<RefCell<Self>>() <= MAX_ALIGN,
+			"{} needs more alignment than lua gives userdata",
+			Self::type_name()
+		);
+
+		let ptr = lua_newuserdata(l, std::mem::size_of::<RefCell<Self>>()) as *mut RefCell<Self>;
+		unsafe { ptr.write(RefCell::new(self)) };
+
+		Self::push_metatable(l);
+		lua_setmetatable(l, -2);
+	}
+
+	/// Returns the cell holding the value at ``idx`` if it is a userdata of this type.
+	/// # Safety
+	/// The userdata must stay alive for all of ``'a``, which lua doesn't track.
+	/// Keeping it on the stack does, like the arguments of a lua function for the duration of the call.
+	unsafe fn test<'a>(l: LuaState, idx: c_int) -> Option<&'a RefCell<Self>> {
+		luaL_testudata(l, idx, Self::NAME.as_ptr()).map(|ptr| unsafe { &*(ptr as *const RefCell<Self>) })
+	}
+
+	/// Returns the cell holding the value at argument ``arg``, raising a lua error if it isn't a userdata of this type.
+	/// # Safety
+	/// The same as [LuaUserData::test].
+	unsafe fn check<'a>(l: LuaState, arg: c_int) -> &'a RefCell<Self> {
+		let ptr = luaL_checkudata(l, arg, Self::NAME.as_ptr());
+		unsafe { &*(ptr as *const RefCell<Self>) }
+	}
+
+	/// Borrows the value at ``idx``, with the error to raise if it isn't a userdata of this type or is mutably borrowed.
+	/// Used for ``&T`` arguments of lua functions.
+	/// # Safety
+	/// The same as [LuaUserData::test].
+	unsafe fn from_arg<'a>(l: LuaState, idx: c_int) -> Result<Ref<'a, Self>, FromLuaError> {
+		let cell = unsafe { Self::test(l, idx) }.ok_or_else(|| FromLuaError::type_mismatch(l, idx, Self::type_name()))?;
+		cell.try_borrow().map_err(|_| already_borrowed::<Self>(l, idx))
+	}
+
+	/// Used for ``&mut T`` arguments of lua functions, see [LuaUserData::from_arg].
+	/// # Safety
+	/// The same as [LuaUserData::test].
+	unsafe fn from_arg_mut<'a>(l: LuaState, idx: c_int) -> Result<RefMut<'a, Self>, FromLuaError> {
+		let cell = unsafe { Self::test(l, idx) }.ok_or_else(|| FromLuaError::type_mismatch(l, idx, Self::type_name()))?;
+		cell.try_borrow_mut().map_err(|_| already_borrowed::<Self>(l, idx))
+	}
+}
+
+/// Fallback for types without a ``#[lua_methods]`` block, which generates an inherent const that takes priority over this.
+#[doc(hidden)]
+pub trait __NoMethods {
+	const __RGLUA_METHODS: &'static [LuaMethod] = &[];
+}
+
+impl<T> __NoMethods for T {}
+
+fn already_borrowed<T: LuaUserData>(l: LuaState, idx: c_int) -> FromLuaError {
+	FromLuaError::new(abs_index(l, idx), T::type_name(), format!("{} already borrowed", T::type_name()))
+}
+
+extern "C-unwind" fn tostring<T: LuaUserData>(l: LuaState) -> c_int {
+	let s = format!("{}: {:p}", T::type_name(), lua_touserdata(l, 1));
+	lua_pushlstring(l, s.as_ptr() as LuaString, s.len());
+	1
+}
+
+extern "C-unwind" fn gc<T: LuaUserData>(l: LuaState) -> c_int {
+	let Some(ptr) = luaL_testudata(l, 1, T::NAME.as_ptr()) else {
+		return 0;
+	};
+
+	// Remove the metatable first, so the value can't be used or dropped again if something still references it.
+	lua_pushnil(l);
+	lua_setmetatable(l, 1);
+
+	match catch_panic(|| unsafe { std::ptr::drop_in_place(ptr as *mut RefCell<T>) }) {
+		Ok(()) => 0,
+		Err(msg) => Raise::error(l, msg).raise(l)
+	}
+}
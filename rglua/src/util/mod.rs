@@ -115,8 +115,8 @@ macro_rules! printgm {
 /// Basic usage
 /// ```rust
 /// use rglua::prelude::*;
-/// extern "C" fn max(l: LuaState) -> i32 { 0 }
-/// extern "C" fn min(l: LuaState) -> i32 { 0 }
+/// extern "C-unwind" fn max(l: LuaState) -> i32 { 0 }
+/// extern "C-unwind" fn min(l: LuaState) -> i32 { 0 }
 /// let my_library = reg! [
 ///     "max" => max,
 ///     "min" => min
@@ -132,6 +132,30 @@ macro_rules! reg {
 	};
 }
 
+/// Creates a [TableBuilder](crate::table::TableBuilder), which creates the table when pushed.
+/// Takes either ``key => value`` pairs, or values for the sequence part.
+/// # Examples
+/// ```rust
+/// use rglua::prelude::*;
+/// #[lua_function]
+/// fn info() -> impl ToLua {
+///     table! {
+///         "name" => "rglua",
+///         "position" => Vector::new(1.0, 2.0, 3.0),
+///         "tags" => table!["fast", "safe"]
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! table {
+	( $( $key:expr => $value:expr ),* $(,)? ) => {
+		$crate::table::TableBuilder::new() $( .field($key, $value) )*
+	};
+	( $( $value:expr ),* $(,)? ) => {
+		$crate::table::TableBuilder::new() $( .push($value) )*
+	};
+}
+
 // get_from_interface using literal c strings.
 #[cfg(feature = "interfaces")]
 fn get_from_interface(
@@ -255,10 +279,10 @@ pub fn dump_stack(l: LuaState) -> Result<String, std::fmt::Error> {
 
 	let top = lua_gettop(l);
 	for i in 1..=top {
-		write!(&mut buf, "[{}] '{}' = ", i, rstr!(luaL_typename(l, i)));
+		write!(&mut buf, "[{}] '{}' = ", i, rstr!(luaL_typename(l, i)))?;
 		match lua_type(l, i) {
 			TNUMBER => write!(&mut buf, "{}", lua_tonumber(l, i)),
-			TSTRING => write!(&mut buf, "{}", rstr!(lua_tostring(l, i))),
+			TSTRING => write!(&mut buf, "\"{}\"", rstr!(lua_tostring(l, i))),
 			TBOOLEAN => write!(
 				&mut buf,
 				"{}",
@@ -272,8 +296,10 @@ pub fn dump_stack(l: LuaState) -> Result<String, std::fmt::Error> {
 			TNONE => write!(&mut buf, "none"),
 			TUSERDATA | TLIGHTUSERDATA => write!(&mut buf, "{:p}", lua_touserdata(l, i)),
 			TTHREAD => write!(&mut buf, "{:p}", lua_tothread(l, i)),
+			TTABLE | TFUNCTION => write!(&mut buf, "{:p}", lua_topointer(l, i)),
 			_ => write!(&mut buf, "Unknown type")
-		}?
+		}?;
+		buf.push('\n');
 	}
 
 	Ok(buf)
@@ -0,0 +1,99 @@
+// Tests for bytecode dumping and loading, run against a stock LuaJIT like luajit.rs.
+use rglua::bytecode::{dump_function, load_chunk, load_reader, DumpError, Header, HeaderError, LoadError};
+use rglua::prelude::*;
+use rglua::testing::TestState;
+use std::io::{self, Read};
+
+#[test]
+fn roundtrip() {
+	let Some(l) = TestState::new() else { return };
+
+	let lua = unsafe { Lua::from_raw(*l) };
+	lua.load_buffer(b"local a, b = ...\nreturn a * b", "@double.lua", None).unwrap();
+
+	let bytes = dump_function(*l, -1, false).unwrap();
+	lua.pop(1);
+
+	let header = Header::parse(&bytes).unwrap();
+	assert!(!header.stripped);
+	assert_eq!(header.chunk_name.as_deref(), Some("@double.lua"));
+
+	load_chunk(*l, &bytes, "=cached", Some("b")).unwrap();
+	assert_eq!(lua.protected_call::<_, f64>((6.0, 7.0)), Ok(42.0));
+
+	// Text isn't allowed as bytecode, and the other way around
+	assert!(matches!(load_chunk(*l, b"return 1", "=text", Some("b")), Err(LuaError::Syntax { .. })));
+	assert!(matches!(load_chunk(*l, &bytes, "=bytes", Some("t")), Err(LuaError::Syntax { .. })));
+}
+
+#[test]
+fn stripped() {
+	let Some(l) = TestState::new() else { return };
+
+	// Uses string.dump as it was when opened
+	l.exec("string.dump = function() return 'replaced' end").unwrap();
+
+	let lua = unsafe { Lua::from_raw(*l) };
+	lua.load_buffer(b"return 1", "@one.lua", None).unwrap();
+	let bytes = dump_function(*l, -1, true).unwrap();
+
+	let header = Header::parse(&bytes).unwrap();
+	assert!(header.stripped);
+	assert_eq!(header.chunk_name, None);
+
+	lua_pushcfunction(*l, rglua::error::traceback);
+	assert!(matches!(dump_function(*l, -1, false), Err(DumpError::NotLuaFunction)));
+}
+
+#[test]
+fn header() {
+	assert_eq!(Header::parse(b"return 1"), Err(HeaderError::NotBytecode));
+	assert_eq!(Header::parse(b"\x1bLJ\x02\x00\x05ab"), Err(HeaderError::Truncated));
+
+	let header = Header::parse(b"\x1bLJ\x02\x04\x03=ab").unwrap();
+	assert_eq!(header.version, 2);
+	assert!(header.ffi && !header.stripped && !header.big_endian);
+	assert_eq!(header.chunk_name.as_deref(), Some("=ab"));
+}
+
+/// Reads a few bytes at a time, then fails if told to.
+struct Trickle<'a> {
+	data: &'a [u8],
+	fail_at: Option<usize>,
+	read: usize
+}
+
+impl Read for Trickle<'_> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		if self.fail_at.is_some_and(|at| self.read >= at) {
+			return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "archive is corrupt"));
+		}
+
+		let n = self.data.len().min(buf.len()).min(3);
+		buf[..n].copy_from_slice(&self.data[..n]);
+		self.data = &self.data[n..];
+		self.read += n;
+		Ok(n)
+	}
+}
+
+#[test]
+fn reader() {
+	let Some(l) = TestState::new() else { return };
+	let lua = unsafe { Lua::from_raw(*l) };
+
+	let code = b"local t = {}\nfor i = 1, 10 do t[i] = i * 2 end\nreturn t[10]";
+	let trickle = Trickle { data: code, fail_at: None, read: 0 };
+	load_reader(*l, trickle, "=streamed", Some("t")).unwrap();
+	assert_eq!(lua.protected_call::<_, f64>(()), Ok(20.0));
+
+	// Cut off by an error, which would otherwise look like a syntax error or even load
+	let top = lua.top();
+	let trickle = Trickle { data: code, fail_at: Some(12), read: 0 };
+	let err = load_reader(*l, trickle, "=streamed", None).unwrap_err();
+	assert!(matches!(&err, LoadError::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof), "{err:?}");
+	assert_eq!(lua.top(), top);
+
+	let err = load_reader(*l, &b"return +"[..], "=broken", None).unwrap_err();
+	assert!(matches!(err, LoadError::Lua(LuaError::Syntax { .. })), "{err:?}");
+}
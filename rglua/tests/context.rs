@@ -0,0 +1,126 @@
+// Tests for ModuleContext and the gmod13_close generated for it, run against a stock LuaJIT like luajit.rs.
+use rglua::context;
+use rglua::prelude::*;
+use rglua::testing::TestState;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[lua_function]
+fn noop() {}
+
+#[gmod_open]
+fn open(_l: LuaState, ctx: &mut ModuleContext) -> Result<i32, LuaError> {
+	ctx.add_hook("Think", "context", noop)?;
+	ctx.add_concommand("context_cmd", noop)?;
+	ctx.create_timer("context_timer", 1.0, 0, noop)?;
+	ctx.on_close(|l| {
+		lua_pushboolean(l, 1);
+		lua_setglobal(l, cstr!("closed"));
+	});
+
+	Ok(0)
+}
+
+/// Stand-ins for gmod's libraries, logging every call to ``calls``.
+fn setup() -> Option<TestState> {
+	let l = TestState::new()?;
+	l.exec(
+		"calls = {}
+		local function lib(name)
+			return setmetatable({}, { __index = function(_, f)
+				return function(a) calls[#calls + 1] = name .. '.' .. f .. ' ' .. a end
+			end })
+		end
+		hook, concommand, timer = lib('hook'), lib('concommand'), lib('timer')"
+	)
+	.unwrap();
+	Some(l)
+}
+
+#[test]
+fn generated_close() {
+	let Some(l) = setup() else { return };
+
+	assert_eq!(gmod13_open(*l), 0);
+	l.exec("assert(#calls == 3, #calls)").unwrap();
+
+	assert_eq!(gmod13_close(*l), 0);
+	l.exec(
+		"assert(closed)
+		local expected = {
+			'hook.Add Think', 'concommand.Add context_cmd', 'timer.Create context_timer',
+			'timer.Remove context_timer', 'concommand.Remove context_cmd', 'hook.Remove Think'
+		}
+		for i, call in ipairs(expected) do assert(calls[i] == call, calls[i]) end
+		assert(#calls == #expected)"
+	)
+	.unwrap();
+
+	// Nothing left to tear down
+	assert_eq!(gmod13_close(*l), 0);
+	l.exec("assert(#calls == 6)").unwrap();
+}
+
+#[test]
+fn reopen() {
+	let Some(l) = setup() else { return };
+
+	assert_eq!(gmod13_open(*l), 0);
+	assert_eq!(gmod13_open(*l), 0);
+	l.exec("assert(calls[4] == 'timer.Remove context_timer', calls[4])\nassert(#calls == 9)").unwrap();
+}
+
+#[test]
+fn reverse_order() {
+	let Some(l) = setup() else { return };
+	let order = Rc::new(RefCell::new(vec![]));
+
+	let ctx = ModuleContext::get(*l);
+	lua_createtable(*l, 0, 0);
+	ctx.keep(LuaRef::pop(*l));
+
+	for i in 0..3 {
+		let order = order.clone();
+		ctx.on_close(move |_| order.borrow_mut().push(i));
+	}
+
+	// A library that's gone is reported, and doesn't stop the rest.
+	ctx.add_hook("Think", "gone", noop).unwrap();
+	l.exec("hook = nil").unwrap();
+
+	context::close(*l);
+	assert_eq!(*order.borrow(), [2, 1, 0]);
+}
+
+#[test]
+fn close_reports_errors() {
+	let Some(l) = setup() else { return };
+	l.exec("ErrorNoHalt = function(msg) reported = msg end").unwrap();
+
+	let ctx = ModuleContext::get(*l);
+	ctx.on_close(|l| {
+		// Recorded while closing, and still torn down
+		ModuleContext::get(l).on_close(|l| {
+			lua_pushboolean(l, 1);
+			lua_setglobal(l, cstr!("closed"));
+		});
+	});
+	ctx.on_close(|l| {
+		luaL_error(l, cstr!("cleanup broke"));
+	});
+
+	context::close(*l);
+	l.exec("assert(reported:find('cleanup broke'), reported)\nassert(closed)").unwrap();
+
+	// Panics can only be caught where lua errors don't unwind through catch_unwind
+	if !rglua::__private::LUA_ERRORS_UNWIND {
+		ModuleContext::get(*l).on_close(|_| panic!("cleanup panicked"));
+		context::close(*l);
+		l.exec("assert(reported:find('cleanup panicked'), reported)").unwrap();
+	}
+
+	// Nothing was left behind
+	l.exec("closed = nil").unwrap();
+	context::close(*l);
+	l.exec("assert(not closed)").unwrap();
+}
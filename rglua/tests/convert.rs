@@ -0,0 +1,94 @@
+// Tests for ToLua / FromLua, run against a stock LuaJIT like luajit.rs.
+use rglua::prelude::*;
+use rglua::testing::TestState;
+use std::collections::HashMap;
+
+fn round_trip<T: ToLua + FromLua>(l: LuaState, v: T) -> T {
+	v.push_to_lua(l);
+	let out = T::from_lua(l, -1).unwrap();
+	lua_pop(l, 1);
+	out
+}
+
+#[test]
+fn primitives() {
+	let Some(l) = TestState::new() else { return };
+
+	assert_eq!(round_trip(*l, 5i32), 5);
+	assert_eq!(round_trip(*l, u64::from(u32::MAX)), u64::from(u32::MAX));
+	assert_eq!(round_trip(*l, -2.5f64), -2.5);
+	assert!(round_trip(*l, true));
+	assert_eq!(round_trip(*l, String::from("hi\0there")), "hi\0there");
+	assert_eq!(round_trip(*l, Some(3u8)), Some(3));
+	assert_eq!(round_trip::<Option<u8>>(*l, None), None);
+	assert_eq!(round_trip(*l, Vector::new(1.0, 2.0, 3.0)), Vector::new(1.0, 2.0, 3.0));
+	assert_eq!(round_trip(*l, Angle::new(0.0, 90.0, 0.0)), Angle::new(0.0, 90.0, 0.0));
+
+	"borrowed".push_to_lua(*l);
+	assert_eq!(String::from_lua(*l, -1).unwrap(), "borrowed");
+	assert_eq!(lua_gettop(*l), 1);
+}
+
+#[test]
+fn tables() {
+	let Some(l) = TestState::new() else { return };
+
+	assert_eq!(round_trip(*l, vec![1, 2, 3]), vec![1, 2, 3]);
+
+	let map = HashMap::from([(String::from("a"), 1.0), (String::from("b"), 2.0)]);
+	assert_eq!(round_trip(*l, map.clone()), map);
+
+	let nested = vec![vec![String::from("x")], vec![]];
+	assert_eq!(round_trip(*l, nested.clone()), nested);
+	assert_eq!(lua_gettop(*l), 0);
+}
+
+#[test]
+fn multi() {
+	let Some(l) = TestState::new() else { return };
+
+	assert_eq!((1, "two", false).push_multi(*l), 3);
+	assert_eq!(<(i32, String, bool)>::COUNT, 3);
+
+	let (a, b, c) = <(i32, String, bool)>::from_lua_multi(*l, 1).unwrap();
+	assert_eq!((a, b.as_str(), c), (1, "two", false));
+
+	assert_eq!(().push_multi(*l), 0);
+	assert_eq!(lua_gettop(*l), 3);
+}
+
+#[test]
+fn errors() {
+	let Some(l) = TestState::new() else { return };
+
+	lua_pushnil(*l);
+	let err = f64::from_lua(*l, 1).unwrap_err();
+	assert_eq!(err.to_string(), "bad argument #1 (number expected, got nil)");
+
+	lua_pushnumber(*l, 300.0);
+	let err = u8::from_lua(*l, -1).unwrap_err();
+	assert_eq!((err.arg, err.expected.as_ref()), (2, "number in range of u8"));
+
+	// The largest doubles below 2^64 and 2^63 fit, those don't
+	for (n, fits) in [(2f64.powi(64), false), (2f64.powi(64) - 4096.0, true)] {
+		lua_pushnumber(*l, n);
+		assert_eq!(u64::from_lua(*l, -1).is_ok(), fits, "{n}");
+		lua_pop(*l, 1);
+	}
+	lua_pushnumber(*l, 2f64.powi(63));
+	assert!(i64::from_lua(*l, -1).is_err());
+	lua_pushnumber(*l, -(2f64.powi(63)));
+	assert_eq!(i64::from_lua(*l, -1).unwrap(), i64::MIN);
+	lua_pop(*l, 2);
+
+	assert!(l.exec("t = {1, 2, 'three'}").is_ok());
+	lua_getglobal(*l, cstr!("t"));
+	let err = Vec::<i32>::from_lua(*l, 3).unwrap_err();
+	assert_eq!(err.to_string(), "bad argument #3 (table of number expected, got string at [3])");
+	assert_eq!(lua_gettop(*l), 3);
+
+	// Lua values can be read as strings, but not the other way around
+	assert_eq!(String::from_lua(*l, 2).unwrap(), "300");
+	assert_eq!(lua_type(*l, 2), TNUMBER);
+	assert!(bool::from_lua(*l, 1).is_err());
+}
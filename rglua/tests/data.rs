@@ -0,0 +1,79 @@
+// Tests for per-state data, run against a stock LuaJIT like luajit.rs.
+use rglua::data::{set_state_data, take_state_data, with_state_data};
+use rglua::prelude::*;
+use rglua::testing::TestState;
+use std::rc::Rc;
+
+/// Counts how many are alive through a shared Rc.
+struct Tracked {
+	_alive: Rc<()>
+}
+
+#[derive(Debug, PartialEq)]
+struct Counter(u32);
+
+#[test]
+fn set_and_take() {
+	let Some(l) = TestState::new() else { return };
+	let top = lua_gettop(*l);
+
+	assert_eq!(with_state_data(*l, |c: &mut Counter| c.0), None);
+	set_state_data(*l, Counter(1));
+	with_state_data(*l, |c: &mut Counter| c.0 += 1);
+	assert_eq!(with_state_data(*l, |c: &mut Counter| c.0), Some(2));
+
+	set_state_data(*l, Counter(10));
+	assert_eq!(take_state_data::<Counter>(*l), Some(Counter(10)));
+	assert_eq!(take_state_data::<Counter>(*l), None);
+	assert_eq!(lua_gettop(*l), top);
+}
+
+#[test]
+fn per_state() {
+	let (Some(a), Some(b)) = (TestState::new(), TestState::new()) else { return };
+
+	set_state_data(*a, Counter(1));
+	assert_eq!(with_state_data(*b, |c: &mut Counter| c.0), None);
+	set_state_data(*b, Counter(2));
+	assert_eq!(with_state_data(*a, |c: &mut Counter| c.0), Some(1));
+}
+
+#[test]
+fn dropped() {
+	let Some(l) = TestState::new() else { return };
+	let alive = Rc::new(());
+
+	set_state_data(*l, Tracked { _alive: alive.clone() });
+	set_state_data(*l, Tracked { _alive: alive.clone() });
+	assert_eq!(Rc::strong_count(&alive), 2, "replaced value wasn't dropped");
+
+	// Other types don't share the slot
+	set_state_data(*l, Counter(0));
+	assert_eq!(Rc::strong_count(&alive), 2);
+
+	lua_gc(*l, GCCOLLECT, 0);
+	assert_eq!(Rc::strong_count(&alive), 2, "value was collected while set");
+
+	drop(l);
+	assert_eq!(Rc::strong_count(&alive), 1, "value wasn't dropped when the state closed");
+}
+
+#[test]
+fn borrowed() {
+	let Some(l) = TestState::new() else { return };
+	let panics = |f: &dyn Fn()| std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).is_err();
+
+	set_state_data(*l, Counter(0));
+	assert!(panics(&|| {
+		with_state_data(*l, |_: &mut Counter| with_state_data(*l, |_: &mut Counter| ()));
+	}));
+	assert!(panics(&|| {
+		with_state_data(*l, |_: &mut Counter| take_state_data::<Counter>(*l));
+	}));
+
+	// Nothing was left on the stack by the panics
+	assert_eq!(lua_gettop(*l), 0);
+
+	// Still there, and not borrowed anymore
+	assert_eq!(take_state_data::<Counter>(*l), Some(Counter(0)));
+}
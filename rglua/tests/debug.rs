@@ -0,0 +1,48 @@
+// Tests for Backtrace, run against a stock LuaJIT like luajit.rs.
+use rglua::debug::Backtrace;
+use rglua::prelude::*;
+use rglua::testing::TestState;
+use std::cell::RefCell;
+
+thread_local! {
+	static CAPTURED: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+}
+
+#[lua_function]
+fn capture(l: LuaState) -> i32 {
+	let trace = Backtrace::capture_from(l, 1, true);
+	CAPTURED.with(|c| *c.borrow_mut() = Some(trace));
+	0
+}
+
+#[test]
+fn backtrace() {
+	let Some(l) = TestState::new() else { return };
+
+	lua_pushcfunction(*l, capture);
+	lua_setglobal(*l, cstr!("capture"));
+
+	l.exec("local up = 'upvalue'\nfunction inner(x)\n\tlocal s = up .. x\n\tcapture()\nend\ninner(5)").unwrap();
+
+	let trace = CAPTURED.with(|c| c.borrow_mut().take()).unwrap();
+	let inner = &trace.frames[0];
+	assert_eq!(inner.short_src, "exec");
+	assert_eq!(inner.line, Some(4));
+	assert_eq!(inner.line_defined, Some(2));
+	assert_eq!(inner.name.as_deref(), Some("inner"));
+	assert_eq!(inner.namewhat, "global");
+	assert_eq!(inner.what, "Lua");
+	assert_eq!(inner.nups, 1);
+
+	let locals: Vec<_> = inner.locals.iter().map(|v| (v.name.as_str(), v.value.as_str())).collect();
+	assert_eq!(locals, [("x", "5"), ("s", "\"upvalue5\"")]);
+	assert_eq!(inner.upvalues[0].name, "up");
+	assert_eq!(inner.upvalues[0].type_name, "string");
+
+	assert_eq!(trace.frames[1].what, "main");
+	assert_eq!(trace.frames[1].to_string(), "exec:6: in main chunk");
+
+	let shown = trace.to_string();
+	assert!(shown.starts_with("stack traceback:\n\texec:4: in function 'inner'\n\t\tlocal x = 5"), "{shown}");
+	assert_eq!(lua_gettop(*l), 0);
+}
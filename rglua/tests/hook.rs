@@ -0,0 +1,72 @@
+// Tests for set_hook, run against a stock LuaJIT like luajit.rs.
+use rglua::debug::{set_hook, HookEvent};
+use rglua::prelude::*;
+use rglua::testing::TestState;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[test]
+fn lines() {
+	let Some(l) = TestState::new() else { return };
+
+	let lines = Rc::new(RefCell::new(vec![]));
+	let hook = set_hook(*l, MASKLINE, 0, {
+		let lines = lines.clone();
+		move |_, event, ar| {
+			if let HookEvent::Line(line) = event {
+				assert_eq!(ar.currentline as u32, line);
+				lines.borrow_mut().push(line);
+			}
+			Ok(())
+		}
+	});
+
+	l.exec("local a = 1\nlocal b = 2\nif a > b then\n\ta = b\nend").unwrap();
+	drop(hook);
+
+	assert_eq!(*lines.borrow(), [1, 2, 3, 5]);
+	assert!(lua_gethook(*l).is_none() || lua_gethookmask(*l) == 0);
+}
+
+#[test]
+fn count_error() {
+	let Some(l) = TestState::new() else { return };
+
+	let mut left = 10;
+	let _hook = set_hook(*l, MASKCOUNT, 100, move |_, event, _| {
+		assert_eq!(event, HookEvent::Count);
+		left -= 1;
+		if left == 0 {
+			return Err(String::from("out of instructions"));
+		}
+		Ok(())
+	});
+
+	// Compiled traces don't run hooks
+	let err = l.exec("jit.off()\nlocal i = 0\nwhile true do i = i + 1 end").unwrap_err();
+	assert!(err.contains("out of instructions"), "{err}");
+}
+
+#[test]
+fn chains_previous() {
+	let Some(l) = TestState::new() else { return };
+
+	l.exec("calls = 0\ndebug.sethook(function() calls = calls + 1 end, 'c')").unwrap();
+
+	let ours = Rc::new(RefCell::new(0));
+	let hook = set_hook(*l, MASKCALL, 0, {
+		let ours = ours.clone();
+		move |_, _, _| {
+			*ours.borrow_mut() += 1;
+			Ok(())
+		}
+	});
+
+	l.exec("calls = 0\nlocal function f() end\nf() f()").unwrap();
+	assert!(*ours.borrow() >= 2);
+
+	drop(hook);
+	let before = *ours.borrow();
+	l.exec("calls = 0\nlocal function f() end\nf() f()\nassert(calls >= 2)\nassert(debug.gethook() ~= nil)").unwrap();
+	assert_eq!(*ours.borrow(), before);
+}
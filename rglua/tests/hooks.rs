@@ -0,0 +1,122 @@
+// Tests for hooks and timer, run against a stock LuaJIT like luajit.rs.
+// The hook and timer libraries are small stand-ins for gmod's.
+use rglua::closure::push_closure;
+use rglua::context;
+use rglua::prelude::*;
+use rglua::testing::TestState;
+use rglua::{hooks, timer};
+use std::cell::Cell;
+use std::rc::Rc;
+
+fn setup() -> Option<TestState> {
+	let l = TestState::new()?;
+	l.exec(
+		"local hooks = {}
+		hook = {
+			Add = function(event, id, f) hooks[event] = hooks[event] or {}; hooks[event][id] = f end,
+			Remove = function(event, id) if hooks[event] then hooks[event][id] = nil end end,
+			Call = function(event, gm, ...)
+				for _, f in pairs(hooks[event] or {}) do
+					local a, b = f(...)
+					if a ~= nil then return a, b end
+				end
+				if gm and gm[event] then return gm[event](gm, ...) end
+			end,
+			Count = function(event) local n = 0 for _ in pairs(hooks[event] or {}) do n = n + 1 end return n end
+		}
+
+		timers = {}
+		timer = {
+			Create = function(name, delay, reps, f) timers[name] = { reps = reps, f = f } end,
+			Remove = function(name) timers[name] = nil end,
+			Fire = function()
+				for name, t in pairs(timers) do
+					t.f()
+					t.reps = t.reps - 1
+					if t.reps == 0 then timers[name] = nil end
+				end
+			end,
+			Count = function() local n = 0 for _ in pairs(timers) do n = n + 1 end return n end
+		}"
+	)
+	.unwrap();
+	Some(l)
+}
+
+#[test]
+fn add_and_call() {
+	let Some(l) = setup() else { return };
+
+	hooks::add(*l, "PlayerSay", "upper", |_, (_ply, text): (Option<bool>, String)| {
+		(text != "pass").then(|| text.to_uppercase())
+	})
+	.unwrap();
+
+	let said: Option<String> = hooks::call(*l, "PlayerSay", (None::<bool>, "hello")).unwrap();
+	assert_eq!(said.as_deref(), Some("HELLO"));
+	let said: Option<String> = hooks::call(*l, "PlayerSay", (None::<bool>, "pass")).unwrap();
+	assert_eq!(said, None);
+
+	// Bad arguments are raised like from a lua function
+	let e = hooks::call::<_, ()>(*l, "PlayerSay", ()).unwrap_err();
+	assert!(e.message().contains("bad argument #2"), "{e}");
+
+	hooks::remove(*l, "PlayerSay", "upper").unwrap();
+	l.exec("assert(hook.Count('PlayerSay') == 0)").unwrap();
+
+	// The gamemode gets called last
+	l.exec("GAMEMODE = { PlayerSay = function(self, ply, text) return 'gm ' .. text end }").unwrap();
+	let said: Option<String> = hooks::call(*l, "PlayerSay", (None::<bool>, "hello")).unwrap();
+	assert_eq!(said.as_deref(), Some("gm hello"));
+}
+
+#[test]
+fn removed_on_close() {
+	let Some(l) = setup() else { return };
+	let dropped = Rc::new(Cell::new(false));
+
+	struct Flag(Rc<Cell<bool>>);
+	impl Drop for Flag {
+		fn drop(&mut self) {
+			self.0.set(true);
+		}
+	}
+
+	let flag = Flag(dropped.clone());
+	hooks::add(*l, "Think", "a", move |_, ()| {
+		let _ = &flag;
+	})
+	.unwrap();
+	hooks::add(*l, "Think", "b", |_, ()| ()).unwrap();
+	timer::create(*l, "repeat", 1.0, 0, |_| ()).unwrap();
+	l.exec("assert(hook.Count('Think') == 2)\nassert(timer.Count() == 1)").unwrap();
+
+	// Lua still has this one after closing
+	push_closure(*l, |_, ()| ());
+	lua_setglobal(*l, cstr!("kept"));
+
+	context::close(*l);
+	l.exec("assert(hook.Count('Think') == 0)\nassert(timer.Count() == 0)").unwrap();
+	assert!(dropped.get(), "closure wasn't dropped on close");
+
+	let e = l.exec("kept()").unwrap_err();
+	assert!(e.contains("called after its module was closed"), "{e}");
+}
+
+#[test]
+fn timers() {
+	let Some(l) = setup() else { return };
+	let ran = Rc::new(Cell::new(0));
+
+	let counter = ran.clone();
+	timer::create(*l, "twice", 1.0, 2, move |_| counter.set(counter.get() + 1)).unwrap();
+	let counter = ran.clone();
+	timer::simple(*l, 1.0, move |_| counter.set(counter.get() + 10)).unwrap();
+
+	l.exec("timer.Fire()\ntimer.Fire()\ntimer.Fire()").unwrap();
+	assert_eq!(ran.get(), 12);
+
+	timer::create(*l, "gone", 1.0, 0, |_| panic!("removed timer ran")).unwrap();
+	timer::remove(*l, "gone").unwrap();
+	l.exec("timer.Fire()\nassert(timer.Count() == 0)").unwrap();
+}
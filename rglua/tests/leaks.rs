@@ -0,0 +1,96 @@
+// Checks that generated lua functions drop everything before raising a lua error.
+// 32 bit LuaJIT longjmps past Rust frames without running destructors, so anything still alive would leak.
+// 64 bit unwinds instead, so this checks what's alive from an xpcall message handler, which runs before the error unwinds anything.
+// Counts live allocations of this thread, so keep this to a single test.
+use rglua::prelude::*;
+use rglua::testing::TestState;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+struct Counting;
+
+thread_local! {
+	static LIVE: Cell<isize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		let _ = LIVE.try_with(|l| l.set(l.get() + layout.size() as isize));
+		System.alloc(layout)
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		let _ = LIVE.try_with(|l| l.set(l.get() - layout.size() as isize));
+		System.dealloc(ptr, layout)
+	}
+}
+
+#[global_allocator]
+static ALLOC: Counting = Counting;
+
+fn live() -> isize {
+	LIVE.with(|l| l.get())
+}
+
+thread_local! {
+	static LIVE_AT_RAISE: Cell<isize> = const { Cell::new(0) };
+}
+
+extern "C-unwind" fn record_live(_l: LuaState) -> i32 {
+	LIVE_AT_RAISE.with(|l| l.set(live()));
+	1
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+struct BigError(String);
+
+#[lua_function]
+fn fails(l: LuaState) -> Result<i32, BigError> {
+	let _held = vec![0u8; 4096];
+	let n = luaL_checkinteger(l, 1);
+	Err(BigError("x".repeat(n as usize)))
+}
+
+#[lua_function]
+fn bad_second(first: String, _second: f64) -> String {
+	first
+}
+
+#[lua_function]
+fn panics(msg: String) {
+	let _held = vec![0u8; 4096];
+	panic!("{msg}");
+}
+
+#[test]
+fn no_leaks() {
+	let Some(l) = TestState::new() else { return };
+
+	lua_pushcfunction(*l, fails);
+	lua_setglobal(*l, cstr!("fails"));
+	lua_pushcfunction(*l, bad_second);
+	lua_setglobal(*l, cstr!("bad_second"));
+	lua_pushcfunction(*l, panics);
+	lua_setglobal(*l, cstr!("panics"));
+	lua_pushcfunction(*l, record_live);
+	lua_setglobal(*l, cstr!("record_live"));
+
+	// Don't print the panics below, and let the panic hook install itself first.
+	std::panic::set_hook(Box::new(|_| {}));
+	let _ = l.exec("pcall(panics, 'warmup')");
+
+	for call in [
+		"fails(1000)",
+		"bad_second(string.rep('y', 1000), 'not a number')",
+		"panics(string.rep('z', 1000))"
+	] {
+		let code = format!("assert(not xpcall(function() {call} end, record_live))");
+		let before = live();
+
+		l.exec(&code).unwrap();
+		assert_eq!(LIVE_AT_RAISE.with(|l| l.get()), before, "{call} had values alive when raising");
+		assert_eq!(live(), before, "{call} leaked");
+	}
+}
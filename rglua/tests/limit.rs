@@ -0,0 +1,75 @@
+// Tests for run_limited, run against a stock LuaJIT like luajit.rs.
+use rglua::limit::{run_limited, LimitError, Limits};
+use rglua::prelude::*;
+use rglua::testing::TestState;
+use std::time::Duration;
+
+#[test]
+fn within_limits() {
+	let Some(l) = TestState::new() else { return };
+
+	let limits = Limits {
+		max_instructions: Some(100_000),
+		max_duration: Some(Duration::from_secs(5)),
+		max_memory: Some(1024 * 1024)
+	};
+
+	let n: f64 = run_limited(*l, "local n = 0\nfor i = 1, 100 do n = n + i end\nreturn n", limits).unwrap();
+	assert_eq!(n, 5050.0);
+
+	let err = run_limited::<()>(*l, "error('nope')", limits).unwrap_err();
+	assert!(matches!(err, LimitError::Lua(LuaError::Runtime { .. })), "{err:?}");
+}
+
+#[test]
+fn instructions() {
+	let Some(l) = TestState::new() else { return };
+
+	let limits = Limits {
+		max_instructions: Some(10_000),
+		..Default::default()
+	};
+
+	// Catching the error doesn't get around the limit
+	let err = run_limited::<()>(*l, "while true do pcall(function() while true do end end) end", limits).unwrap_err();
+	assert_eq!(err, LimitError::Instructions(10_000));
+	assert!(lua_gethookmask(*l) == 0);
+}
+
+#[test]
+fn duration() {
+	let Some(l) = TestState::new() else { return };
+
+	let limits = Limits {
+		max_duration: Some(Duration::from_millis(20)),
+		..Default::default()
+	};
+
+	let err = run_limited::<()>(*l, "while true do end", limits).unwrap_err();
+	assert_eq!(err, LimitError::Duration(Duration::from_millis(20)));
+}
+
+#[test]
+fn memory() {
+	let Some(l) = TestState::new() else { return };
+
+	let mut ud = std::ptr::null_mut();
+	let before = lua_getallocf(*l, &mut ud) as usize;
+
+	let limits = Limits {
+		max_memory: Some(64 * 1024),
+		..Default::default()
+	};
+
+	let err = run_limited::<()>(*l, "local t = {}\nfor i = 1, 1e6 do t[i] = tostring(i) end", limits).unwrap_err();
+	assert_eq!(err, LimitError::Memory(64 * 1024));
+
+	// The allocator is put back and the state still works
+	assert_eq!(lua_getallocf(*l, &mut ud) as usize, before);
+	l.exec("local t = {}\nfor i = 1, 1e5 do t[i] = tostring(i) end").unwrap();
+
+	// Memory from before the run that's freed during it isn't added to the budget
+	l.exec("big = string.rep('x', 1024 * 1024)").unwrap();
+	let err = run_limited::<()>(*l, "big = nil collectgarbage() collectgarbage() local s = string.rep('y', 512 * 1024)", limits).unwrap_err();
+	assert_eq!(err, LimitError::Memory(64 * 1024));
+}
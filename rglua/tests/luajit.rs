@@ -0,0 +1,257 @@
+// Tests that run against a stock LuaJIT through rglua::testing.
+// They are skipped if LuaJIT can't be found, set RGLUA_LUA_SHARED to point at it.
+use rglua::prelude::*;
+use rglua::testing::TestState;
+
+#[lua_function]
+fn is_even(l: LuaState) -> i32 {
+	let num = luaL_checkinteger(l, 1);
+	lua_pushboolean(l, (num % 2 == 0) as i32);
+	1
+}
+
+#[derive(Debug, thiserror::Error)]
+enum NumError {
+	#[error("Number was negative!")]
+	Negative
+}
+
+#[lua_function]
+fn positive(l: LuaState) -> Result<i32, NumError> {
+	let num = luaL_checkinteger(l, 1);
+	if num < 0 {
+		return Err(NumError::Negative);
+	}
+
+	lua_pushinteger(l, num);
+	Ok(1)
+}
+
+#[lua_function]
+fn add(_l: LuaState, a: f64, b: Option<f64>) -> Result<(f64, bool), NumError> {
+	let sum = a + b.unwrap_or(1.0);
+	if sum < 0.0 {
+		return Err(NumError::Negative);
+	}
+	Ok((sum, sum > 10.0))
+}
+
+#[lua_function]
+fn join(parts: Vec<String>, sep: String) -> String {
+	parts.join(&sep)
+}
+
+#[lua_function]
+fn panics(msg: String) -> i32 {
+	panic!("{msg}");
+}
+
+#[lua_function]
+fn panics_with_state(l: LuaState) -> i32 {
+	let msg = rstr!(luaL_checkstring(l, 1));
+	panic!("{msg}");
+}
+
+#[test]
+fn stack() {
+	let Some(l) = TestState::new() else { return };
+
+	lua_pushnumber(*l, 5000.0);
+	lua_pushstring(*l, cstr!("hello"));
+	lua_pushboolean(*l, 1);
+	lua_pushnil(*l);
+	lua_newtable(*l);
+
+	assert!(dump_stack(*l).unwrap().contains("[5] 'table' = 0x"));
+	lua_pop(*l, 1);
+
+	assert_eq!(
+		dump_stack(*l).unwrap(),
+		"[1] 'number' = 5000\n[2] 'string' = \"hello\"\n[3] 'boolean' = true\n[4] 'nil' = nil\n"
+	);
+}
+
+#[test]
+fn register() {
+	let Some(l) = TestState::new() else { return };
+
+	let lib = reg! [
+		"is_even" => is_even,
+		"positive" => positive
+	];
+	luaL_register(*l, cstr!("nums"), lib.as_ptr());
+	lua_pop(*l, 1);
+
+	l.exec("assert(nums.is_even(2) == true and nums.is_even(3) == false)").unwrap();
+	l.exec("assert(nums.positive(5) == 5)").unwrap();
+
+	let err = l.exec("nums.positive(-1)").unwrap_err();
+	assert!(err.contains("Number was negative!"), "{err}");
+
+	let err = l.exec("nums.is_even('foo')").unwrap_err();
+	assert!(err.contains("bad argument #1"), "{err}");
+}
+
+#[test]
+fn vector() {
+	let Some(l) = TestState::new() else { return };
+
+	lua_pushvector(*l, Vector::new(1.0, 2.0, 3.0));
+	assert_eq!(luaL_checkvector(*l, 1), Vector::new(1.0, 2.0, 3.0));
+	assert_eq!(lua_tovector(*l, 1), Some(Vector::new(1.0, 2.0, 3.0)));
+	assert_eq!(lua_toangle(*l, 1), None);
+	assert_eq!(lua_gettop(*l), 1);
+
+	lua_pushangle(*l, Angle::new(90.0, 0.0, 0.0));
+	assert_eq!(lua_toangle(*l, 2), Some(Angle::new(90.0, 0.0, 0.0)));
+}
+
+#[test]
+fn testudata() {
+	let Some(l) = TestState::new() else { return };
+
+	luaL_newmetatable(*l, cstr!("Foo"));
+	lua_newuserdata(*l, 8);
+	lua_pushvalue(*l, 1);
+	lua_setmetatable(*l, -2);
+	lua_newuserdata(*l, 8);
+
+	assert!(luaL_testudata(*l, 2, cstr!("Foo")).is_some());
+	assert!(luaL_testudata(*l, 2, cstr!("Bar")).is_none());
+	// Without a metatable, and not a userdata
+	assert!(luaL_testudata(*l, 3, cstr!("Foo")).is_none());
+	assert!(luaL_testudata(*l, 1, cstr!("Foo")).is_none());
+	assert_eq!(lua_gettop(*l), 3);
+}
+
+#[test]
+fn dostring() {
+	let Some(l) = TestState::new() else { return };
+
+	assert!(luaL_dostring(*l, cstr!("ran = 1")));
+	assert!(!luaL_dostring(*l, cstr!("error('no')")));
+	lua_pop(*l, 1);
+
+	let path = std::env::temp_dir().join(format!("rglua-dofile-{}.lua", std::process::id()));
+	std::fs::write(&path, "ran = ran + 1").unwrap();
+	let name = std::ffi::CString::new(path.to_str().unwrap()).unwrap();
+	let ran = luaL_dofile(*l, name.as_ptr());
+	std::fs::remove_file(&path).unwrap();
+	assert!(ran);
+
+	lua_getglobal(*l, cstr!("ran"));
+	assert_eq!(lua_tonumber(*l, -1), 2.0);
+}
+
+#[test]
+fn shims() {
+	let Some(l) = TestState::new() else { return };
+
+	assert_eq!(luaL_newmetatable_type(*l, cstr!("Foo"), 100), 1);
+	lua_getfield(*l, -1, cstr!("MetaName"));
+	assert_eq!(rstr!(lua_tostring(*l, -1)), "Foo");
+	lua_pop(*l, 2);
+
+	assert!(luaL_dostring(*l, cstr!("co = coroutine.create(function(a) local b = coroutine.yield(a + 1) return b end)")));
+
+	lua_getglobal(*l, cstr!("co"));
+	let co = lua_tothread(*l, -1);
+
+	lua_pushnumber(co, 1.0);
+	assert_eq!(lua_resume(co, 1), YIELD);
+	assert_eq!(lua_tonumber(co, -1), 2.0);
+}
+
+#[test]
+fn typed_args() {
+	let Some(l) = TestState::new() else { return };
+
+	let lib = reg! [
+		"add" => add,
+		"join" => join
+	];
+	luaL_register(*l, cstr!("typed"), lib.as_ptr());
+	lua_pop(*l, 1);
+
+	l.exec("local s, big = typed.add(2, 3) assert(s == 5 and big == false)").unwrap();
+	l.exec("local s, big = typed.add(20) assert(s == 21 and big == true)").unwrap();
+	l.exec("assert(typed.join({'a', 'b'}, ', ') == 'a, b')").unwrap();
+
+	let err = l.exec("typed.add(-5)").unwrap_err();
+	assert!(err.contains("Number was negative!"), "{err}");
+
+	let err = l.exec("typed.add(1, 'x')").unwrap_err();
+	assert!(err.contains("bad argument #2 to 'add' (number expected, got string)"), "{err}");
+
+	let err = l.exec("typed.join({'a', {}}, '')").unwrap_err();
+	assert!(err.contains("bad argument #1 to 'join' (table of string expected, got table at [2])"), "{err}");
+}
+
+#[test]
+fn panic() {
+	let Some(l) = TestState::new() else { return };
+
+	lua_pushcfunction(*l, panics);
+	lua_setglobal(*l, cstr!("panics"));
+
+	let err = l.exec("panics('Oh no!')").unwrap_err();
+	assert!(err.contains("panicked at"), "{err}");
+	assert!(err.contains("luajit.rs"), "{err}");
+	assert!(err.ends_with("Oh no!"), "{err}");
+
+	// Still usable after
+	let err = l.exec("panics('Again')").unwrap_err();
+	assert!(err.ends_with("Again"), "{err}");
+}
+
+#[test]
+fn panic_with_state() {
+	let Some(l) = TestState::new() else { return };
+
+	lua_pushcfunction(*l, panics_with_state);
+	lua_setglobal(*l, cstr!("panics_with_state"));
+
+	if !rglua::__private::LUA_ERRORS_UNWIND {
+		let err = l.exec("panics_with_state('Oh no!')").unwrap_err();
+		assert!(err.ends_with("Oh no!"), "{err}");
+		return;
+	}
+
+	// Aborts where it can't be caught, so that's checked from another process running just this
+	if std::env::var_os("RGLUA_PANIC_CHILD").is_some() {
+		let _ = l.exec("panics_with_state('Oh no!')");
+		return;
+	}
+
+	let out = std::process::Command::new(std::env::current_exe().unwrap())
+		.args(["panic_with_state", "--exact", "--nocapture", "--test-threads=1"])
+		.env("RGLUA_PANIC_CHILD", "1")
+		.output()
+		.unwrap();
+
+	let stderr = String::from_utf8_lossy(&out.stderr);
+	assert!(!out.status.success(), "{stderr}");
+	assert!(stderr.contains("Oh no!"), "{stderr}");
+	assert!(stderr.contains("rglua: aborting"), "{stderr}");
+
+	// Lua errors still pass through
+	let err = l.exec("panics_with_state()").unwrap_err();
+	assert!(err.contains("bad argument #1"), "{err}");
+}
+
+#[test]
+fn missing_symbols() {
+	use rglua::lua::{init_lua_shared_fns, lua_shared, LuaSharedError, LuaSharedFns};
+	let Some(_l) = TestState::new() else { return };
+
+	// The gmod specific functions are filled in for a stock LuaJIT
+	let mut fns = LuaSharedFns::resolve(lua_shared().unwrap());
+	assert!(fns.missing().is_empty(), "{:?}", fns.missing());
+
+	fns.lua_gettop = None;
+	match init_lua_shared_fns(fns) {
+		Err(LuaSharedError::MissingSymbols(missing)) => assert_eq!(missing, ["lua_gettop"]),
+		Err(other) => panic!("Expected MissingSymbols, got {other}"),
+		Ok(_) => panic!("Expected MissingSymbols")
+	}
+}
@@ -0,0 +1,123 @@
+// Tests for MainThreadQueue, run against a stock LuaJIT like luajit.rs.
+// Ticks are driven by hand instead of by gmod's Think hook.
+use rglua::prelude::*;
+use rglua::queue::{MainThreadQueue, QueueError};
+use rglua::testing::TestState;
+use std::time::Duration;
+
+fn setup() -> Option<TestState> {
+	let l = TestState::new()?;
+	l.exec("hooks = {}\nhook = {\n\tAdd = function(event, name, f) hooks[event] = f end,\n\tRemove = function(event, name) hooks[event] = nil end\n}").unwrap();
+	Some(l)
+}
+
+#[test]
+fn queue() {
+	let Some(l) = setup() else { return };
+	let queue = MainThreadQueue::install(*l, Duration::from_secs(1)).unwrap();
+
+	std::thread::spawn(move || {
+		for i in 1..=3 {
+			queue.queue(move |l| {
+				lua_pushinteger(l, i);
+				lua_setglobal(l, cstr!("last"));
+			}).unwrap();
+		}
+	}).join().unwrap();
+
+	l.exec("hooks.Think()\nassert(last == 3, last)").unwrap();
+	assert_eq!(MainThreadQueue::drain(*l), 0);
+	MainThreadQueue::uninstall(*l);
+}
+
+#[test]
+fn block_on_main() {
+	let Some(l) = setup() else { return };
+	let queue = MainThreadQueue::install(*l, Duration::from_secs(1)).unwrap();
+	l.exec("answer = 42").unwrap();
+
+	assert_eq!(queue.block_on_main(|_| ()), Err(QueueError::OnMainThread));
+
+	let worker = std::thread::spawn(move || {
+		let answer = queue.block_on_main(|l| {
+			lua_getglobal(l, cstr!("answer"));
+			let n = lua_tointeger(l, -1);
+			lua_pop(l, 1);
+			n
+		});
+		let errored = queue.block_on_main(|l| {
+			luaL_error(l, cstr!("oops"));
+		});
+		let panicked = (!rglua::__private::LUA_ERRORS_UNWIND).then(|| queue.block_on_main(|_| panic!("oh no")));
+		(answer, errored, panicked)
+	});
+
+	while !worker.is_finished() {
+		MainThreadQueue::drain(*l);
+		std::thread::sleep(Duration::from_millis(1));
+	}
+
+	let (answer, errored, panicked) = worker.join().unwrap();
+	assert_eq!(answer, Ok(42));
+	assert!(matches!(errored, Err(QueueError::Lua(e)) if e.to_string().ends_with("oops")));
+	// Panics can only be caught where lua errors don't unwind through catch_unwind
+	if let Some(panicked) = panicked {
+		assert!(matches!(panicked, Err(QueueError::Panicked(msg)) if msg.contains("oh no")));
+	}
+	MainThreadQueue::uninstall(*l);
+}
+
+#[test]
+fn drain_reports_errors() {
+	let Some(l) = setup() else { return };
+	l.exec("ErrorNoHalt = function(msg) reported = msg end").unwrap();
+	let queue = MainThreadQueue::install(*l, Duration::from_secs(1)).unwrap();
+
+	queue.queue(|l| {
+		luaL_error(l, cstr!("queued error"));
+	}).unwrap();
+	queue.queue(|l| {
+		lua_pushboolean(l, 1);
+		lua_setglobal(l, cstr!("ran"));
+	}).unwrap();
+
+	// The error doesn't stop the rest
+	assert_eq!(MainThreadQueue::drain(*l), 2);
+	l.exec("assert(reported:find('queued error'), reported)\nassert(ran)").unwrap();
+	MainThreadQueue::uninstall(*l);
+}
+
+#[test]
+fn budget() {
+	let Some(l) = setup() else { return };
+	let queue = MainThreadQueue::install(*l, Duration::ZERO).unwrap();
+
+	for _ in 0..3 {
+		queue.queue(|_| std::thread::sleep(Duration::from_millis(1))).unwrap();
+	}
+
+	// At least one runs every tick, however small the budget.
+	assert_eq!(MainThreadQueue::drain(*l), 1);
+
+	MainThreadQueue::install(*l, Duration::from_secs(1)).unwrap();
+	assert_eq!(MainThreadQueue::drain(*l), 2);
+	MainThreadQueue::uninstall(*l);
+}
+
+#[test]
+fn uninstall() {
+	let Some(l) = setup() else { return };
+	let queue = MainThreadQueue::install(*l, Duration::from_secs(1)).unwrap();
+	queue.queue(|_| panic!("dropped without running")).unwrap();
+
+	// Closed whether it gets its closure queued before uninstalling or not.
+	let waiting = queue.clone();
+	let worker = std::thread::spawn(move || waiting.block_on_main(|_| ()));
+	std::thread::sleep(Duration::from_millis(10));
+
+	MainThreadQueue::uninstall(*l);
+	l.exec("assert(hooks.Think == nil)").unwrap();
+	assert_eq!(worker.join().unwrap(), Err(QueueError::Closed));
+	assert_eq!(queue.queue(|_| ()), Err(QueueError::Closed));
+	assert_eq!(MainThreadQueue::drain(*l), 0);
+}
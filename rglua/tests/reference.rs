@@ -0,0 +1,77 @@
+// Tests for LuaRef and its typed variants, run against a stock LuaJIT like luajit.rs.
+use rglua::prelude::*;
+use rglua::testing::TestState;
+
+#[test]
+fn push_and_drop() {
+	let Some(l) = TestState::new() else { return };
+	lua_pushstring(*l, cstr!("pinned"));
+	let r = LuaRef::pop(*l);
+	assert_eq!(lua_gettop(*l), 0);
+	assert_eq!(r.type_of(), TSTRING);
+
+	let copy = r.clone();
+	assert_ne!(copy.id(), r.id());
+
+	r.push();
+	assert_eq!(String::from_lua(*l, -1).unwrap(), "pinned");
+	lua_pop(*l, 1);
+
+	// Freed references are reused
+	let (id, copy_id) = (r.id(), copy.id());
+	drop(r);
+	drop(copy);
+	lua_pushboolean(*l, 1);
+	let a = LuaRef::pop(*l);
+	lua_pushboolean(*l, 1);
+	let b = LuaRef::pop(*l);
+	assert_eq!((b.id(), a.id()), (id, copy_id));
+
+	lua_pushnil(*l);
+	assert_eq!(LuaRef::pop(*l).id(), REFNIL);
+}
+
+#[test]
+fn typed() {
+	let Some(l) = TestState::new() else { return };
+
+	l.exec("function add(a, b) return a + b end t = { x = 1 }").unwrap();
+
+	lua_getglobal(*l, cstr!("add"));
+	let add = LuaFunctionRef::from_lua(*l, -1).unwrap();
+	assert!(LuaTableRef::from_lua(*l, -1).is_err());
+	lua_pop(*l, 1);
+
+	assert_eq!(add.call::<_, f64>((1, 2)).unwrap(), 3.0);
+	assert!(matches!(add.call::<_, f64>(("a", 2)), Err(LuaError::Runtime { .. })));
+	assert!(matches!(add.call::<_, bool>((1, 2)), Err(LuaError::Returns(_))));
+
+	lua_getglobal(*l, cstr!("t"));
+	let t = LuaTableRef::new(*l, -1).unwrap();
+	lua_pop(*l, 1);
+
+	assert_eq!(t.get::<_, i32>("x").unwrap(), 1);
+	t.set("y", "two");
+	l.exec("assert(t.y == 'two')").unwrap();
+	assert_eq!(lua_gettop(*l), 0);
+}
+
+
+#[test]
+fn outlives_coroutine() {
+	let Some(l) = TestState::new() else { return };
+	assert_eq!(rglua::state::main_thread(*l), *l);
+
+	let co = lua_newthread(*l);
+	lua_pushstring(co, cstr!("from a coroutine"));
+	let r = LuaRef::pop(co);
+	assert_eq!(r.state(), *l);
+
+	// Nothing references the coroutine anymore
+	lua_pop(*l, 1);
+	l.exec("collectgarbage() collectgarbage()").unwrap();
+
+	r.push();
+	assert_eq!(String::from_lua(*l, -1).unwrap(), "from a coroutine");
+	lua_pop(*l, 1);
+}
@@ -0,0 +1,63 @@
+// Tests for functions registered with #[lua_function(name, lib)], run against a stock LuaJIT like luajit.rs.
+use rglua::prelude::*;
+use rglua::testing::TestState;
+
+#[lua_function(lib = "reg_test")]
+fn double(n: f64) -> f64 {
+	n * 2.0
+}
+
+#[lua_function(name = "triple", lib = "reg_test.nested")]
+fn times_three(n: f64) -> f64 {
+	n * 3.0
+}
+
+#[lua_function(name = "RegTestGlobal")]
+fn global(l: LuaState) -> i32 {
+	lua_pushstring(l, cstr!("global"));
+	1
+}
+
+// Same name in a different library is fine
+#[lua_function(name = "double", lib = "reg_test2")]
+fn double2(n: f64) -> f64 {
+	n * 2.0
+}
+
+// Caught when registering, as it's in another module
+mod other {
+	use rglua::prelude::*;
+
+	#[lua_function(name = "double", lib = "reg_test")]
+	fn double_again(n: f64) -> f64 {
+		n * 2.0
+	}
+}
+
+#[gmod_open]
+fn open(_l: LuaState) -> i32 {
+	0
+}
+
+#[test]
+fn registered() {
+	let names: Vec<_> = rglua::registry::functions().iter().map(|f| (f.lib, f.name)).collect();
+	assert!(names.contains(&(Some("reg_test"), "double")));
+	assert!(names.contains(&(None, "RegTestGlobal")));
+	assert_eq!(names.len(), 5);
+}
+
+#[test]
+fn gmod_open_registers() {
+	let Some(l) = TestState::new() else { return };
+	l.exec("ErrorNoHalt = function(msg) reported = msg end").unwrap();
+
+	assert_eq!(gmod13_open(*l), 0);
+	assert_eq!(lua_gettop(*l), 0);
+	l.exec("assert(reported:find('reg_test.double is registered more than once', 1, true), reported)").unwrap();
+
+	l.exec("assert(reg_test.double(2) == 4)").unwrap();
+	l.exec("assert(reg_test.nested.triple(2) == 6)").unwrap();
+	l.exec("assert(reg_test2.double(3) == 6)").unwrap();
+	l.exec("assert(RegTestGlobal() == 'global')").unwrap();
+}
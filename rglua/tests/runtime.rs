@@ -0,0 +1,167 @@
+// Tests for the async runtime and async lua functions, run against a stock LuaJIT like luajit.rs.
+// Ticks are driven by hand instead of by gmod's Think hook.
+use rglua::prelude::*;
+use rglua::runtime;
+use rglua::testing::TestState;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Pending on the first poll, like waiting on a worker thread for one tick.
+struct NextTick(bool);
+
+impl Future for NextTick {
+	type Output = ();
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+		if self.0 {
+			return Poll::Ready(());
+		}
+
+		self.0 = true;
+		cx.waker().wake_by_ref();
+		Poll::Pending
+	}
+}
+
+#[lua_function]
+async fn double(x: f64) -> f64 {
+	NextTick(false).await;
+	x * 2.0
+}
+
+#[lua_function]
+async fn fails(why: String) -> Result<(), std::io::Error> {
+	NextTick(false).await;
+	Err(std::io::Error::other(why))
+}
+
+#[lua_function]
+async fn set_later(l: LuaState, name: String) {
+	NextTick(false).await;
+	lua_pushboolean(l, 1);
+	lua_setfield(l, GLOBALSINDEX, to_cstr(&name).as_ptr());
+}
+
+fn to_cstr(s: &str) -> std::ffi::CString {
+	std::ffi::CString::new(s).unwrap()
+}
+
+fn setup() -> Option<TestState> {
+	let l = TestState::new()?;
+	lua_pushcfunction(*l, double);
+	lua_setglobal(*l, cstr!("double"));
+	lua_pushcfunction(*l, set_later);
+	lua_setglobal(*l, cstr!("set_later"));
+	lua_pushcfunction(*l, fails);
+	lua_setglobal(*l, cstr!("fails"));
+	Some(l)
+}
+
+fn global_number(l: LuaState, name: LuaString) -> f64 {
+	lua_getglobal(l, name);
+	let n = lua_tonumber(l, -1);
+	lua_pop(l, 1);
+	n
+}
+
+#[test]
+fn callback() {
+	let Some(l) = setup() else { return };
+
+	l.exec("double(21, function(n) got = n end)").unwrap();
+	assert_eq!(global_number(*l, cstr!("got")), 0.0);
+
+	assert_eq!(runtime::tick(*l), 1);
+	assert_eq!(runtime::tick(*l), 0);
+	assert_eq!(global_number(*l, cstr!("got")), 42.0);
+
+	// The callback is the last argument, even after extra ones
+	l.exec("double(5, 'extra', function(n) got = n end)").unwrap();
+	while runtime::tick(*l) > 0 {}
+	assert_eq!(global_number(*l, cstr!("got")), 10.0);
+}
+
+#[test]
+fn error() {
+	let Some(l) = setup() else { return };
+
+	l.exec("fails('nope', function(ok, err) got = tostring(ok) .. ' ' .. err end)").unwrap();
+	while runtime::tick(*l) > 0 {}
+
+	l.exec("assert(got == 'nil nope', got)").unwrap();
+}
+
+#[test]
+fn coroutine() {
+	let Some(l) = setup() else { return };
+
+	l.exec("co = coroutine.create(function(n) got = double(n) + double(1) end)\nassert(coroutine.resume(co, 4))").unwrap();
+	l.exec("assert(coroutine.status(co) == 'suspended')").unwrap();
+
+	while runtime::tick(*l) > 0 {}
+
+	assert_eq!(global_number(*l, cstr!("got")), 10.0);
+	l.exec("assert(coroutine.status(co) == 'dead')").unwrap();
+}
+
+#[test]
+fn needs_callback() {
+	let Some(l) = setup() else { return };
+
+	let e = l.exec("double(1)").unwrap_err();
+	assert!(e.contains("callback"), "{e}");
+	assert_eq!(runtime::tick(*l), 0);
+}
+
+#[test]
+fn install() {
+	let Some(l) = setup() else { return };
+
+	l.exec("hooks = {}\nhook = {\n\tAdd = function(event, name, f) hooks[event] = f end,\n\tRemove = function(event, name) hooks[event] = nil end\n}").unwrap();
+	runtime::install(*l).unwrap();
+
+	l.exec("double(2, function(n) got = n end)\nhooks.Think()\nhooks.Think()").unwrap();
+	assert_eq!(global_number(*l, cstr!("got")), 4.0);
+
+	l.exec("double(3, function(n) got = n end)").unwrap();
+	runtime::uninstall(*l);
+	l.exec("assert(hooks.Think == nil)").unwrap();
+	assert_eq!(runtime::tick(*l), 0);
+}
+
+#[test]
+fn outlives_caller() {
+	let Some(l) = setup() else { return };
+
+	// The coroutine is collected before the future uses the state
+	l.exec("coroutine.wrap(function() set_later('done', function() end) end)()\ncollectgarbage()\ncollectgarbage()").unwrap();
+	while runtime::tick(*l) > 0 {}
+
+	l.exec("assert(done)").unwrap();
+}
+
+#[test]
+fn lua_error_in_task() {
+	let Some(l) = setup() else { return };
+	let state = *l;
+
+	runtime::spawn(state, async move {
+		luaL_error(state, cstr!("broke"));
+	});
+	l.exec("double(2, function(n) got = n end)").unwrap();
+
+	// The error unwinds out of the tick, without losing the other task
+	lua_pushcfunction(state, tick);
+	assert_ne!(lua_pcall(state, 0, 0, 0), OK);
+	lua_pop(state, 1);
+
+	assert_eq!(runtime::tick(state), 1);
+	assert_eq!(runtime::tick(state), 0);
+	assert_eq!(global_number(state, cstr!("got")), 4.0);
+}
+
+#[lua_function]
+fn tick(l: LuaState) -> usize {
+	runtime::tick(l)
+}
@@ -0,0 +1,64 @@
+// Tests for Sandbox, run against a stock LuaJIT like luajit.rs.
+use rglua::prelude::*;
+use rglua::sandbox::{Preset, Sandbox};
+use rglua::testing::TestState;
+
+fn run(l: LuaState, env: &rglua::sandbox::Environment, code: &str) -> Result<(), LuaError> {
+	env.load(code.as_bytes(), "=sandboxed")?;
+	unsafe { Lua::from_raw(l) }.pcall(0, 0)
+}
+
+#[test]
+fn presets() {
+	let Some(l) = TestState::new() else { return };
+
+	assert_eq!("math+string".parse(), Ok(Preset::MathString));
+	assert!("everything".parse::<Preset>().is_err());
+
+	let pure = Sandbox::preset(Preset::Pure).build(*l);
+	run(*l, &pure, "assert(tostring(1) == '1')\nassert(math == nil and io == nil and require == nil)").unwrap();
+	run(*l, &pure, "assert(_G.pairs == pairs)").unwrap();
+
+	let env = Sandbox::preset(Preset::MathString).allow("os.time").build(*l);
+	run(*l, &env, "assert(math.floor(1.5) == 1)\nassert(os.time() and os.execute == nil)").unwrap();
+}
+
+#[test]
+fn copies() {
+	let Some(l) = TestState::new() else { return };
+
+	let env = Sandbox::preset(Preset::MathString).build(*l);
+	run(*l, &env, "math.floor = nil\nx = 5").unwrap();
+
+	// The originals are untouched, and globals stay in the environment
+	l.exec("assert(math.floor(1.5) == 1)\nassert(x == nil)").unwrap();
+	assert_eq!(env.table().get::<_, f64>("x"), Ok(5.0));
+}
+
+#[test]
+fn read_only() {
+	let Some(l) = TestState::new() else { return };
+
+	let env = Sandbox::preset(Preset::MathString).read_only(true).build(*l);
+	run(*l, &env, "assert(math.pi > 3)\nassert(getmetatable == nil)").unwrap();
+
+	for code in ["x = 5", "math.pi = 3", "_G.x = 5"] {
+		let err = run(*l, &env, code).unwrap_err();
+		assert!(err.message().contains("read-only"), "{code}: {err}");
+	}
+}
+
+#[test]
+fn no_bytecode() {
+	let Some(l) = TestState::new() else { return };
+
+	l.exec("bytecode = string.dump(function() return 1 end)").unwrap();
+	let lua = unsafe { Lua::from_raw(*l) };
+	lua.get_global("bytecode");
+	let code = lua.check_string(-1);
+	lua.pop(1);
+
+	let env = Sandbox::new().build(*l);
+	let err = env.load(code.as_bytes(), "=bytecode").unwrap_err();
+	assert!(matches!(err, LuaError::Syntax { .. }), "{err:?}");
+}
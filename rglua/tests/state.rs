@@ -0,0 +1,173 @@
+// Tests for the safe Lua handle, run against a stock LuaJIT like luajit.rs.
+use rglua::prelude::*;
+use rglua::testing::TestState;
+
+#[lua_function]
+fn concat_two(l: LuaState) -> i32 {
+	let lua = unsafe { Lua::from_raw(l) };
+	let guard = lua.guard();
+
+	let a = lua.check_string(1);
+	let b = lua.check_string(2);
+
+	lua.get_global("string");
+	lua.get_field(-1, "rep");
+	lua.push_string(&a);
+	lua.push_integer(2);
+	lua.call(2, 1);
+	lua.push_string(&b);
+	lua.push_bool(true);
+
+	// Leaves string, a, b on the stack but only returns the last two
+	guard.keep(2)
+}
+
+#[lua_function]
+fn guarded_error(l: LuaState) -> i32 {
+	let lua = unsafe { Lua::from_raw(l) };
+	let _guard = lua.guard();
+
+	lua.push_integer(1);
+	lua.push_integer(2);
+	lua.get_global("error");
+	lua.push_string("raised while guarded");
+	lua.push_integer(0);
+	lua.call(2, 0);
+	0
+}
+
+#[test]
+fn push_get() {
+	let Some(l) = TestState::new() else { return };
+	let lua = unsafe { Lua::from_raw(*l) };
+
+	lua.push_number(5.5);
+	lua.push_integer(7);
+	lua.push_bool(false);
+	lua.push_string("nul\0in here");
+	lua.push_nil();
+
+	assert_eq!(lua.top(), 5);
+	assert_eq!(lua.get_number(1), Some(5.5));
+	assert_eq!(lua.get_integer(2), Some(7));
+	assert!(!lua.get_bool(3));
+	assert_eq!(lua.get_string(4).as_deref(), Some("nul\0in here"));
+	assert_eq!(lua.get_number(5), None);
+	assert_eq!(lua.type_name(5), "nil");
+
+	// Reading a number as a string shouldn't convert it in place
+	assert_eq!(lua.get_string(2).as_deref(), Some("7"));
+	assert_eq!(lua.type_of(2), TNUMBER);
+}
+
+#[test]
+fn globals_and_calls() {
+	let Some(l) = TestState::new() else { return };
+	let lua = unsafe { Lua::from_raw(*l) };
+
+	lua.push_cfunction(concat_two);
+	lua.set_global("concat_two");
+	l.exec("local a, b = concat_two('ab', 'c') assert(a == 'c' and b == true, tostring(a))").unwrap();
+
+	lua.get_global("math");
+	lua.get_field(-1, "max");
+	lua.push_integer(2);
+	lua.push_integer(9);
+	lua.call(2, 1);
+	assert_eq!(lua.get_integer(-1), Some(9));
+	lua.set_top(0);
+
+	lua.get_global("error");
+	lua.push_string("oops");
+	lua.push_integer(0);
+	assert_eq!(lua.pcall(2, 0).unwrap_err().message(), "oops");
+	assert_eq!(lua.top(), 0);
+}
+
+#[test]
+fn guard() {
+	let Some(l) = TestState::new() else { return };
+	let lua = unsafe { Lua::from_raw(*l) };
+
+	lua.push_integer(1);
+	{
+		let _guard = lua.guard();
+		lua.push_integer(2);
+		lua.new_table(0, 0);
+	}
+	assert_eq!(lua.top(), 1);
+
+	let guard = lua.guard();
+	lua.push_integer(2);
+	lua.push_integer(3);
+	lua.push_integer(4);
+	assert_eq!(guard.keep(1), 1);
+	assert_eq!(lua.top(), 2);
+	assert_eq!(lua.get_integer(2), Some(4));
+}
+
+#[test]
+fn guard_raising() {
+	let Some(l) = TestState::new() else { return };
+
+	// The error raised inside the guard's scope gets through untouched
+	lua_pushcfunction(*l, guarded_error);
+	lua_setglobal(*l, cstr!("guarded_error"));
+	l.exec("local ok, err = pcall(guarded_error)\nassert(not ok and err == 'raised while guarded', tostring(err))").unwrap();
+
+	// and guards after it still restore the stack
+	let lua = unsafe { Lua::from_raw(*l) };
+	{
+		let _guard = lua.guard();
+		lua.push_integer(1);
+	}
+	assert_eq!(lua.top(), 0);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "Stack shrank")]
+fn guard_underflow() {
+	let Some(l) = TestState::new() else {
+		panic!("Stack shrank (skipped, LuaJIT isn't available)")
+	};
+	let lua = unsafe { Lua::from_raw(*l) };
+
+	lua.push_integer(1);
+	let _guard = lua.guard();
+	lua.pop(1);
+}
+
+#[test]
+fn protected() {
+	let Some(l) = TestState::new() else { return };
+	let lua = unsafe { Lua::from_raw(*l) };
+
+	lua.load_buffer(b"local function inner() error('deep') end\nfunction outer(n) inner() return n end", "=chunk", Some("t"))
+		.unwrap();
+	lua.call(0, 0);
+
+	lua.get_global("outer");
+	let err = lua.protected_call::<_, f64>(1).unwrap_err();
+	assert_eq!(lua.top(), 0);
+	assert_eq!(err.status(), ERRRUN);
+	assert_eq!(err.message(), "chunk:1: deep");
+
+	let frames = err.traceback();
+	assert_eq!(frames[0].source, "[C]");
+	assert_eq!(frames[0].context, "in function 'error'");
+	assert_eq!((frames[1].source.as_str(), frames[1].line), ("chunk", Some(1)));
+	assert_eq!(frames[2].to_string(), "chunk:2: in function <chunk:2>");
+
+	lua.load_string("function id(n) return n end").unwrap();
+	lua.call(0, 0);
+	lua.get_global("id");
+	assert_eq!(lua.protected_call::<_, f64>(5).unwrap(), 5.0);
+	lua.get_global("id");
+	assert!(matches!(lua.protected_call::<_, bool>(5), Err(LuaError::Returns(_))));
+
+	let err = lua.load_buffer(b"this isn't lua", "=bad", None).unwrap_err();
+	assert!(matches!(err, LuaError::Syntax { .. }), "{err:?}");
+	assert!(lua.load_buffer(b"\x1bLJ", "=bytecode", Some("t")).is_err());
+	assert_eq!(lua.top(), 0);
+}
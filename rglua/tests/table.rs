@@ -0,0 +1,69 @@
+// Tests for Table and table!, run against a stock LuaJIT like luajit.rs.
+use rglua::prelude::*;
+use rglua::testing::TestState;
+use std::collections::HashMap;
+
+#[test]
+fn get_set() {
+	let Some(l) = TestState::new() else { return };
+
+	let t = Table::create(*l, 0, 0);
+	t.set("a", 1);
+	t.raw_set(2, "two");
+	t.push(true);
+	assert_eq!(t.get::<_, i32>("a").unwrap(), 1);
+	assert_eq!(t.raw_get::<_, String>(2).unwrap(), "two");
+	assert!(t.raw_get_index::<bool>(1).unwrap());
+	assert_eq!(t.len(), 2);
+	assert!(t.get::<_, i32>("missing").is_err());
+	assert_eq!(lua_gettop(*l), 1);
+
+	lua_pushnumber(*l, 1.0);
+	assert!(Table::new(*l, -1).is_err());
+}
+
+#[test]
+fn iter() {
+	let Some(l) = TestState::new() else { return };
+
+	l.exec("t = { a = 1, b = 2, c = 3, 10, 20, 30 }").unwrap();
+	lua_getglobal(*l, cstr!("t"));
+	let t = Table::new(*l, -1).unwrap();
+
+	let seq: Vec<i32> = t.sequence().collect::<Result<_, _>>().unwrap();
+	assert_eq!(seq, [10, 20, 30]);
+
+	// Number keys convert to strings without breaking lua_next
+	let all: HashMap<String, i32> = t.iter().collect::<Result<_, _>>().unwrap();
+	assert_eq!(all.len(), 6);
+	assert_eq!(all["1"], 10);
+	assert_eq!(all["b"], 2);
+
+	// Mixed key types error per pair
+	assert_eq!(t.iter::<bool, i32>().filter(|p| p.is_err()).count(), 6);
+
+	// Stack stays balanced when stopping early
+	assert!(t.iter::<String, i32>().next().is_some());
+	assert_eq!(lua_gettop(*l), 1);
+}
+
+#[test]
+fn builder() {
+	let Some(l) = TestState::new() else { return };
+
+	let name = String::from("rglua");
+	table! {
+		"name" => name.as_str(),
+		"pos" => Vector::new(1.0, 2.0, 3.0),
+		"list" => table![1, 2, 3],
+		"empty" => table! {}
+	}
+	.push_to_lua(*l);
+	lua_setglobal(*l, cstr!("t"));
+
+	l.exec("assert(t.name == 'rglua' and type(t.pos) == 'userdata' and #t.list == 3 and t.list[3] == 3 and next(t.empty) == nil)").unwrap();
+
+	let t = table!["a", "b"].build(*l);
+	assert_eq!(t.len(), 2);
+	assert_eq!(lua_gettop(*l), 1);
+}
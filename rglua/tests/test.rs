@@ -19,3 +19,24 @@ fn rstr_test() {
 	let b = rstr!(a);
 	assert_eq!(b, "How are you?");
 }
+
+#[test]
+fn lua_shared_not_found() {
+	use rglua::lua::{LuaSharedError, LuaSharedLoader};
+
+	let err = LuaSharedLoader::new()
+		.path("does/not/exist/lua_shared.so")
+		.env(None)
+		.reuse_loaded(false)
+		.search_defaults(false)
+		.open()
+		.unwrap_err();
+
+	match err {
+		LuaSharedError::NotFound(ref tried) => {
+			assert_eq!(tried.len(), 1);
+			assert!(err.to_string().contains("does/not/exist/lua_shared.so"));
+		}
+		other => panic!("Expected NotFound, got {other}")
+	}
+}
@@ -0,0 +1,75 @@
+// Tests for Thread, run against a stock LuaJIT like luajit.rs.
+use rglua::prelude::*;
+use rglua::testing::TestState;
+use rglua::thread::{Resumed, Thread, ThreadStatus};
+
+#[test]
+fn resume() {
+	let Some(l) = TestState::new() else { return };
+
+	l.exec("function counter(start)\n\tlocal got = coroutine.yield(start)\n\tlocal last = coroutine.yield(start + got)\n\treturn 'done', last\nend").unwrap();
+	lua_getglobal(*l, cstr!("counter"));
+	let thread = Thread::new(*l, -1).unwrap();
+	lua_pop(*l, 1);
+
+	assert_eq!(thread.status(), ThreadStatus::Suspended);
+	assert_eq!(thread.resume::<_, f64>(1.0), Resumed::Yielded(1.0));
+	assert_eq!(thread.resume::<_, f64>(5.0), Resumed::Yielded(6.0));
+	assert_eq!(
+		thread.resume::<_, (String, bool)>(true),
+		Resumed::Finished((String::from("done"), true))
+	);
+
+	assert_eq!(thread.status(), ThreadStatus::Dead);
+	assert!(matches!(thread.resume::<_, ()>(()), Resumed::Error(e) if e.message().contains("dead")));
+}
+
+#[test]
+fn error() {
+	let Some(l) = TestState::new() else { return };
+
+	l.exec("function fails()\n\tcoroutine.yield()\n\terror('broke')\nend").unwrap();
+	lua_getglobal(*l, cstr!("fails"));
+	let thread = Thread::new(*l, -1).unwrap();
+	lua_pop(*l, 1);
+
+	let top = lua_gettop(*l);
+	assert_eq!(thread.resume::<_, ()>(()), Resumed::Yielded(()));
+
+	let Resumed::Error(e) = thread.resume::<_, ()>(()) else { panic!("didn't error") };
+	assert_eq!(e.message(), "exec:3: broke");
+	assert!(e.traceback().iter().any(|f| f.source == "exec" && f.line == Some(3)), "{:?}", e.traceback());
+	assert_eq!(thread.status(), ThreadStatus::Dead);
+	assert_eq!(lua_gettop(*l), top);
+}
+
+#[test]
+fn outlives_creator() {
+	let Some(l) = TestState::new() else { return };
+
+	// Created from a coroutine that's collected before it errors
+	let co = lua_newthread(*l);
+	luaL_loadstring(co, cstr!("error('broke')"));
+	let thread = Thread::new(co, -1).unwrap();
+	lua_pop(*l, 1);
+	l.exec("collectgarbage() collectgarbage()").unwrap();
+
+	let top = lua_gettop(*l);
+	let Resumed::Error(e) = thread.resume::<_, ()>(()) else { panic!("didn't error") };
+	assert!(e.message().ends_with("broke"), "{}", e.message());
+	assert_eq!(lua_gettop(*l), top);
+}
+
+#[test]
+fn iter() {
+	let Some(l) = TestState::new() else { return };
+
+	l.exec("gen = coroutine.create(function()\n\tfor i = 1, 3 do coroutine.yield(i, i * i) end\n\treturn 'ignored'\nend)").unwrap();
+	lua_getglobal(*l, cstr!("gen"));
+	let thread = Thread::from_thread(*l, -1).unwrap();
+	lua_pop(*l, 1);
+
+	let pairs: Vec<(f64, f64)> = thread.iter().collect::<Result<_, _>>().unwrap();
+	assert_eq!(pairs, [(1.0, 1.0), (2.0, 4.0), (3.0, 9.0)]);
+	assert_eq!(thread.status(), ThreadStatus::Dead);
+}
@@ -0,0 +1,120 @@
+// Tests for #[derive(LuaUserData)] and #[lua_methods], run against a stock LuaJIT like luajit.rs.
+use rglua::prelude::*;
+use rglua::testing::TestState;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, LuaUserData)]
+#[lua(name = "Point")]
+struct Point {
+	x: f64,
+	y: f64
+}
+
+#[lua_methods]
+impl Point {
+	fn new(x: f64, y: f64) -> Self {
+		Self { x, y }
+	}
+
+	fn x(&self) -> f64 {
+		self.x
+	}
+
+	fn translate(&mut self, x: f64, y: f64) {
+		self.x += x;
+		self.y += y;
+	}
+
+	fn copy_from(&mut self, other: &Self) {
+		self.x = other.x;
+		self.y = other.y;
+	}
+
+	#[lua(name = "__add")]
+	fn add(&self, other: &Self) -> Self {
+		Self::new(self.x + other.x, self.y + other.y)
+	}
+
+	#[lua(name = "__tostring")]
+	fn display(&self) -> String {
+		format!("Point({}, {})", self.x, self.y)
+	}
+
+	#[lua(skip)]
+	#[allow(dead_code)]
+	fn hidden(&self) {}
+}
+
+#[derive(LuaUserData)]
+struct Tracked;
+
+impl Drop for Tracked {
+	fn drop(&mut self) {
+		DROPPED.fetch_add(1, Ordering::SeqCst);
+	}
+}
+
+#[lua_function]
+fn new_point(x: f64, y: f64) -> Point {
+	Point::new(x, y)
+}
+
+#[lua_function]
+fn point_y(p: &Point) -> f64 {
+	p.y
+}
+
+#[test]
+fn methods() {
+	let Some(l) = TestState::new() else { return };
+
+	lua_pushcfunction(*l, new_point);
+	lua_setglobal(*l, cstr!("Point"));
+	lua_pushcfunction(*l, point_y);
+	lua_setglobal(*l, cstr!("point_y"));
+
+	l.exec("p = Point(1, 2)").unwrap();
+	l.exec("assert(p:x() == 1 and point_y(p) == 2)").unwrap();
+	l.exec("p:translate(1, 1) assert(p:x() == 2 and point_y(p) == 3)").unwrap();
+	l.exec("assert(tostring(p + Point(1, 1)) == 'Point(3, 4)')").unwrap();
+	l.exec("assert(p.hidden == nil and p.new == nil)").unwrap();
+
+	let err = l.exec("point_y({})").unwrap_err();
+	assert!(err.contains("Point expected, got table"), "{err}");
+	let err = l.exec("p.x({})").unwrap_err();
+	assert!(err.contains("Point expected, got table"), "{err}");
+
+	l.exec("p:copy_from(Point(2, 3))").unwrap();
+	let err = l.exec("p:copy_from(p)").unwrap_err();
+	assert!(err.contains("bad argument #1 to 'copy_from' (Point expected, got Point already borrowed)"), "{err}");
+	// The borrow of self ended with the error
+	l.exec("p:translate(0, 0)").unwrap();
+
+	lua_getglobal(*l, cstr!("p"));
+	// Still on the stack while borrowed
+	unsafe {
+		assert_eq!(Point::check(*l, -1).borrow().x, 2.0);
+		assert!(Point::test(*l, -1).is_some());
+		assert!(Tracked::test(*l, -1).is_none());
+	}
+	lua_pop(*l, 1);
+}
+
+#[test]
+fn gc() {
+	let Some(l) = TestState::new() else { return };
+
+	Tracked.push_to_lua(*l);
+	lua_getglobal(*l, cstr!("tostring"));
+	lua_pushvalue(*l, 1);
+	lua_call(*l, 1, 1);
+	let s = String::from_lua(*l, -1).unwrap();
+	assert!(s.starts_with("Tracked: 0x"), "{s}");
+
+	let before = DROPPED.load(Ordering::SeqCst);
+	lua_settop(*l, 0);
+	l.exec("collectgarbage() collectgarbage()").unwrap();
+	assert_eq!(DROPPED.load(Ordering::SeqCst), before + 1);
+}